@@ -45,3 +45,196 @@ mod construction {
 
 
 
+
+mod read_exact {
+    use super::test;
+
+    use BufReader;
+
+    use std::io::{self, Read};
+
+    fn read_exact_all<R: Read>(mut rdr: R) {
+        let mut buf = [0u8; 8];
+        while rdr.read_exact(&mut buf).is_ok() {}
+    }
+
+    #[bench]
+    fn bufreader_small(b: &mut test::Bencher) {
+        let data = vec![0u8; 64 * 1024];
+
+        b.iter(|| read_exact_all(BufReader::new(&data[..])));
+    }
+
+    #[bench]
+    fn std_bufreader_small(b: &mut test::Bencher) {
+        let data = vec![0u8; 64 * 1024];
+
+        b.iter(|| read_exact_all(io::BufReader::new(&data[..])));
+    }
+}
+
+mod fill_full {
+    use super::test;
+
+    use BufReader;
+
+    use std::io::{self, BufRead, Read};
+
+    /// Returns at most 300 bytes per read, like a pipe fed by small writes.
+    struct Dribble<'a>(&'a [u8]);
+
+    impl<'a> Read for Dribble<'a> {
+        fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+            let len = self.0.len().min(300);
+            let read = (&self.0[..len]).read(out)?;
+            self.0 = &self.0[read..];
+            Ok(read)
+        }
+    }
+
+    fn fill_all(mut rdr: BufReader<Dribble>) {
+        loop {
+            let len = test::black_box(rdr.fill_buf().unwrap()).len();
+            if len == 0 { break; }
+            rdr.consume(len);
+        }
+    }
+
+    #[bench]
+    fn single_read(b: &mut test::Bencher) {
+        let data = vec![0u8; 1024 * 1024];
+
+        b.iter(|| fill_all(BufReader::new(Dribble(&data))));
+    }
+
+    #[bench]
+    fn fill_full(b: &mut test::Bencher) {
+        let data = vec![0u8; 1024 * 1024];
+
+        b.iter(|| {
+            let mut rdr = BufReader::new(Dribble(&data));
+            rdr.set_fill_full(true);
+            fill_all(rdr)
+        });
+    }
+}
+
+mod bytes {
+    use super::test;
+
+    use BufReader;
+
+    use std::io::Read;
+
+    #[bench]
+    fn bytes_buffered(b: &mut test::Bencher) {
+        let data = vec![1u8; 64 * 1024];
+
+        b.iter(|| {
+            let mut rdr = BufReader::new(&data[..]);
+            rdr.bytes_buffered().fold(0u64, |sum, byte| sum + byte.unwrap() as u64)
+        });
+    }
+
+    #[bench]
+    fn read_bytes(b: &mut test::Bencher) {
+        let data = vec![1u8; 64 * 1024];
+
+        b.iter(|| {
+            BufReader::new(&data[..]).bytes().fold(0u64, |sum, byte| sum + byte.unwrap() as u64)
+        });
+    }
+
+    #[bench]
+    fn slice_iter(b: &mut test::Bencher) {
+        let data = vec![1u8; 64 * 1024];
+
+        b.iter(|| test::black_box(&data[..]).iter().fold(0u64, |sum, &byte| sum + byte as u64));
+    }
+}
+
+mod lines {
+    use super::test;
+
+    use BufReader;
+
+    use std::io::BufRead;
+
+    fn log_lines() -> Vec<u8> {
+        let mut data = Vec::new();
+
+        for i in 0 .. 4096 {
+            data.extend_from_slice(format!("{} some log line of moderate length\n", i).as_bytes());
+        }
+
+        data
+    }
+
+    #[bench]
+    fn for_each_line(b: &mut test::Bencher) {
+        let data = log_lines();
+
+        b.iter(|| {
+            let mut total = 0;
+            BufReader::new(&data[..]).for_each_line(|line| { total += line.len(); Ok(()) })
+                .unwrap();
+            total
+        });
+    }
+
+    #[bench]
+    fn lines(b: &mut test::Bencher) {
+        let data = log_lines();
+
+        b.iter(|| BufReader::new(&data[..]).lines().map(|line| line.unwrap().len()).sum::<usize>());
+    }
+}
+
+mod reserve {
+    use super::test;
+
+    use Buffer;
+
+    #[bench]
+    fn reserve_small_repeated(b: &mut test::Bencher) {
+        b.iter(|| {
+            let mut buffer = Buffer::with_capacity(64 * 1024);
+
+            for _ in 0 .. 16 {
+                let cap = buffer.capacity();
+                buffer.reserve(cap + 1);
+            }
+
+            test::black_box(buffer.capacity())
+        });
+    }
+}
+
+mod make_room {
+    use super::test;
+
+    use Buffer;
+
+    fn large_residue(b: &mut test::Bencher, consume: usize) {
+        let data = vec![0u8; 1024 * 1024];
+        let mut buffer = Buffer::with_capacity(data.len());
+
+        b.iter(|| {
+            buffer.clear();
+            buffer.copy_from_slice(&data);
+            buffer.consume(consume);
+            buffer.make_room();
+            test::black_box(buffer.len())
+        });
+    }
+
+    #[bench]
+    fn overlapping(b: &mut test::Bencher) {
+        large_residue(b, 256 * 1024);
+    }
+
+    #[bench]
+    fn nonoverlapping(b: &mut test::Bencher) {
+        large_residue(b, 512 * 1024);
+    }
+}
@@ -9,17 +9,27 @@
 
 mod std_buf;
 
-#[cfg(feature = "slice-deque")]
+#[cfg(all(feature = "slice-deque", any(unix, windows)))]
 mod slice_deque_buf;
 
 use self::std_buf::StdBuf;
 
-#[cfg(feature = "slice-deque")]
+pub use self::std_buf::AllocBuf;
+
+use self::std_buf::wipe;
+
+use std::mem::MaybeUninit;
+use std::sync::Arc;
+
+use pool::PoolShared;
+use BufStorage;
+
+#[cfg(all(feature = "slice-deque", any(unix, windows)))]
 use self::slice_deque_buf::SliceDequeBuf;
 
 pub enum BufImpl {
     Std(StdBuf),
-    #[cfg(feature = "slice-deque")]
+    #[cfg(all(feature = "slice-deque", any(unix, windows)))]
     Ringbuf(SliceDequeBuf),
 }
 
@@ -28,7 +38,7 @@ macro_rules! forward_method {
         pub fn $fnname(&self $($args)*) $(-> $ret)* {
             match *self {
                 BufImpl::Std(ref buf) => buf.$fnname($($passargs)*),
-                #[cfg(feature = "slice-deque")]
+                #[cfg(all(feature = "slice-deque", any(unix, windows)))]
                 BufImpl::Ringbuf(ref buf) => buf.$fnname($($passargs)*),
             }
         }
@@ -38,7 +48,7 @@ macro_rules! forward_method {
         pub fn $fnname(&mut self $($args)*) $(-> $ret)* {
             match *self {
                 BufImpl::Std(ref mut buf) => buf.$fnname($($passargs)*),
-                #[cfg(feature = "slice-deque")]
+                #[cfg(all(feature = "slice-deque", any(unix, windows)))]
                 BufImpl::Ringbuf(ref mut buf) => buf.$fnname($($passargs)*),
             }
         }
@@ -48,7 +58,7 @@ macro_rules! forward_method {
         pub unsafe fn $fnname(&self $($args)*) $(-> $ret)* {
             match *self {
                 BufImpl::Std(ref buf) => buf.$fnname($($passargs)*),
-                #[cfg(feature = "slice-deque")]
+                #[cfg(all(feature = "slice-deque", any(unix, windows)))]
                 BufImpl::Ringbuf(ref buf) => buf.$fnname($($passargs)*),
             }
         }
@@ -58,7 +68,7 @@ macro_rules! forward_method {
         pub unsafe fn $fnname(&mut self $($args)*) $(-> $ret)* {
             match *self {
                 BufImpl::Std(ref mut buf) => buf.$fnname($($passargs)*),
-                #[cfg(feature = "slice-deque")]
+                #[cfg(all(feature = "slice-deque", any(unix, windows)))]
                 BufImpl::Ringbuf(ref mut buf) => buf.$fnname($($passargs)*),
             }
         }
@@ -78,14 +88,70 @@ impl BufImpl {
         BufImpl::Std(StdBuf::with_capacity(cap))
     }
 
-    #[cfg(feature = "slice-deque")]
+    pub fn with_capacity_aligned(cap: usize, align: usize) -> Self {
+        BufImpl::Std(StdBuf::with_capacity_aligned(cap, align))
+    }
+
+    pub fn from_pool(buf: AllocBuf, pool: Arc<PoolShared>) -> Self {
+        BufImpl::Std(StdBuf::from_pool(buf, pool))
+    }
+
+    /// Stop this buffer from being returned to the `BufferPool` it came from, if any.
+    pub fn detach_pool(&mut self) {
+        if let BufImpl::Std(ref mut buf) = *self {
+            buf.detach_pool();
+        }
+    }
+
+    pub fn from_vec(vec: Vec<u8>, pos: usize, end: usize) -> Self {
+        BufImpl::Std(StdBuf::from_vec(vec, pos, end))
+    }
+
+    /// See `StdBuf::into_vec()`.
+    pub fn into_vec(self, untruncated: bool) -> (Vec<u8>, usize) {
+        match self {
+            BufImpl::Std(buf) => buf.into_vec(untruncated),
+            #[cfg(all(feature = "slice-deque", any(unix, windows)))]
+            BufImpl::Ringbuf(buf) => {
+                let mut vec = Vec::with_capacity(buf.capacity());
+                vec.extend_from_slice(buf.buf());
+                let len = vec.len();
+
+                if untruncated {
+                    vec.resize(buf.capacity(), 0);
+                }
+
+                (vec, len)
+            },
+        }
+    }
+
+    /// Create an empty buffer of the same kind as this one; its capacity is zero if possible.
+    pub fn empty_like(&self) -> Self {
+        match *self {
+            BufImpl::Std(ref buf) => BufImpl::Std(buf.empty_like()),
+            #[cfg(all(feature = "slice-deque", any(unix, windows)))]
+            BufImpl::Ringbuf(ref buf) => BufImpl::with_capacity_ringbuf(buf.capacity()),
+        }
+    }
+
+    pub fn with_storage(storage: Box<dyn BufStorage>) -> Self {
+        BufImpl::Std(StdBuf::with_storage(storage))
+    }
+
+    #[cfg(all(feature = "slice-deque", any(unix, windows)))]
     pub fn with_capacity_ringbuf(cap: usize) -> Self {
         BufImpl::Ringbuf(SliceDequeBuf::with_capacity(cap))
     }
 
+    #[cfg(not(all(feature = "slice-deque", any(unix, windows))))]
+    pub fn with_capacity_ringbuf(cap: usize) -> Self {
+        BufImpl::with_capacity(cap)
+    }
+
     pub fn is_ringbuf(&self) -> bool {
         match *self {
-            #[cfg(feature = "slice-deque")]
+            #[cfg(all(feature = "slice-deque", any(unix, windows)))]
             BufImpl::Ringbuf(_) => true,
             _ => false,
         }
@@ -102,14 +168,28 @@ impl BufImpl {
 
         pub fn make_room(&mut self)[];
 
+        pub fn shrink(&mut self, new_cap: usize)[new_cap] -> bool;
+
         pub fn buf(&self)[] -> &[u8];
 
         pub fn buf_mut(&mut self)[] -> &mut [u8];
 
         pub unsafe fn write_buf(&mut self)[] -> &mut [u8];
 
+        pub unsafe fn write_buf_uninit(&mut self)[] -> &mut [MaybeUninit<u8>];
+
         pub unsafe fn bytes_written(&mut self, add: usize)[add];
 
         pub fn consume(&mut self, amt: usize)[amt];
+
+        pub fn unconsume(&mut self, amt: usize)[amt] -> usize;
+
+        pub fn clear(&mut self)[];
+
+        pub fn set_retain(&mut self, retain: usize)[retain];
+
+        pub fn zeroize(&self)[] -> bool;
+
+        pub fn set_zeroize(&mut self, zeroize: bool)[zeroize];
     }
 }
@@ -14,10 +14,22 @@
 extern crate slice_deque;
 use self::slice_deque::SliceDeque;
 
-use std::cmp;
+use std::mem::MaybeUninit;
+use std::{cmp, ptr, slice};
+
+use super::wipe;
 
 pub struct SliceDequeBuf {
     deque: SliceDeque<u8>,
+    /// The number of consumed bytes before the head which have not been overwritten yet.
+    history: usize,
+    /// The number of consumed bytes before the head which must not be overwritten.
+    retain: usize,
+    /// If the whole allocation has been initialized; the free space of a new allocation is not
+    /// zeroed until it is first written to.
+    initialized: bool,
+    /// Wipe the memory before it is freed.
+    zeroize: bool,
 }
 
 /// Move-free buffer utilizing the [`slice-deque`] crate.
@@ -30,6 +42,10 @@ impl SliceDequeBuf {
     pub fn with_capacity(cap: usize) -> Self {
         SliceDequeBuf {
             deque: SliceDeque::with_capacity(cap),
+            history: 0,
+            retain: 0,
+            initialized: false,
+            zeroize: false,
         }
     }
 
@@ -40,14 +56,83 @@ impl SliceDequeBuf {
     pub fn len(&self) -> usize { self.deque.len() }
 
     pub fn usable_space(&self) -> usize {
-        self.capacity() - self.len()
+        self.capacity() - self.len() - self.retained()
     }
 
     pub fn reserve(&mut self, additional: usize) -> bool {
-        self.deque.reserve(additional);
+        let cap = self.capacity();
+
+        // only the bytes in the deque are copied to the new allocation,
+        // so temporarily restore the retained ones
+        let retained = self.retained();
+
+        if self.zeroize {
+            // `SliceDeque::reserve()` frees the old memory without wiping it
+            let new_cap = self.len() + retained + additional;
+            if new_cap > cap { self.realloc(new_cap); }
+            return true;
+        }
+
+        unsafe {
+            self.deque.move_head(-(retained as isize));
+            self.deque.reserve(additional);
+            self.deque.move_head(retained as isize);
+        }
+
+        if self.capacity() != cap {
+            self.history = retained;
+            self.initialized = false;
+        }
+
+        true
+    }
+
+    pub fn shrink(&mut self, new_cap: usize) -> bool {
+        if new_cap >= self.capacity() { return false; }
+
+        // don't discard any buffered or retained bytes
+        let retained = self.retained();
+        let new_cap = cmp::max(new_cap, retained + self.len());
+
+        // the capacity is rounded up to the page size so this may not be any smaller
+        if SliceDeque::<u8>::with_capacity(new_cap).capacity() >= self.capacity() { return false; }
+
+        self.realloc(new_cap);
         true
     }
 
+    // move the buffered and retained bytes to a new allocation of *at least* `new_cap` bytes
+    fn realloc(&mut self, new_cap: usize) {
+        let retained = self.retained();
+        let mut deque = SliceDeque::with_capacity(new_cap);
+
+        unsafe {
+            self.deque.move_head(-(retained as isize));
+            deque.extend_from_slice(&self.deque);
+            deque.move_head(retained as isize);
+        }
+
+        if self.zeroize { self.wipe(); }
+
+        self.deque = deque;
+        self.history = retained;
+        self.initialized = false;
+    }
+
+    pub fn zeroize(&self) -> bool {
+        self.zeroize
+    }
+
+    pub fn set_zeroize(&mut self, zeroize: bool) {
+        self.zeroize = zeroize;
+    }
+
+    // overwrite the whole allocation with zeroes; together the data and the free space cover it
+    fn wipe(&mut self) {
+        wipe(&mut self.deque);
+        wipe(unsafe { self.deque.tail_head_slice() });
+    }
+
     /// This method is a no-op.
     pub fn make_room(&mut self) {}
 
@@ -56,7 +141,23 @@ impl SliceDequeBuf {
     pub fn buf_mut(&mut self) -> &mut [u8] { &mut self.deque }
 
     pub unsafe fn write_buf(&mut self) -> &mut [u8] {
-        self.deque.tail_head_slice()
+        // everything but the free space is initialized, and once that has been zeroed
+        // the bytes consumed from the head stay initialized as they wrap around
+        if !self.initialized {
+            let free = self.deque.tail_head_slice();
+            ptr::write_bytes(free.as_mut_ptr(), 0, free.len());
+            self.initialized = true;
+        }
+
+        let usable = self.usable_space();
+        &mut self.deque.tail_head_slice()[..usable]
+    }
+
+    /// Get the free space; `slice_deque` only exposes it as initialized memory, so it's zeroed
+    /// like for `.write_buf()`, which only happens once.
+    pub unsafe fn write_buf_uninit(&mut self) -> &mut [MaybeUninit<u8>] {
+        let buf = self.write_buf();
+        slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut MaybeUninit<u8>, buf.len())
     }
 
     pub unsafe fn bytes_written(&mut self, add: usize) {
@@ -67,11 +168,16 @@ impl SliceDequeBuf {
         }
 
         self.deque.move_tail(offset);
+
+        // the tail may have wrapped around into the consumed bytes
+        self.history = cmp::min(self.history, self.capacity() - self.len());
     }
 
     pub fn consume(&mut self, amt: usize) {
+        let amt = cmp::min(amt, self.len());
+
         unsafe {
-            let offset = cmp::min(amt, self.len()) as isize;
+            let offset = amt as isize;
 
             if offset < 0 {
                 panic!("BufImpl.consume() arg overflowed isize: {:x}", amt)
@@ -79,5 +185,40 @@ impl SliceDequeBuf {
 
             self.deque.move_head(offset);
         }
+
+        self.history = cmp::min(self.history + amt, self.capacity() - self.len());
+    }
+
+    pub fn unconsume(&mut self, amt: usize) -> usize {
+        let amt = cmp::min(amt, self.history);
+
+        unsafe {
+            self.deque.move_head(-(amt as isize));
+        }
+
+        self.history -= amt;
+        amt
+    }
+
+    pub fn clear(&mut self) {
+        let len = self.len();
+        self.consume(len);
+        self.history = 0;
+    }
+
+    pub fn set_retain(&mut self, retain: usize) {
+        self.retain = retain;
+    }
+
+    fn retained(&self) -> usize {
+        cmp::min(self.retain, self.history)
+    }
+}
+
+impl Drop for SliceDequeBuf {
+    fn drop(&mut self) {
+        if self.zeroize {
+            self.wipe();
+        }
     }
 }
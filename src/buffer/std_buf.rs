@@ -6,27 +6,134 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use safemem;
+use std::mem::MaybeUninit;
+use std::{cmp, mem, ptr};
 
-use std::cmp;
+use std::sync::atomic::{self, Ordering};
+use std::sync::Arc;
 
 use self::impl_::RawBuf;
 
+pub use self::impl_::AllocBuf;
+
+use pool::PoolShared;
+use BufStorage;
+
 pub struct StdBuf {
     buf: RawBuf,
     pos: usize,
     end: usize,
+    /// The number of consumed bytes before `pos` which must not be discarded.
+    retain: usize,
+    /// Wipe memory before it is freed and data left behind when it is moved.
+    zeroize: bool,
 }
 
+/// With an alignment other than 1, the tail cursor is kept at a multiple of it whenever the buffer
+/// is written to, moving the data if necessary, so reads into the buffer are always aligned.
 impl StdBuf {
     pub fn with_capacity(cap: usize) -> Self {
+        Self::with_capacity_aligned(cap, 1)
+    }
+
+    pub fn with_capacity_aligned(cap: usize, align: usize) -> Self {
+        StdBuf {
+            buf: RawBuf::with_capacity_aligned(cap, align),
+            pos: 0,
+            end: 0,
+            retain: 0,
+            zeroize: false,
+        }
+    }
+
+    pub fn from_pool(buf: AllocBuf, pool: Arc<PoolShared>) -> Self {
+        StdBuf {
+            buf: RawBuf::Alloc(buf, Some(pool)),
+            pos: 0,
+            end: 0,
+            retain: 0,
+            zeroize: false,
+        }
+    }
+
+    pub fn detach_pool(&mut self) {
+        self.buf.detach_pool();
+    }
+
+    pub fn with_storage(storage: Box<dyn BufStorage>) -> Self {
         StdBuf {
-            buf: RawBuf::with_capacity(cap),
+            buf: RawBuf::Custom(storage),
             pos: 0,
             end: 0,
+            retain: 0,
+            zeroize: false,
         }
     }
 
+    /// Take ownership of `vec`, with `vec[pos .. end]` being the buffered data.
+    pub fn from_vec(vec: Vec<u8>, pos: usize, end: usize) -> Self {
+        assert!(pos <= end && end <= vec.len());
+
+        StdBuf {
+            buf: RawBuf::Alloc(AllocBuf::from_vec(vec), None),
+            pos,
+            end,
+            retain: 0,
+            zeroize: false,
+        }
+    }
+
+    /// Convert into a `Vec` with the buffered data at the front and the capacity of the buffer,
+    /// without copying if possible, also returning the length of the data.
+    ///
+    /// If `untruncated`, the length of the `Vec` is its capacity instead of the length of the data.
+    pub fn into_vec(mut self, untruncated: bool) -> (Vec<u8>, usize) {
+        self.retain = 0;
+        self.make_room();
+
+        let len = self.len();
+
+        if untruncated {
+            self.buf.initialize();
+        }
+
+        let vec_len = if untruncated { self.capacity() } else { len };
+
+        if let RawBuf::Alloc(ref mut buf, ref mut pool) = self.buf {
+            // the data was moved to the front if the buffer isn't aligned
+            let alloc = mem::replace(buf, AllocBuf::with_capacity_aligned(0, 1));
+
+            match alloc.into_vec(vec_len) {
+                Ok(vec) => {
+                    // it's ours now
+                    *pool = None;
+                    return (vec, len);
+                },
+                Err(alloc) => *buf = alloc,
+            }
+        }
+
+        let mut vec = Vec::with_capacity(self.capacity());
+        vec.extend_from_slice(self.buf());
+        vec.resize(vec_len, 0);
+        (vec, len)
+    }
+
+    /// Create an empty buffer with the same alignment and zeroizing setting as this one.
+    pub fn empty_like(&self) -> Self {
+        let mut buf = Self::with_capacity_aligned(0, self.buf.align());
+        buf.zeroize = self.zeroize;
+        buf
+    }
+
+    pub fn zeroize(&self) -> bool {
+        self.zeroize
+    }
+
+    pub fn set_zeroize(&mut self, zeroize: bool) {
+        self.zeroize = zeroize;
+    }
+
     pub fn capacity(&self) -> usize {
         self.buf.capacity()
     }
@@ -36,7 +143,21 @@ impl StdBuf {
     }
 
     pub fn usable_space(&self) -> usize {
-        self.capacity() - self.end
+        self.capacity() - self.write_start()
+    }
+
+    // where the next write into the buffer will start: the cursors are reset before it
+    // if the buffer is empty, and the data is moved to keep the tail aligned
+    fn write_start(&self) -> usize {
+        let align = self.buf.align();
+
+        if self.pos == self.end {
+            round_up(self.retained(), align)
+        } else if self.end.is_multiple_of(align) {
+            self.end
+        } else {
+            round_up(self.end - self.start(), align)
+        }
     }
 
     pub fn reserve(&mut self, additional: usize) -> bool {
@@ -51,32 +172,76 @@ impl StdBuf {
             return false;
         }
 
-        // don't copy the contents of the buffer as they're irrelevant now
-        if self.pos == self.end {
-            let capacity = self.buf.capacity();
-            // free the existing memory
-            self.buf = RawBuf::with_capacity(0);
-            self.buf = RawBuf::with_capacity(capacity + additional);
-            return true;
+        if self.zeroize {
+            let new_cap = self.capacity() + additional - usable_space;
+            return self.buf.resize_wiped(new_cap, self.end);
+        }
+
+        // don't copy the contents of the buffer as they're irrelevant now;
+        // the whole new allocation is usable, so it only needs to be `additional` bytes
+        if self.end == 0 {
+            return self.buf.reserve_discard(additional - usable_space);
         }
 
         self.buf.reserve(additional - usable_space)
     }
 
+    pub fn shrink(&mut self, new_cap: usize) -> bool {
+        if new_cap >= self.capacity() { return false; }
+
+        self.make_room();
+
+        // don't discard any buffered or retained bytes
+        let new_cap = cmp::max(new_cap, self.end);
+        if new_cap >= self.capacity() { return false; }
+
+        if self.zeroize {
+            self.buf.resize_wiped(new_cap, self.end);
+        } else {
+            self.buf.shrink(new_cap);
+        }
+
+        true
+    }
+
     pub fn make_room(&mut self) {
-        self.check_cursors();
+        if !self.check_cursors() {
+            self.compact();
+        }
+    }
+
+    // move the data and retained bytes down to the beginning of the buffer,
+    // or just far enough from it to align the tail
+    fn compact(&mut self) {
+        let start = self.start();
+        let len = self.end - start;
+        let dest = round_up(len, self.buf.align()) - len;
 
         // no room at the head of the buffer
-        if self.pos == 0 { return; }
+        if start == dest { return; }
 
-        // simply move the bytes down to the beginning
-        let len = self.len();
+        self.move_bytes(start, dest, len);
 
-        safemem::copy_over(unsafe { self.buf.as_mut_slice() },
-                           self.pos, 0, len);
+        if self.zeroize {
+            // wipe the part of the old location which wasn't overwritten
+            let (wipe_start, wipe_end) = if dest < start {
+                (cmp::max(start, dest + len), start + len)
+            } else {
+                (start, cmp::min(start + len, dest))
+            };
 
-        self.pos = 0;
-        self.end = len;
+            wipe(unsafe { &mut self.buf.as_mut_slice()[wipe_start .. wipe_end] });
+        }
+
+        self.pos = self.pos - start + dest;
+        self.end = dest + len;
+    }
+
+    // get ready to write to the buffer
+    fn check_tail(&mut self) {
+        if !self.check_cursors() && !self.end.is_multiple_of(self.buf.align()) {
+            self.compact();
+        }
     }
 
     pub fn buf(&self) -> &[u8] {
@@ -92,22 +257,77 @@ impl StdBuf {
     }
 
     pub unsafe fn write_buf(&mut self) -> &mut [u8] {
+        self.check_tail();
+        // zero any memory which hasn't been written yet so it can be given to readers
+        self.buf.initialize();
         &mut self.buf.as_mut_slice()[self.end ..]
     }
 
+    /// Get the tail of the buffer without initializing it first.
+    ///
+    /// Initialized bytes must not be overwritten with uninitialized ones.
+    pub unsafe fn write_buf_uninit(&mut self) -> &mut [MaybeUninit<u8>] {
+        self.check_tail();
+        self.buf.tail_uninit(self.end)
+    }
+
     pub unsafe fn bytes_written(&mut self, amt: usize) {
         self.end = cmp::min(self.end + amt, self.capacity());
+        // a no-op unless the bytes were written through `.write_buf_uninit()`
+        self.buf.assume_init(self.end);
     }
 
     pub fn consume(&mut self, amt: usize) {
+        // don't reset the cursors yet so the consumed bytes can still be restored
         self.pos = cmp::min(self.pos + amt, self.end);
-        self.check_cursors();
+    }
+
+    pub fn unconsume(&mut self, amt: usize) -> usize {
+        let amt = cmp::min(amt, self.pos);
+        self.pos -= amt;
+        amt
+    }
+
+    pub fn clear(&mut self) {
+        self.pos = 0;
+        self.end = 0;
+    }
+
+    pub fn set_retain(&mut self, retain: usize) {
+        self.retain = retain;
+    }
+
+    // copy the `len` bytes at `src` to `dest`
+    fn move_bytes(&mut self, src: usize, dest: usize, len: usize) {
+        let buf = unsafe { self.buf.as_mut_slice() };
+        assert!(cmp::max(src, dest) <= buf.len() && len <= buf.len() - cmp::max(src, dest));
+
+        // derive both pointers from the same borrow so neither invalidates the other
+        let ptr = buf.as_mut_ptr();
+
+        unsafe {
+            if src.abs_diff(dest) >= len {
+                // the regions don't overlap so we can use `memcpy` instead of `memmove`
+                ptr::copy_nonoverlapping(ptr.add(src), ptr.add(dest), len);
+            } else {
+                ptr::copy(ptr.add(src), ptr.add(dest), len);
+            }
+        }
+    }
+
+    fn retained(&self) -> usize {
+        cmp::min(self.retain, self.pos)
+    }
+
+    // the start of the retained bytes
+    fn start(&self) -> usize {
+        self.pos - self.retained()
     }
 
     pub fn check_cursors(&mut self) -> bool {
         if self.pos == self.end {
-            self.pos = 0;
-            self.end = 0;
+            // keep the retained bytes, moving them down to the beginning
+            self.compact();
             true
         } else {
             false
@@ -115,107 +335,367 @@ impl StdBuf {
     }
 }
 
-#[cfg(not(feature = "nightly"))]
-mod impl_ {
-    use std::mem;
+impl Drop for StdBuf {
+    fn drop(&mut self) {
+        // this happens before the memory is freed or returned to its pool
+        if self.zeroize {
+            self.buf.wipe();
+        }
+    }
+}
+
+/// Overwrite `buf` with zeroes in a way that won't be optimized out.
+pub fn wipe(buf: &mut [u8]) {
+    for byte in buf.iter_mut() {
+        unsafe { ptr::write_volatile(byte, 0); }
+    }
+
+    // don't let later accesses be reordered before the wipe either
+    atomic::compiler_fence(Ordering::SeqCst);
+}
 
-    pub struct RawBuf {
-        buf: Box<[u8]>,
+fn round_up(n: usize, align: usize) -> usize {
+    n.checked_next_multiple_of(align).expect("capacity overflow")
+}
+
+mod impl_ {
+    use std::alloc::{self, Layout};
+    use std::mem::MaybeUninit;
+    use std::ptr::{self, NonNull};
+    use std::sync::Arc;
+    use std::{mem, slice};
+
+    use pool::PoolShared;
+    use BufStorage;
+
+    use super::wipe;
+
+    /// The memory backing `StdBuf`, either allocated by us, possibly from a `BufferPool`
+    /// it will be returned to, or supplied by the user.
+    pub enum RawBuf {
+        Alloc(AllocBuf, Option<Arc<PoolShared>>),
+        Custom(Box<dyn BufStorage>),
     }
 
     impl RawBuf {
-        pub fn with_capacity(capacity: usize) -> Self {
-            let mut buf = Vec::with_capacity(capacity);
-            let true_cap = buf.capacity();
+        pub fn with_capacity_aligned(capacity: usize, align: usize) -> Self {
+            RawBuf::Alloc(AllocBuf::with_capacity_aligned(capacity, align), None)
+        }
 
-            unsafe {
-                buf.set_len(true_cap);
+        /// Stop this buffer from being returned to its pool.
+        pub fn detach_pool(&mut self) {
+            if let RawBuf::Alloc(_, ref mut pool) = *self {
+                *pool = None;
             }
+        }
 
-            RawBuf {
-                buf: buf.into_boxed_slice(),
+        pub fn capacity(&self) -> usize {
+            match *self {
+                RawBuf::Alloc(ref buf, _) => buf.capacity(),
+                RawBuf::Custom(ref storage) => storage.as_slice().len(),
             }
         }
 
-        pub fn capacity(&self) -> usize {
-            self.buf.len()
+        pub fn align(&self) -> usize {
+            match *self {
+                RawBuf::Alloc(ref buf, _) => buf.align(),
+                RawBuf::Custom(_) => 1,
+            }
         }
 
+        /// Grow the capacity by *at least* `additional`, returning `true` if the memory moved.
         pub fn reserve(&mut self, additional: usize) -> bool {
-            let mut buf = mem::replace(&mut self.buf, Box::new([])).into_vec();
+            match *self {
+                RawBuf::Alloc(ref mut buf, _) => buf.reserve(additional),
+                RawBuf::Custom(ref mut storage) => {
+                    let old_ptr = storage.as_slice().as_ptr();
+                    storage.reserve(additional);
+                    old_ptr != storage.as_slice().as_ptr()
+                }
+            }
+        }
 
-            let old_ptr = self.buf.as_ptr();
+        /// Like `.reserve()`, but the contents may be discarded.
+        pub fn reserve_discard(&mut self, additional: usize) -> bool {
+            match *self {
+                RawBuf::Alloc(ref mut buf, _) => {
+                    let align = buf.align();
+                    let capacity = buf.capacity() + additional;
+                    // free the existing memory first
+                    *buf = AllocBuf::with_capacity_aligned(0, align);
+                    *buf = AllocBuf::with_capacity_aligned(capacity, align);
+                    true
+                },
+                RawBuf::Custom(_) => self.reserve(additional),
+            }
+        }
 
-            buf.reserve_exact(additional);
+        /// Change the capacity to `new_cap`, keeping the first `keep` bytes; the old memory is
+        /// wiped before it is freed, except custom storage which is resized as usual.
+        pub fn resize_wiped(&mut self, new_cap: usize, keep: usize) -> bool {
+            match *self {
+                RawBuf::Alloc(ref mut buf, _) => {
+                    // `realloc()` may free the old memory, so always copy to a new allocation
+                    let mut new_buf = AllocBuf::with_capacity_aligned(new_cap, buf.align());
+
+                    unsafe {
+                        let dest = new_buf.tail_uninit(0).as_mut_ptr() as *mut u8;
+                        ptr::copy_nonoverlapping(buf.as_slice().as_ptr(), dest, keep);
+                        new_buf.assume_init(keep);
+                        wipe(buf.as_mut_slice());
+                    }
+
+                    *buf = new_buf;
+                    true
+                },
+                RawBuf::Custom(ref mut storage) => {
+                    let cap = storage.as_slice().len();
+
+                    if new_cap > cap {
+                        let old_ptr = storage.as_slice().as_ptr();
+                        storage.reserve(new_cap - cap);
+                        old_ptr != storage.as_slice().as_ptr()
+                    } else {
+                        storage.shrink(new_cap);
+                        false
+                    }
+                },
+            }
+        }
 
-            unsafe {
-                let new_cap = buf.capacity();
-                buf.set_len(new_cap);
+        /// Overwrite all the initialized memory with zeroes.
+        pub fn wipe(&mut self) {
+            unsafe { wipe(self.as_mut_slice()); }
+        }
+
+        pub fn shrink(&mut self, new_cap: usize) {
+            match *self {
+                RawBuf::Alloc(ref mut buf, _) => buf.shrink(new_cap),
+                RawBuf::Custom(ref mut storage) => storage.shrink(new_cap),
             }
+        }
 
-            self.buf = buf.into_boxed_slice();
+        pub fn reserve_in_place(&mut self, additional: usize) -> bool {
+            match *self {
+                RawBuf::Alloc(ref mut buf, _) => buf.reserve_in_place(additional),
+                RawBuf::Custom(_) => false,
+            }
+        }
 
-            old_ptr == self.buf.as_ptr()
+        pub fn initialize(&mut self) {
+            // custom storage is always initialized
+            if let RawBuf::Alloc(ref mut buf, _) = *self {
+                buf.initialize();
+            }
         }
 
-        pub fn reserve_in_place(&mut self, _additional: usize) -> bool {
-            // `Vec` does not support this
-            return false;
+        pub unsafe fn tail_uninit(&mut self, start: usize) -> &mut [MaybeUninit<u8>] {
+            match *self {
+                RawBuf::Alloc(ref mut buf, _) => buf.tail_uninit(start),
+                // always initialized; the caller must not write uninitialized bytes to it
+                RawBuf::Custom(ref mut storage) => {
+                    let tail = &mut storage.as_mut_slice()[start ..];
+                    slice::from_raw_parts_mut(tail.as_mut_ptr() as *mut MaybeUninit<u8>, tail.len())
+                },
+            }
+        }
+
+        pub unsafe fn assume_init(&mut self, init: usize) {
+            if let RawBuf::Alloc(ref mut buf, _) = *self {
+                buf.assume_init(init);
+            }
         }
 
         pub unsafe fn as_slice(&self) -> &[u8] {
-            &self.buf
+            match *self {
+                RawBuf::Alloc(ref buf, _) => buf.as_slice(),
+                RawBuf::Custom(ref storage) => storage.as_slice(),
+            }
         }
 
         pub unsafe fn as_mut_slice(&mut self) -> &mut [u8] {
-            &mut self.buf
+            match *self {
+                RawBuf::Alloc(ref mut buf, _) => buf.as_mut_slice(),
+                RawBuf::Custom(ref mut storage) => storage.as_mut_slice(),
+            }
         }
     }
-}
 
-#[cfg(feature = "nightly")]
-mod impl_ {
-    extern crate alloc;
+    impl Drop for RawBuf {
+        fn drop(&mut self) {
+            if let RawBuf::Alloc(ref mut buf, Some(ref pool)) = *self {
+                pool.give_back(mem::replace(buf, AllocBuf::with_capacity_aligned(0, 1)));
+            }
+        }
+    }
 
-    use self::alloc::raw_vec::RawVec;
+    /// The default allocation backing `StdBuf`. Its capacity is always a multiple of its alignment.
+    ///
+    /// Only the first `init` bytes have been initialized, so creating and growing the buffer
+    /// doesn't touch the new memory. The slices returned by `as_slice()` and `as_mut_slice()`
+    /// only cover this initialized prefix, and `initialize()` zeroes the rest before it is handed
+    /// to a reader; `StdBuf` never indexes past its tail cursor otherwise, and the tail cursor
+    /// only advances over memory which was handed out by `.write_buf()`.
+    pub struct AllocBuf {
+        ptr: NonNull<u8>,
+        cap: usize,
+        align: usize,
+        init: usize,
+    }
 
-    use std::slice;
+    // `AllocBuf` uniquely owns its allocation, like `Vec<u8>`
+    unsafe impl Send for AllocBuf {}
+    unsafe impl Sync for AllocBuf {}
+
+    impl AllocBuf {
+        /// ### Panics
+        /// If `align` is not a power of two.
+        pub fn with_capacity_aligned(capacity: usize, align: usize) -> Self {
+            assert!(align.is_power_of_two(), "buffer alignment must be a power of two: {}", align);
+
+            let mut buf = AllocBuf {
+                ptr: dangling(align),
+                cap: 0,
+                align,
+                init: 0,
+            };
+
+            buf.resize(capacity);
+            buf
+        }
 
-    pub struct RawBuf {
-        buf: RawVec<u8>,
-    }
+        /// Take ownership of the allocation of `vec`; its contents are the initialized bytes.
+        pub fn from_vec(vec: Vec<u8>) -> Self {
+            let mut vec = mem::ManuallyDrop::new(vec);
 
-    impl RawBuf {
-        pub fn with_capacity(capacity: usize) -> Self {
-            RawBuf {
-                buf: RawVec::with_capacity(capacity)
+            AllocBuf {
+                // `Vec` uses a dangling pointer if it hasn't allocated, like we do
+                ptr: NonNull::new(vec.as_mut_ptr()).expect("Vec pointer is null"),
+                cap: vec.capacity(),
+                align: 1,
+                init: vec.len(),
+            }
+        }
+
+        /// Convert into a `Vec` of the first `len` bytes, which must be initialized.
+        ///
+        /// Fails if the buffer is aligned, as `Vec<u8>` can't deallocate that.
+        pub fn into_vec(self, len: usize) -> Result<Vec<u8>, Self> {
+            if self.align != 1 { return Err(self); }
+
+            assert!(len <= self.init);
+
+            let buf = mem::ManuallyDrop::new(self);
+
+            // this was allocated by the global allocator with the same layout `Vec<u8>` uses,
+            // or not at all if the capacity is zero
+            unsafe {
+                Ok(Vec::from_raw_parts(buf.ptr.as_ptr(), len, buf.cap))
             }
         }
 
         pub fn capacity(&self) -> usize {
-            self.buf.cap()
+            self.cap
+        }
+
+        pub fn align(&self) -> usize {
+            self.align
         }
 
         pub fn reserve(&mut self, additional: usize) -> bool {
-            let cap = self.capacity();
-            let old_ptr = self.buf.ptr();
-            self.buf.reserve_exact(cap, additional);
-            old_ptr != self.buf.ptr()
+            let old_ptr = self.ptr;
+            let new_cap = self.cap.checked_add(additional).expect("capacity overflow");
+
+            // the initialized bytes are copied if it reallocates
+            self.resize(new_cap);
+
+            old_ptr != self.ptr
         }
 
-        pub fn reserve_in_place(&mut self, additional: usize) -> bool {
-            let cap = self.capacity();
-            self.buf.reserve_in_place(cap, additional)
+        pub fn shrink(&mut self, new_cap: usize) {
+            if new_cap < self.cap {
+                self.resize(new_cap);
+            }
+        }
+
+        pub fn reserve_in_place(&mut self, _additional: usize) -> bool {
+            // `std::alloc` does not support this
+            false
+        }
+
+        /// Zero the memory which hasn't been initialized yet; this only happens once for
+        /// each byte of an allocation.
+        pub fn initialize(&mut self) {
+            if self.init < self.cap {
+                unsafe {
+                    self.ptr.as_ptr().add(self.init).write_bytes(0, self.cap - self.init);
+                }
+
+                self.init = self.cap;
+            }
+        }
+
+        /// Get the memory from `start` to the end of the allocation, which may be uninitialized.
+        pub unsafe fn tail_uninit(&mut self, start: usize) -> &mut [MaybeUninit<u8>] {
+            let tail = self.ptr.as_ptr().add(start) as *mut MaybeUninit<u8>;
+            slice::from_raw_parts_mut(tail, self.cap - start)
+        }
+
+        /// Mark the first `init` bytes as initialized.
+        pub unsafe fn assume_init(&mut self, init: usize) {
+            self.init = ::std::cmp::max(self.init, init);
         }
 
         pub unsafe fn as_slice(&self) -> &[u8] {
-            slice::from_raw_parts(self.buf.ptr(), self.buf.cap())
+            slice::from_raw_parts(self.ptr.as_ptr(), self.init)
         }
 
         pub unsafe fn as_mut_slice(&mut self) -> &mut [u8] {
-            slice::from_raw_parts_mut(self.buf.ptr(), self.buf.cap())
+            slice::from_raw_parts_mut(self.ptr.as_ptr(), self.init)
+        }
+
+        // change the capacity to `new_cap` rounded up to the alignment, keeping the initialized
+        // bytes which still fit
+        fn resize(&mut self, new_cap: usize) {
+            let new_cap = new_cap.checked_next_multiple_of(self.align).expect("capacity overflow");
+            if new_cap == self.cap { return; }
+
+            let new_layout = Layout::from_size_align(new_cap, self.align)
+                .expect("capacity overflow");
+
+            let ptr = unsafe {
+                if new_cap == 0 {
+                    alloc::dealloc(self.ptr.as_ptr(), self.layout());
+                    dangling(self.align).as_ptr()
+                } else if self.cap == 0 {
+                    alloc::alloc(new_layout)
+                } else {
+                    alloc::realloc(self.ptr.as_ptr(), self.layout(), new_cap)
+                }
+            };
+
+            self.ptr = NonNull::new(ptr).unwrap_or_else(|| alloc::handle_alloc_error(new_layout));
+            self.cap = new_cap;
+            self.init = ::std::cmp::min(self.init, new_cap);
         }
 
+        fn layout(&self) -> Layout {
+            // this succeeded when the buffer was allocated
+            unsafe { Layout::from_size_align_unchecked(self.cap, self.align) }
+        }
+    }
+
+    impl Drop for AllocBuf {
+        fn drop(&mut self) {
+            if self.cap > 0 {
+                unsafe { alloc::dealloc(self.ptr.as_ptr(), self.layout()); }
+            }
+        }
+    }
+
+    // a non-null pointer with the given alignment for empty buffers, like `NonNull::dangling()`
+    fn dangling(align: usize) -> NonNull<u8> {
+        unsafe { NonNull::new_unchecked(align as *mut u8) }
     }
 }
 
@@ -233,3 +713,86 @@ fn read_into_full() {
     assert_eq!(buffer.read_from(&mut bytes).unwrap(), 1);
     assert_eq!(buffer.read_from(&mut bytes).unwrap(), 0);
 }
+
+#[test]
+fn reserve_empty_exact() {
+    use Buffer;
+
+    let mut buffer = Buffer::with_capacity(16);
+    buffer.reserve(17);
+
+    // only as much as was requested, not the old capacity on top
+    assert!(buffer.capacity() >= 17 && buffer.capacity() < 32,
+            "capacity: {}", buffer.capacity());
+
+    // repeatedly growing by a little should not double the buffer each time
+    for _ in 0 .. 16 {
+        let cap = buffer.capacity();
+        buffer.reserve(cap + 1);
+    }
+
+    assert!(buffer.capacity() < 64, "capacity: {}", buffer.capacity());
+}
+
+#[test]
+fn reserve_keeps_data() {
+    use Buffer;
+
+    let mut buffer = Buffer::with_capacity(4);
+    assert_eq!(buffer.copy_from_slice(&[1, 2, 3]), 3);
+    buffer.consume(1);
+
+    // reallocates, only the initialized bytes need to be copied
+    buffer.reserve(64);
+    assert_eq!(buffer.buf(), &[2, 3]);
+
+    let mut bytes = &[4u8, 5][..];
+    assert_eq!(buffer.read_from(&mut bytes).unwrap(), 2);
+    assert_eq!(buffer.buf(), &[2, 3, 4, 5]);
+
+    buffer.shrink(0);
+    assert_eq!(buffer.buf(), &[2, 3, 4, 5]);
+    assert_eq!(buffer.capacity(), 4);
+}
+
+#[test]
+fn read_from_trusted() {
+    use Buffer;
+
+    let mut buffer = Buffer::with_capacity(8);
+
+    let mut bytes = &[1u8, 2, 3][..];
+    assert_eq!(buffer.read_from_trusted(&mut bytes).unwrap(), 3);
+    assert_eq!(buffer.buf(), &[1, 2, 3]);
+
+    // the safe path zeroes only what the trusted read left uninitialized
+    let mut bytes = &[4u8, 5][..];
+    assert_eq!(buffer.read_from(&mut bytes).unwrap(), 2);
+    assert_eq!(buffer.buf(), &[1, 2, 3, 4, 5]);
+
+    buffer.consume(5);
+    let mut bytes = &[6u8; 16][..];
+    assert_eq!(buffer.read_from_trusted(&mut bytes).unwrap(), 8);
+    assert_eq!(buffer.buf(), &[6; 8]);
+}
+
+#[test]
+fn make_room_overlapping() {
+    use Buffer;
+
+    let mut buffer = Buffer::with_capacity(8);
+    assert_eq!(buffer.copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]), 8);
+
+    // 5 bytes from offset 3 overlap their destination
+    buffer.consume(3);
+    buffer.make_room();
+    assert_eq!(buffer.buf(), &[4, 5, 6, 7, 8]);
+    assert_eq!(buffer.usable_space(), 3);
+
+    // 2 bytes from offset 6 don't
+    assert_eq!(buffer.copy_from_slice(&[9, 10, 11]), 3);
+    buffer.consume(6);
+    buffer.make_room();
+    assert_eq!(buffer.buf(), &[10, 11]);
+    assert_eq!(buffer.usable_space(), 6);
+}
@@ -0,0 +1,180 @@
+// Copyright 2018 Austin Bonander <austin.bonander@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+//! A builder for configuring `BufReader`s in one place.
+
+use {BufReader, DEFAULT_BUF_SIZE};
+
+/// A builder for `BufReader`s with the given capacity and settings.
+///
+/// Each setting corresponds to a method on `BufReader`; anything not set keeps the default of
+/// `BufReader::new()`. `.build()` doesn't consume the builder, so one builder can create any
+/// number of identically configured readers, e.g. one per connection:
+///
+/// ```rust
+/// use buf_redux::BufReaderBuilder;
+///
+/// let builder = BufReaderBuilder::new().capacity(8192).max_capacity(1 << 20).move_threshold(256);
+///
+/// let first = builder.build(&b"first"[..]);
+/// let second = builder.build(&b"second"[..]);
+/// assert_eq!(first.capacity(), second.capacity());
+/// assert_eq!(second.max_capacity(), Some(1 << 20));
+/// ```
+#[derive(Clone, Debug)]
+pub struct BufReaderBuilder {
+    capacity: usize,
+    ringbuf: bool,
+    max_capacity: Option<usize>,
+    move_threshold: usize,
+    auto_shrink: Option<(usize, u32)>,
+    buffer_aware_seek: bool,
+    fill_full: bool,
+    bypass_threshold: Option<Option<usize>>,
+    grow_on_demand: usize,
+    fused: bool,
+    #[cfg(feature = "secure")]
+    zeroize: bool,
+}
+
+impl BufReaderBuilder {
+    /// Create a builder with the default settings.
+    pub fn new() -> Self {
+        BufReaderBuilder {
+            capacity: DEFAULT_BUF_SIZE,
+            ringbuf: false,
+            max_capacity: None,
+            move_threshold: 0,
+            auto_shrink: None,
+            buffer_aware_seek: false,
+            fill_full: false,
+            bypass_threshold: None,
+            grow_on_demand: 0,
+            fused: false,
+            #[cfg(feature = "secure")]
+            zeroize: false,
+        }
+    }
+
+    /// Set the initial capacity of the buffer; see `BufReader::with_capacity()`.
+    pub fn capacity(mut self, cap: usize) -> Self {
+        self.capacity = cap;
+        self
+    }
+
+    /// Use a ringbuffer if possible; see `BufReader::with_capacity_ringbuf()`.
+    pub fn ringbuf(mut self, ringbuf: bool) -> Self {
+        self.ringbuf = ringbuf;
+        self
+    }
+
+    /// Limit the capacity of the buffer; see `BufReader::set_max_capacity()`.
+    pub fn max_capacity(mut self, max: usize) -> Self {
+        self.max_capacity = Some(max);
+        self
+    }
+
+    /// See `BufReader::set_move_threshold()`.
+    pub fn move_threshold(mut self, bytes: usize) -> Self {
+        self.move_threshold = bytes;
+        self
+    }
+
+    /// See `BufReader::set_auto_shrink()`.
+    pub fn auto_shrink(mut self, threshold: usize, after_reads: u32) -> Self {
+        self.auto_shrink = Some((threshold, after_reads));
+        self
+    }
+
+    /// See `BufReader::set_buffer_aware_seek()`.
+    pub fn buffer_aware_seek(mut self, enabled: bool) -> Self {
+        self.buffer_aware_seek = enabled;
+        self
+    }
+
+    /// See `BufReader::set_fill_full()`.
+    pub fn fill_full(mut self, enabled: bool) -> Self {
+        self.fill_full = enabled;
+        self
+    }
+
+    /// See `BufReader::set_bypass_threshold()`.
+    pub fn bypass_threshold(mut self, threshold: Option<usize>) -> Self {
+        self.bypass_threshold = Some(threshold);
+        self
+    }
+
+    /// See `BufReader::set_grow_on_demand()`.
+    pub fn grow_on_demand(mut self, max: usize) -> Self {
+        self.grow_on_demand = max;
+        self
+    }
+
+    /// See `BufReader::set_fused()`.
+    pub fn fused(mut self, fused: bool) -> Self {
+        self.fused = fused;
+        self
+    }
+
+    /// See `BufReader::set_zeroize_on_drop()`. Requires the `secure` feature.
+    #[cfg(feature = "secure")]
+    pub fn zeroize_on_drop(mut self, zeroize: bool) -> Self {
+        self.zeroize = zeroize;
+        self
+    }
+
+    /// Create a `BufReader` wrapping `inner` with these settings.
+    ///
+    /// ### Panics
+    /// If the capacity is larger than the maximum capacity.
+    pub fn build<R>(&self, inner: R) -> BufReader<R> {
+        if let Some(max) = self.max_capacity {
+            assert!(self.capacity <= max,
+                    "BufReaderBuilder capacity ({}) is larger than max_capacity ({})",
+                    self.capacity, max);
+        }
+
+        let mut reader = self.with_capacity(inner);
+
+        if let Some(max) = self.max_capacity {
+            reader.set_max_capacity(max);
+        }
+
+        if let Some((threshold, after_reads)) = self.auto_shrink {
+            reader.set_auto_shrink(threshold, after_reads);
+        }
+
+        reader.set_move_threshold(self.move_threshold);
+        reader.set_buffer_aware_seek(self.buffer_aware_seek);
+        reader.set_fill_full(self.fill_full);
+        reader.set_grow_on_demand(self.grow_on_demand);
+        reader.set_fused(self.fused);
+
+        if let Some(threshold) = self.bypass_threshold {
+            reader.set_bypass_threshold(threshold);
+        }
+
+        #[cfg(feature = "secure")]
+        reader.set_zeroize_on_drop(self.zeroize);
+
+        reader
+    }
+
+    fn with_capacity<R>(&self, inner: R) -> BufReader<R> {
+        if self.ringbuf {
+            BufReader::with_capacity_ringbuf(self.capacity, inner)
+        } else {
+            BufReader::with_capacity(self.capacity, inner)
+        }
+    }
+}
+
+impl Default for BufReaderBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,354 @@
+// Copyright 2018 Austin Bonander <austin.bonander@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+//! Buffered readers with fixed storage that never allocate: inline or borrowed.
+
+use std::io::prelude::*;
+use std::io::{self, SeekFrom};
+use std::{cmp, fmt};
+
+/// A buffered reader whose buffer is a `[u8; N]` stored inline, so it never allocates;
+/// for embedded or latency-critical code.
+///
+/// It supports the core of the `BufReader` API (`fill_buf()`, `consume()`, `read()`,
+/// `.make_room()`, `.read_into_buf()`, `.fill_buf_min()` and friends) but the buffer can never
+/// grow; operations which would need it to return an error instead. Reads behave like
+/// `BufReader` with the default policy. See also [`BufReaderSlice`](BufReaderSlice).
+///
+/// As the buffer lives inside the struct, this is as large as `N` bytes plus the reader, and
+/// moving it copies the buffer. Errors produced by this type itself carry only an
+/// `io::ErrorKind`, as a message would have to be allocated.
+pub struct FixedBufReader<R, const N: usize> {
+    buf: [u8; N],
+    pos: usize,
+    end: usize,
+    inner: R,
+}
+
+impl<R, const N: usize> FixedBufReader<R, N> {
+    /// Wrap `inner` with a buffer of `N` bytes.
+    pub fn new(inner: R) -> Self {
+        FixedBufReader {
+            buf: [0; N],
+            pos: 0,
+            end: 0,
+            inner,
+        }
+    }
+}
+
+/// A buffered reader using a slice borrowed from the caller as its buffer, e.g. a chunk of an
+/// arena lent to it for the duration of a request; it never allocates.
+///
+/// This has the same API as [`FixedBufReader`](FixedBufReader), with the length of the slice
+/// as its capacity. The borrow simply ends when the reader is dropped or `.into_inner()` is
+/// called.
+pub struct BufReaderSlice<'a, R> {
+    buf: &'a mut [u8],
+    pos: usize,
+    end: usize,
+    inner: R,
+}
+
+impl<'a, R> BufReaderSlice<'a, R> {
+    /// Wrap `inner`, using `buf` as the buffer. Its contents are disregarded.
+    pub fn new(buf: &'a mut [u8], inner: R) -> Self {
+        BufReaderSlice {
+            buf,
+            pos: 0,
+            end: 0,
+            inner,
+        }
+    }
+}
+
+// the implementation shared by the fixed readers, which have the fields
+// `buf` (dereferencing to `[u8]`), `pos`, `end` and `inner`
+macro_rules! fixed_reader_impls {
+    ($name:expr, [$($generics:tt)*] $ty:ty) => {
+        impl<$($generics)*> $ty {
+            /// Get the capacity of the buffer.
+            pub fn capacity(&self) -> usize {
+                self.buf.len()
+            }
+
+            /// Get the section of the buffer containing valid data; may be empty.
+            pub fn buffer(&self) -> &[u8] {
+                &self.buf[self.pos .. self.end]
+            }
+
+            /// Get the current number of bytes available in the buffer.
+            pub fn buf_len(&self) -> usize {
+                self.end - self.pos
+            }
+
+            /// Get the number of bytes that can be read into the buffer before the data in it
+            /// needs to be moved down.
+            pub fn usable_space(&self) -> usize {
+                self.capacity() - self.end
+            }
+
+            /// Move data to the start of the buffer, making room at the end for more reading.
+            pub fn make_room(&mut self) {
+                if self.pos == 0 { return; }
+
+                self.buf.copy_within(self.pos .. self.end, 0);
+                self.end -= self.pos;
+                self.pos = 0;
+            }
+
+            /// Restore up to `amt` of the most recently consumed bytes to the buffer, returning
+            /// the number of bytes actually restored; they are lost once the data is moved down
+            /// or the buffer is read into after being emptied.
+            pub fn unconsume(&mut self, amt: usize) -> usize {
+                let amt = cmp::min(amt, self.pos);
+                self.pos -= amt;
+                amt
+            }
+
+            /// Empty the buffer.
+            pub fn clear(&mut self) {
+                self.pos = 0;
+                self.end = 0;
+            }
+
+            /// Get an immutable reference to the underlying reader.
+            pub fn get_ref(&self) -> &R { &self.inner }
+
+            /// Get a mutable reference to the underlying reader.
+            ///
+            /// ## Note
+            /// Reading directly from the underlying reader is not recommended, as some
+            /// data has likely already been moved into the buffer.
+            pub fn get_mut(&mut self) -> &mut R { &mut self.inner }
+
+            /// Consume `self` and return the inner reader only.
+            pub fn into_inner(self) -> R {
+                self.inner
+            }
+        }
+
+        impl<$($generics)*> $ty where R: Read {
+            /// Unconditionally perform a read into the buffer, moving the data down first if
+            /// there is no usable space; returns the number of bytes read, which is only `0` at
+            /// EOF.
+            ///
+            /// ### Errors
+            /// Returns an error of kind `OutOfMemory` if the buffer is full, as well as any error
+            /// from the underlying reader.
+            pub fn read_into_buf(&mut self) -> io::Result<usize> {
+                let cap = self.capacity();
+
+                if self.pos == self.end {
+                    self.clear();
+                } else if self.end == cap {
+                    self.make_room();
+                }
+
+                if self.end == cap {
+                    return Err(io::ErrorKind::OutOfMemory.into());
+                }
+
+                let read = self.inner.read(&mut self.buf[self.end ..])?;
+                assert!(read <= cap - self.end,
+                        "reader returned more bytes than fit in the buffer");
+                self.end += read;
+                Ok(read)
+            }
+
+            /// Read from the underlying reader until the buffer contains at least `min` bytes or
+            /// the reader reaches EOF, then return the entire section of the buffer containing
+            /// valid data.
+            ///
+            /// ### Errors
+            /// Returns an error of kind `OutOfMemory` if `min` is larger than the capacity.
+            pub fn fill_buf_min(&mut self, min: usize) -> io::Result<&[u8]> {
+                if min > self.capacity() {
+                    return Err(io::ErrorKind::OutOfMemory.into());
+                }
+
+                while self.buf_len() < min {
+                    if self.usable_space() < min - self.buf_len() {
+                        self.make_room();
+                    }
+
+                    match self.read_into_buf() {
+                        Ok(0) => break,
+                        Ok(_) => (),
+                        Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                        Err(e) => return Err(e),
+                    }
+                }
+
+                Ok(self.buffer())
+            }
+
+            /// Get a slice of *up to* `n` bytes from the buffer without consuming them, reading
+            /// from the underlying reader until that many bytes are available or it reaches EOF.
+            ///
+            /// See `.fill_buf_min()` for details.
+            pub fn peek(&mut self, n: usize) -> io::Result<&[u8]> {
+                let buf = self.fill_buf_min(n)?;
+                Ok(&buf[..cmp::min(n, buf.len())])
+            }
+        }
+
+        impl<$($generics)*> Read for $ty where R: Read {
+            fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+                // bypass the buffer for reads at least as large as it
+                if self.pos == self.end && out.len() >= self.capacity() {
+                    return self.inner.read(out);
+                }
+
+                let nread = self.fill_buf()?.read(out)?;
+                self.consume(nread);
+                Ok(nread)
+            }
+        }
+
+        impl<$($generics)*> BufRead for $ty where R: Read {
+            fn fill_buf(&mut self) -> io::Result<&[u8]> {
+                if self.pos == self.end && self.capacity() > 0 {
+                    self.read_into_buf()?;
+                }
+
+                Ok(self.buffer())
+            }
+
+            fn consume(&mut self, amt: usize) {
+                self.pos = cmp::min(self.pos + amt, self.end);
+            }
+        }
+
+        impl<$($generics)*> Seek for $ty where R: Seek {
+            /// Seek to an offset, in bytes, in the underlying reader, discarding the buffer.
+            ///
+            /// The position used for seeking with `SeekFrom::Current(_)` is the position the
+            /// underlying reader would be at if there was no buffer; see the `Seek` impl of
+            /// `BufReader`.
+            fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+                let result = if let SeekFrom::Current(n) = pos {
+                    let remainder = self.buf_len() as i64;
+
+                    if let Some(offset) = n.checked_sub(remainder) {
+                        self.inner.seek(SeekFrom::Current(offset))?
+                    } else {
+                        // seek backwards by our remainder, and then by the offset
+                        self.inner.seek(SeekFrom::Current(-remainder))?;
+                        self.clear();
+                        self.inner.seek(SeekFrom::Current(n))?
+                    }
+                } else {
+                    self.inner.seek(pos)?
+                };
+
+                self.clear();
+                Ok(result)
+            }
+        }
+
+        impl<$($generics)*> fmt::Debug for $ty where R: fmt::Debug {
+            fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+                fmt.debug_struct($name)
+                    .field("reader", &self.inner)
+                    .field("buf_len", &self.buf_len())
+                    .field("capacity", &self.capacity())
+                    .finish()
+            }
+        }
+    };
+}
+
+fixed_reader_impls!("buf_redux::FixedBufReader", [R, const N: usize] FixedBufReader<R, N>);
+fixed_reader_impls!("buf_redux::BufReaderSlice", ['a, R] BufReaderSlice<'a, R>);
+
+#[cfg(test)]
+mod test {
+    use std::io::prelude::*;
+    use std::io::{self, Cursor, SeekFrom};
+
+    use super::{BufReaderSlice, FixedBufReader};
+
+    #[test]
+    fn test_fixed_buf_reader() {
+        let data = (0 .. 20).collect::<Vec<u8>>();
+        let mut reader = FixedBufReader::<_, 8>::new(&data[..]);
+        assert_eq!(reader.capacity(), 8);
+
+        assert_eq!(reader.fill_buf().unwrap(), &data[..8]);
+        reader.consume(6);
+
+        // moves the data down to make room
+        assert_eq!(reader.fill_buf_min(4).unwrap(), &data[6 .. 14]);
+        assert_eq!(reader.peek(3).unwrap(), &data[6 .. 9]);
+        assert_eq!(reader.fill_buf_min(9).unwrap_err().kind(), io::ErrorKind::OutOfMemory);
+
+        let mut out = [0u8; 4];
+        reader.read_exact(&mut out).unwrap();
+        assert_eq!(out, [6, 7, 8, 9]);
+        assert_eq!(reader.unconsume(2), 2);
+
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).unwrap();
+        assert_eq!(out, &data[8 ..]);
+
+        // the buffer can't grow
+        let mut reader = FixedBufReader::<_, 4>::new(&data[..]);
+        assert_eq!(reader.read_into_buf().unwrap(), 4);
+        assert_eq!(reader.read_into_buf().unwrap_err().kind(), io::ErrorKind::OutOfMemory);
+        reader.consume(1);
+        assert_eq!(reader.read_into_buf().unwrap(), 1);
+        assert_eq!(reader.buffer(), &data[1 .. 5]);
+    }
+
+    #[test]
+    fn test_fixed_buf_reader_seek() {
+        let data = (0 .. 20).collect::<Vec<u8>>();
+        let mut reader = FixedBufReader::<_, 8>::new(Cursor::new(data.clone()));
+
+        reader.fill_buf().unwrap();
+        reader.consume(3);
+        assert_eq!(reader.seek(SeekFrom::Current(2)).unwrap(), 5);
+        assert_eq!(reader.fill_buf().unwrap(), &data[5 .. 13]);
+        assert_eq!(reader.seek(SeekFrom::Start(18)).unwrap(), 18);
+        assert_eq!(reader.fill_buf().unwrap(), &data[18 ..]);
+
+        assert_eq!(format!("{:?}", reader),
+                   "buf_redux::FixedBufReader { reader: Cursor { inner: [0, 1, 2, 3, 4, 5, 6, 7, \
+                    8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19], pos: 20 }, buf_len: 2, \
+                    capacity: 8 }");
+    }
+
+    #[test]
+    fn test_buf_reader_slice() {
+        let data = (0 .. 20).collect::<Vec<u8>>();
+        let mut arena = [0xFFu8; 32];
+
+        let inner = {
+            let (chunk, _) = arena.split_at_mut(8);
+            let mut reader = BufReaderSlice::new(chunk, Cursor::new(&data[..]));
+            assert_eq!(reader.capacity(), 8);
+            assert_eq!(reader.fill_buf().unwrap(), &data[..8]);
+            reader.consume(7);
+
+            // refills and compaction stay within the slice
+            assert_eq!(reader.fill_buf_min(5).unwrap(), &data[7 .. 15]);
+            assert_eq!(reader.fill_buf_min(9).unwrap_err().kind(), io::ErrorKind::OutOfMemory);
+            reader.consume(8);
+
+            let mut out = Vec::new();
+            reader.read_to_end(&mut out).unwrap();
+            assert_eq!(out, &data[15 ..]);
+
+            reader.into_inner()
+        };
+
+        // the borrow of the arena has ended
+        assert_eq!(inner.position(), 20);
+        assert!(arena[8 ..].iter().all(|&b| b == 0xFF));
+    }
+}
@@ -0,0 +1,63 @@
+// Copyright 2018 Austin Bonander <austin.bonander@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Endian-aware reading of integers and floats, enabled by the `read-ints` feature.
+
+use std::io::{self, Read};
+
+use policy::ReaderPolicy;
+
+use BufReader;
+
+macro_rules! read_nums {
+    ($($name:ident -> $ty:ident, $from:ident, $endian:expr;)*) => {
+        $(
+            #[doc = concat!("Read a ", $endian, " `", stringify!($ty), "`.")]
+            ///
+            /// Reads directly from the buffer if enough bytes are available, otherwise refills it
+            /// first. Returns an error of kind `UnexpectedEof` if the underlying reader reaches
+            /// EOF first; see `.read_array()`.
+            #[inline]
+            pub fn $name(&mut self) -> io::Result<$ty> {
+                self.read_array().map($ty::$from)
+            }
+        )*
+    }
+}
+
+impl<R: Read + ?Sized, P: ReaderPolicy> BufReader<R, P> {
+    read_nums! {
+        read_u8 -> u8, from_ne_bytes, "single";
+        read_i8 -> i8, from_ne_bytes, "single";
+
+        read_u16_le -> u16, from_le_bytes, "little-endian";
+        read_u16_be -> u16, from_be_bytes, "big-endian";
+        read_i16_le -> i16, from_le_bytes, "little-endian";
+        read_i16_be -> i16, from_be_bytes, "big-endian";
+
+        read_u32_le -> u32, from_le_bytes, "little-endian";
+        read_u32_be -> u32, from_be_bytes, "big-endian";
+        read_i32_le -> i32, from_le_bytes, "little-endian";
+        read_i32_be -> i32, from_be_bytes, "big-endian";
+
+        read_u64_le -> u64, from_le_bytes, "little-endian";
+        read_u64_be -> u64, from_be_bytes, "big-endian";
+        read_i64_le -> i64, from_le_bytes, "little-endian";
+        read_i64_be -> i64, from_be_bytes, "big-endian";
+
+        read_u128_le -> u128, from_le_bytes, "little-endian";
+        read_u128_be -> u128, from_be_bytes, "big-endian";
+        read_i128_le -> i128, from_le_bytes, "little-endian";
+        read_i128_be -> i128, from_be_bytes, "big-endian";
+
+        read_f32_le -> f32, from_le_bytes, "little-endian";
+        read_f32_be -> f32, from_be_bytes, "big-endian";
+        read_f64_le -> f64, from_le_bytes, "little-endian";
+        read_f64_be -> f64, from_be_bytes, "big-endian";
+    }
+}
@@ -114,6 +114,8 @@
 //! This is the exact trick used by [the `slice-deque` crate](https://crates.io/crates/slice-deque),
 //! which is now provided as an optional feature `slice-deque` exposed via the
 //! `new_ringbuf()` and `with_capacity_ringbuf()` constructors added to the buffered types here.
+//! Without the feature, or on platforms without virtual memory support, they fall back to an
+//! ordinary buffer.
 //! When a buffered type is constructed using one of these functions, `.make_room()` is turned into
 //! a no-op as consuming bytes from the head of the buffer simultaneously makes room at the tail.
 //! However, this has some caveats:
@@ -134,20 +136,35 @@
 //! (32-bit targets) then this may be a concern.
 //!
 //! [ringbuf-wikipedia]: https://en.wikipedia.org/wiki/Circular_buffer#Optimization
+//!
+//! ### Reading Numbers / `read-ints` Feature
+//! The optional `read-ints` feature adds methods to `BufReader` for reading integers and floats
+//! in either byte order, like `.read_u32_le()` and `.read_f64_be()`. These read directly
+//! from the buffer when enough bytes are available, only refilling it when a value straddles
+//! its end.
+//!
+//! ### Wiping Sensitive Data / `secure` Feature
+//! The optional `secure` feature adds `BufReader::new_secure()` and `.set_zeroize_on_drop()`
+//! (and the latter to `Buffer`) for buffers that hold secrets like private keys or passphrases;
+//! the buffer memory is overwritten with zeroes before it is freed, and wherever data is left
+//! behind when it is moved. See `Buffer::set_zeroize_on_drop()` for exactly when this happens.
 #![warn(missing_docs)]
-#![cfg_attr(feature = "nightly", feature(alloc, read_initializer, specialization))]
+#![cfg_attr(feature = "nightly", feature(specialization))]
 #![cfg_attr(all(test, feature = "nightly"), feature(io, test))]
 
 extern crate memchr;
 
-extern crate safemem;
+#[cfg(unix)]
+extern crate libc;
 
 use std::any::Any;
 use std::cell::RefCell;
+use std::fs::File;
 use std::io::prelude::*;
 use std::io::SeekFrom;
-use std::mem::ManuallyDrop;
-use std::{cmp, error, fmt, io, ptr};
+use std::mem::{self, ManuallyDrop, MaybeUninit};
+use std::path::Path;
+use std::{cmp, error, fmt, io, ptr, slice, str};
 
 #[cfg(all(feature = "nightly", test))]
 mod benches;
@@ -156,25 +173,62 @@ mod benches;
 #[cfg(test)]
 mod std_tests;
 
-#[cfg(all(test, feature = "slice-deque"))]
+#[cfg(test)]
+mod reader_tests;
+
+#[cfg(all(test, feature = "slice-deque", any(unix, windows)))]
 mod ringbuf_tests;
 
 #[cfg(feature = "nightly")]
 mod nightly;
 
-#[cfg(feature = "nightly")]
-use nightly::init_buffer;
-
 mod buffer;
 
+mod builder;
+
+mod fixed;
+
+mod pool;
+
+#[cfg(any(unix, windows))]
+mod positional;
+
+mod spill;
+
+mod trust;
+
+#[cfg(feature = "read-ints")]
+mod ints;
+
 use buffer::BufImpl;
 
 pub mod policy;
 
+pub use builder::BufReaderBuilder;
+
+pub use fixed::{BufReaderSlice, FixedBufReader};
+
+pub use pool::BufferPool;
+
+#[cfg(any(unix, windows))]
+pub use positional::ReadAt;
+
+use spill::Spill;
+
+pub use trust::TrustRead;
+
 use self::policy::{ReaderPolicy, WriterPolicy, StdPolicy, FlushOnNewline};
 
 const DEFAULT_BUF_SIZE: usize = 8 * 1024;
 
+// the capacity a zero-capacity `BufReader` allocates when buffered data is requested
+const MIN_BUF_SIZE: usize = 256;
+
+// the error returned when a `BufReader` needs to grow past `.set_max_capacity()`
+fn max_capacity_exceeded() -> io::Error {
+    io::Error::new(io::ErrorKind::OutOfMemory, "BufReader buffer would exceed its maximum capacity")
+}
+
 /// A drop-in replacement for `std::io::BufReader` with more functionality.
 ///
 /// Original method names/signatures and implemented traits are left untouched,
@@ -198,11 +252,81 @@ const DEFAULT_BUF_SIZE: usize = 8 * 1024;
 /// [`new_ringbuf()`]: BufReader::new_ringbuf
 /// [`with_capacity_ringbuf()`]: BufReader::with_capacity_ringbuf
 /// [ringbufs-root]: index.html#ringbuffers--slice-deque-feature
-pub struct BufReader<R, P = StdPolicy>{
+pub struct BufReader<R: ?Sized, P = StdPolicy>{
     // First field for null pointer optimization.
     buf: Buffer,
-    inner: R,
     policy: P,
+    mark: Option<Mark>,
+    /// The capacity to restore before the next read after `.shrink_to_fit()`, or 0.
+    regrow_cap: usize,
+    auto_shrink: Option<AutoShrink>,
+    /// The capacity the buffer may not be grown beyond, set by `.set_max_capacity()`.
+    max_cap: Option<usize>,
+    /// Data is moved down before reading into the buffer if no more than this many bytes remain.
+    move_threshold: usize,
+    /// Where lookahead beyond the buffer goes, set by `.set_spill()`.
+    spill: Option<Spill>,
+    /// The most data the buffer has held after a read into it.
+    high_water: usize,
+    /// The number of bytes read from `inner`, wrapping around on overflow.
+    read_count: u64,
+    /// The position of `inner` minus `read_count` (wrapping), if known from the last seek.
+    inner_offset: Option<u64>,
+    /// Whether seeks within the buffered data just move the position in the buffer.
+    buffer_aware_seek: bool,
+    /// Whether a refill keeps reading until the buffer is full, set by `.set_fill_full()`.
+    fill_full: bool,
+    /// Which reads may skip the buffer, set by `.set_bypass_threshold()`.
+    bypass: Bypass,
+    /// The most data delimiter searches may keep in the buffer, set by `.set_grow_on_demand()`.
+    grow_on_demand: usize,
+    /// Whether to stop reading from `inner` once it reports EOF, set by `.set_fused()`.
+    fused: bool,
+    /// Whether `inner` reported EOF while fused.
+    fused_eof: bool,
+    /// Whether the last read from `inner` returned 0, reported by `.is_eof()`.
+    eof: bool,
+    /// `read_uninit_erased::<R>`, if enabled by `.set_trusted_reads()`; it doesn't name `R` so
+    /// `BufReader<R>` can still be coerced to `BufReader<dyn Read>`.
+    trusted_read: Option<ReadUninitFn>,
+    // Last field so `R` may be unsized.
+    inner: R,
+}
+
+type ReadUninitFn = unsafe fn(*mut u8, &mut [MaybeUninit<u8>]) -> io::Result<usize>;
+
+// `inner` must point to an `R`
+unsafe fn read_uninit_erased<R: TrustRead>(inner: *mut u8, out: &mut [MaybeUninit<u8>])
+-> io::Result<usize> {
+    (*(inner as *mut R)).read_uninit(out)
+}
+
+/// A position in the stream saved by `BufReader::mark()`.
+#[derive(Debug)]
+struct Mark {
+    /// The number of bytes consumed since the mark was set.
+    consumed: usize,
+    limit: usize,
+}
+
+/// The state of automatic shrinking set by `BufReader::set_auto_shrink()`.
+#[derive(Clone, Debug)]
+struct AutoShrink {
+    threshold: usize,
+    after_reads: u32,
+    /// The number of consecutive reads into the buffer which left it below the threshold.
+    low_reads: u32,
+}
+
+/// Which reads may skip the buffer when it's empty, set by `BufReader::set_bypass_threshold()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Bypass {
+    /// Reads at least as large as the capacity of the buffer (the default).
+    Capacity,
+    /// Reads of at least the given length.
+    AtLeast(usize),
+    /// No reads skip the buffer.
+    Never,
 }
 
 impl<R> BufReader<R, StdPolicy> {
@@ -217,10 +341,38 @@ impl<R> BufReader<R, StdPolicy> {
     ///
     /// The actual capacity of the buffer may vary based on implementation details of the global
     /// allocator.
+    ///
+    /// With a capacity of `0`, reads are passed straight through to `inner` and nothing is
+    /// allocated until buffered data is requested, e.g. by `.fill_buf()`, `.read_until()` or
+    /// `.lines()`; a small buffer is allocated then.
     pub fn with_capacity(cap: usize, inner: R) -> Self {
         Self::with_buffer(Buffer::with_capacity(cap), inner)
     }
 
+    /// Create a new `BufReader` wrapping `inner`, utilizing a buffer with a capacity of *at least*
+    /// `cap` bytes which is aligned to `align` bytes, e.g. for files opened with `O_DIRECT`.
+    ///
+    /// Reads into the buffer always start at a multiple of `align` and request a multiple of
+    /// `align` bytes; see [`Buffer::with_capacity_aligned()`](Buffer::with_capacity_aligned).
+    ///
+    /// ### Panics
+    /// If `align` is not a power of two.
+    pub fn with_capacity_aligned(cap: usize, align: usize, inner: R) -> Self {
+        Self::with_buffer(Buffer::with_capacity_aligned(cap, align), inner)
+    }
+
+    /// Create a new `BufReader` wrapping `inner` with a buffer of the default capacity which is
+    /// wiped before it is freed; for reading secrets like private keys or passphrases.
+    ///
+    /// See [`Buffer::set_zeroize_on_drop()`](Buffer::set_zeroize_on_drop) for when the buffer is
+    /// wiped and its limitations. Requires the `secure` feature.
+    #[cfg(feature = "secure")]
+    pub fn new_secure(inner: R) -> Self {
+        let mut reader = Self::new(inner);
+        reader.set_zeroize_on_drop(true);
+        reader
+    }
+
     /// Create a new `BufReader` wrapping `inner`, utilizing a ringbuffer with the default capacity
     /// and `ReaderPolicy`.
     ///
@@ -229,11 +381,11 @@ impl<R> BufReader<R, StdPolicy> {
     /// [`MinBuffered`](policy::MinBuffered) to ensure there is always room to read more data
     /// if necessary, without expensive copying operations.
     ///
-    /// Only available on platforms with virtual memory support and with the `slice-deque` feature
-    /// enabled. The default capacity will differ between Windows and Unix-derivative targets.
+    /// A ringbuffer requires the `slice-deque` feature and a platform with virtual memory support;
+    /// otherwise this falls back to an ordinary buffer, like `::new()`.
+    /// The default capacity will differ between Windows and Unix-derivative targets.
     /// See [`Buffer::new_ringbuf()`](struct.Buffer.html#method.new_ringbuf)
     /// or [the crate root docs](index.html#ringbuffers--slice-deque-feature) for more info.
-    #[cfg(feature = "slice-deque")]
     pub fn new_ringbuf(inner: R) -> Self {
         Self::with_capacity_ringbuf(DEFAULT_BUF_SIZE, inner)
     }
@@ -246,229 +398,3212 @@ impl<R> BufReader<R, StdPolicy> {
     /// [`MinBuffered`](policy::MinBuffered) to ensure there is always room to read more data
     /// if necessary, without expensive copying operations.
     ///
-    /// Only available on platforms with virtual memory support and with the `slice-deque` feature
-    /// enabled. The capacity will be rounded up to the minimum size for the target platform.
+    /// A ringbuffer requires the `slice-deque` feature and a platform with virtual memory support;
+    /// otherwise this falls back to an ordinary buffer, like `::with_capacity()`.
+    /// The capacity will be rounded up to the minimum size for the target platform.
     /// See [`Buffer::with_capacity_ringbuf()`](struct.Buffer.html#method.with_capacity_ringbuf)
     /// or [the crate root docs](index.html#ringbuffers--slice-deque-feature) for more info.
-    #[cfg(feature = "slice-deque")]
     pub fn with_capacity_ringbuf(cap: usize, inner: R) -> Self {
         Self::with_buffer(Buffer::with_capacity_ringbuf(cap), inner)
     }
 
-    /// Wrap `inner` with an existing `Buffer` instance and the default `ReaderPolicy`.
-    ///
-    /// ### Note
-    /// Does **not** clear the buffer first! If there is data already in the buffer
-    /// then it will be returned in `read()` and `fill_buf()` ahead of any data from `inner`.
-    pub fn with_buffer(buf: Buffer, inner: R) -> Self {
-        BufReader {
-            buf, inner, policy: StdPolicy
+    /// Wrap `inner` with an existing `Buffer` instance and the default `ReaderPolicy`.
+    ///
+    /// A `Vec<u8>` may be passed as well, adopting its allocation; see
+    /// [`Buffer::from_vec()`](Buffer::from_vec). Its capacity becomes the capacity of the reader.
+    ///
+    /// ### Note
+    /// Does **not** clear the buffer first! If there is data already in the buffer
+    /// then it will be returned in `read()` and `fill_buf()` ahead of any data from `inner`;
+    /// for a `Vec` this is its contents. Use `.with_empty_buffer()` to only reuse the allocation.
+    pub fn with_buffer<B: Into<Buffer>>(buf: B, inner: R) -> Self {
+        let buf = buf.into();
+        let high_water = buf.len();
+
+        BufReader {
+            buf, inner, policy: StdPolicy, mark: None, regrow_cap: 0,
+            auto_shrink: None, max_cap: None, move_threshold: 0, spill: None, high_water,
+            read_count: 0, inner_offset: None, buffer_aware_seek: false, fill_full: false,
+            bypass: Bypass::Capacity, grow_on_demand: 0, fused: false, fused_eof: false,
+            eof: false, trusted_read: None,
+        }
+    }
+
+    /// Reconstruct a `BufReader` from the parts returned by `.into_parts()`: the underlying reader
+    /// and a buffer where `buf[pos .. cap]` is the buffered data.
+    ///
+    /// The allocation of `buf` is adopted without copying; its capacity becomes the capacity of
+    /// the reader.
+    ///
+    /// ### Errors
+    /// Returns an error of kind `InvalidInput` unless `pos <= cap <= buf.len()`.
+    pub fn from_parts(inner: R, buf: Vec<u8>, pos: usize, cap: usize) -> io::Result<Self> {
+        if pos > cap || cap > buf.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "BufReader::from_parts() requires pos <= cap <= buf.len()"));
+        }
+
+        Ok(Self::with_buffer(Buffer { buf: BufImpl::from_vec(buf, pos, cap) }, inner))
+    }
+
+    /// Wrap `inner`, reusing the allocation of `buf` as the buffer but discarding its contents.
+    pub fn with_empty_buffer(mut buf: Vec<u8>, inner: R) -> Self {
+        buf.clear();
+        Self::with_buffer(buf, inner)
+    }
+
+    /// Convert a `std::io::BufReader` into a `BufReader` of the same capacity, keeping the data
+    /// in its buffer.
+    ///
+    /// The buffered data is copied so it is returned before anything else read from `inner`.
+    pub fn from_std(std_reader: io::BufReader<R>) -> Self {
+        let mut buf = Buffer::with_capacity(std_reader.capacity());
+        buf.push_bytes(std_reader.buffer());
+        Self::with_buffer(buf, std_reader.into_inner())
+    }
+}
+
+impl<R, P: ReaderPolicy> BufReader<R, P> {
+    /// Create a new `BufReader` wrapping `inner`, utilizing a buffer of the default capacity and
+    /// the given [`ReaderPolicy`](policy::ReaderPolicy).
+    ///
+    /// The same as `BufReader::new(inner).set_policy(policy)`.
+    pub fn with_policy(inner: R, policy: P) -> Self {
+        BufReader::new(inner).set_policy(policy)
+    }
+
+    /// Create a new `BufReader` wrapping `inner`, utilizing a buffer with a capacity of *at least*
+    /// `cap` bytes and the given [`ReaderPolicy`](policy::ReaderPolicy).
+    ///
+    /// The same as `BufReader::with_capacity(cap, inner).set_policy(policy)`.
+    pub fn with_capacity_and_policy(cap: usize, inner: R, policy: P) -> Self {
+        BufReader::with_capacity(cap, inner).set_policy(policy)
+    }
+}
+
+impl<R> From<io::BufReader<R>> for BufReader<R, StdPolicy> {
+    /// See `BufReader::from_std()`.
+    fn from(std_reader: io::BufReader<R>) -> Self {
+        Self::from_std(std_reader)
+    }
+}
+
+impl<R: SizeHint> BufReader<R, StdPolicy> {
+    /// Create a new `BufReader` wrapping `inner`, with a buffer no larger than the amount of data
+    /// `inner` has left according to [`SizeHint`](SizeHint), up to the default capacity.
+    ///
+    /// The hint only decides the initial capacity; the default capacity is used if there is none.
+    pub fn new_sized(inner: R) -> Self {
+        let cap = inner.remaining_hint()
+            .map_or(DEFAULT_BUF_SIZE, |hint| cmp::min(hint, DEFAULT_BUF_SIZE as u64) as usize);
+        Self::with_capacity(cap, inner)
+    }
+}
+
+impl BufReader<File, StdPolicy> {
+    /// Open the file at `path` for reading, with a buffer sized to fit the file.
+    ///
+    /// The capacity is the length of the file rounded up to a power of two, but no less than 256
+    /// bytes and no more than 32 KiB, which is 4 times the default. If the length isn't known,
+    /// e.g. because the file isn't a regular file or reports its length as zero, the default
+    /// capacity is used. See `.open_with_capacity()` to choose it yourself.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+
+        let cap = match file.metadata() {
+            Ok(ref meta) if meta.is_file() && meta.len() > 0 => {
+                let max = DEFAULT_BUF_SIZE * 4;
+                cmp::max(cmp::min(meta.len(), max as u64) as usize, MIN_BUF_SIZE)
+                    .next_power_of_two()
+            },
+            _ => DEFAULT_BUF_SIZE,
+        };
+
+        Ok(Self::with_capacity(cap, file))
+    }
+
+    /// Open the file at `path` for reading, with a buffer of the given capacity.
+    pub fn open_with_capacity<P: AsRef<Path>>(cap: usize, path: P) -> io::Result<Self> {
+        File::open(path).map(|file| Self::with_capacity(cap, file))
+    }
+}
+
+#[cfg(any(unix, windows))]
+impl<F: ::std::borrow::Borrow<File>> BufReader<ReadAt<F>, StdPolicy> {
+    /// Create a `BufReader` over `file` which refills with positioned reads starting at
+    /// `start_offset`, keeping track of the offset itself instead of using the cursor of the file.
+    ///
+    /// Several readers can share one file this way (as `&File` or `Arc<File>`, or a handle from
+    /// `File::try_clone()`) without interfering with each other; seeking just sets the offset.
+    /// See [`ReadAt`](ReadAt) for details.
+    pub fn positional(file: F, start_offset: u64) -> Self {
+        Self::new(ReadAt::new(file, start_offset))
+    }
+}
+
+impl<R: ?Sized, P> BufReader<R, P> {
+    /// Apply a new `ReaderPolicy` to this `BufReader`, returning the transformed type.
+    pub fn set_policy<P_: ReaderPolicy>(self, policy: P_) -> BufReader<R, P_> where R: Sized {
+        BufReader {
+            inner: self.inner,
+            buf: self.buf,
+            policy,
+            mark: self.mark,
+            regrow_cap: self.regrow_cap,
+            auto_shrink: self.auto_shrink,
+            max_cap: self.max_cap,
+            move_threshold: self.move_threshold,
+            spill: self.spill,
+            high_water: self.high_water,
+            read_count: self.read_count,
+            inner_offset: self.inner_offset,
+            buffer_aware_seek: self.buffer_aware_seek,
+            fill_full: self.fill_full,
+            bypass: self.bypass,
+            grow_on_demand: self.grow_on_demand,
+            fused: self.fused,
+            fused_eof: self.fused_eof,
+            eof: self.eof,
+            trusted_read: self.trusted_read,
+        }
+    }
+
+    /// Mutate the current [`ReaderPolicy`](policy::ReaderPolicy) in-place.
+    ///
+    /// If you want to change the type, use `.set_policy()`.
+    pub fn policy_mut(&mut self) -> &mut P { &mut self.policy }
+
+    /// Inspect the current `ReaderPolicy`.
+    pub fn policy(&self) -> &P {
+        &self.policy
+    }
+
+    /// Move data to the start of the buffer, making room at the end for more 
+    /// reading.
+    ///
+    /// This is a no-op with the `*_ringbuf()` constructors (requires `slice-deque` feature).
+    pub fn make_room(&mut self) {
+        self.buf.make_room();
+    }
+
+    /// Move the data in the buffer down before reading into it if no more than `bytes` remain, so
+    /// the read has the whole free space to fill.
+    ///
+    /// A larger threshold means fewer, larger reads into the buffer at the cost of copying up to
+    /// `bytes` each time; this suits buffers which are large relative to the typical amount left
+    /// unconsumed. `0`, the default, disables this: data is never moved down unless the
+    /// [`ReaderPolicy`](policy::ReaderPolicy) does so, `.make_room()` is called or there is no
+    /// usable space left at all.
+    ///
+    /// This is a no-op with the `*_ringbuf()` constructors.
+    pub fn set_move_threshold(&mut self, bytes: usize) {
+        self.move_threshold = bytes;
+    }
+
+    /// Set whether the buffer is wiped before it is freed and when data is moved within it;
+    /// see [`Buffer::set_zeroize_on_drop()`](Buffer::set_zeroize_on_drop) for details.
+    ///
+    /// This includes dropping the buffer in `.into_inner()`, and the setting carries over to
+    /// buffers installed by `.detach_buf()` and `.restore_buf()`. Requires the `secure` feature.
+    #[cfg(feature = "secure")]
+    pub fn set_zeroize_on_drop(&mut self, zeroize: bool) {
+        self.buf.set_zeroize_on_drop(zeroize);
+    }
+
+    /// Ensure room in the buffer for *at least* `additional` bytes. May not be
+    /// quite exact due to implementation details of the buffer's allocator.
+    ///
+    /// The buffer is not grown beyond the limit set by `.set_max_capacity()`.
+    pub fn reserve(&mut self, additional: usize) {
+        self.regrow_cap = 0;
+        self.reserve_limited(additional);
+    }
+
+    /// Ensure the capacity of the buffer is *at least* `total` bytes, growing it by the difference
+    /// if it is smaller.
+    ///
+    /// Unlike `.reserve()` this doesn't depend on how much data is buffered, so calling it again
+    /// with the same value does nothing. The buffer is not grown beyond the limit set by
+    /// `.set_max_capacity()`.
+    pub fn ensure_capacity(&mut self, total: usize) {
+        self.ensure_capacity_limited(total);
+    }
+
+    /// Limit the capacity this `BufReader` may grow its buffer to to `max` bytes, e.g. to protect
+    /// against untrusted input driving it to gigabytes.
+    ///
+    /// Operations which would need a larger buffer, like `.fill_buf_min()` or `.peek()` for more
+    /// than `max` bytes, return an error of kind `OutOfMemory` instead of growing it.
+    /// `.reserve()` and `.mark()` only grow it up to `max`. If the buffer is already larger,
+    /// e.g. from `with_capacity()`, it is shrunk as with `.shrink()`.
+    ///
+    /// Resizing the buffer done by the [`ReaderPolicy`](policy::ReaderPolicy) is not limited.
+    /// There is no limit by default.
+    pub fn set_max_capacity(&mut self, max: usize) {
+        self.max_cap = Some(max);
+        self.regrow_cap = cmp::min(self.regrow_cap, max);
+        self.buf.shrink(max);
+    }
+
+    /// Get the limit set by `.set_max_capacity()`, if any.
+    pub fn max_capacity(&self) -> Option<usize> {
+        self.max_cap
+    }
+
+    // grow the capacity to `total` but not past the maximum, returning `false` if that wasn't
+    // possible
+    fn ensure_capacity_limited(&mut self, total: usize) -> bool {
+        let cap = self.capacity();
+        if cap >= total { return true; }
+
+        // `.reserve()` is relative to the usable space
+        let usable = self.buf.usable_space();
+        self.reserve_limited(usable + (total - cap))
+    }
+
+    // reserve `additional` bytes of usable space without growing the buffer past the maximum
+    // capacity, returning `false` if that wasn't possible
+    fn reserve_limited(&mut self, additional: usize) -> bool {
+        let max = match self.max_cap {
+            Some(max) => max,
+            None => {
+                self.buf.reserve(additional);
+                return true;
+            }
+        };
+
+        let usable = self.buf.usable_space();
+        if usable >= additional { return true; }
+
+        let used = self.buf.capacity() - usable;
+        let room = max.saturating_sub(used);
+
+        self.buf.reserve(cmp::min(additional, room));
+        room >= additional
+    }
+
+    /// Restore up to `amt` of the most recently consumed bytes to the buffer so they will be
+    /// returned again, returning the number of bytes actually restored.
+    ///
+    /// This only succeeds for bytes which are still present in the buffer; they are lost
+    /// when the buffer is moved down by `.make_room()` (which may be called by the
+    /// [`ReaderPolicy`](policy::ReaderPolicy)), grown, or read into after being emptied.
+    /// Seeking also discards them. See [`Buffer::unconsume()`](Buffer::unconsume) for details.
+    pub fn unconsume(&mut self, amt: usize) -> usize {
+        let restored = self.buf.unconsume(amt);
+
+        // restoring bytes from before the mark moves it back
+        if let Some(ref mut mark) = self.mark {
+            mark.consumed = mark.consumed.saturating_sub(restored);
+            self.buf.retain_consumed(mark.consumed);
+        }
+
+        restored
+    }
+
+    /// Save the current position in the stream so it can be returned to with `.reset()`, as long
+    /// as no more than `limit` bytes are consumed in the meantime.
+    ///
+    /// The consumed bytes are kept in the buffer until then, so this works even if the underlying
+    /// reader does not support seeking. The buffer is grown if its capacity is not larger than
+    /// `limit`, and `.make_room()` will not discard the bytes consumed since the mark.
+    ///
+    /// If `.set_max_capacity()` prevents growing the buffer, `limit` is reduced to fit.
+    ///
+    /// Replaces any previous mark.
+    pub fn mark(&mut self, mut limit: usize) {
+        // leave room to read into the buffer even when `limit` bytes are retained
+        if !self.ensure_capacity_limited(limit.saturating_add(1)) {
+            limit = cmp::min(limit, self.capacity().saturating_sub(1));
+        }
+
+        self.mark = Some(Mark { consumed: 0, limit });
+        self.buf.retain_consumed(0);
+    }
+
+    /// Return to the position saved by the last call to `.mark()`, so the bytes consumed since
+    /// will be returned again.
+    ///
+    /// The mark is kept so that this may be called several times.
+    ///
+    /// ### Errors
+    /// Returns an error of kind `InvalidInput` if there is no mark to return to: `.mark()` was
+    /// not called, or the mark was invalidated by consuming more than `limit` bytes or by seeking.
+    pub fn reset(&mut self) -> io::Result<()> {
+        let consumed = match self.mark {
+            Some(ref mut mark) => mem::replace(&mut mark.consumed, 0),
+            None => return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                              "BufReader::reset() called without a valid mark")),
+        };
+
+        let restored = self.buf.unconsume(consumed);
+        debug_assert_eq!(restored, consumed, "BufReader lost bytes retained for mark");
+        self.buf.retain_consumed(0);
+
+        Ok(())
+    }
+
+    // update the mark after consuming bytes, invalidating it if more than its limit were consumed
+    fn mark_consumed(&mut self, amt: usize) {
+        if let Some(mut mark) = self.mark.take() {
+            mark.consumed = mark.consumed.saturating_add(amt);
+
+            if mark.consumed <= mark.limit {
+                self.buf.retain_consumed(mark.consumed);
+                self.mark = Some(mark);
+            } else {
+                self.buf.retain_consumed(0);
+            }
+        }
+    }
+
+    /// Reduce the capacity of the buffer to `new_cap`, releasing the excess memory.
+    ///
+    /// Buffered data is moved down first and never discarded, so the capacity will not be
+    /// reduced below `.buf_len()`; bytes retained for `.reset()` are kept as well.
+    /// This is a no-op if `new_cap >= self.capacity()`.
+    ///
+    /// With the `*_ringbuf()` constructors the capacity is rounded up to a multiple of
+    /// the page size, so the buffer may not actually shrink.
+    pub fn shrink(&mut self, new_cap: usize) {
+        self.regrow_cap = 0;
+        self.buf.shrink(new_cap);
+    }
+
+    /// Move the data in the buffer down and shrink it to fit exactly, releasing all excess
+    /// memory, e.g. for idle readers.
+    ///
+    /// The capacity is restored before the buffer is next read into,
+    /// unless it was changed in the meantime.
+    pub fn shrink_to_fit(&mut self) {
+        let cap = self.capacity();
+        self.buf.shrink(0);
+
+        if self.capacity() < cap {
+            self.regrow_cap = cmp::max(self.regrow_cap, cap);
+        }
+    }
+
+    /// Automatically halve the capacity of the buffer, but not below `threshold`, once
+    /// `after_reads` consecutive reads into it have left fewer than `threshold` bytes buffered.
+    ///
+    /// This keeps memory usage proportional to the actual behavior of the stream, e.g. for
+    /// servers holding many connections. Buffered data is never discarded; see `.shrink()`.
+    ///
+    /// Disabled by default; `after_reads == 0` disables it again.
+    pub fn set_auto_shrink(&mut self, threshold: usize, after_reads: u32) {
+        self.auto_shrink = if after_reads > 0 {
+            Some(AutoShrink { threshold, after_reads, low_reads: 0 })
+        } else {
+            None
+        };
+    }
+
+    /// Get the most data the buffer has held after reading into it, since the reader was created
+    /// or `.reset_high_water_mark()` was called; e.g. for choosing the capacity for a workload.
+    ///
+    /// This is only updated when the buffer is read into, so data added by other means, like
+    /// `.push_bytes()`, counts on the next read.
+    pub fn high_water_mark(&self) -> usize {
+        self.high_water
+    }
+
+    /// Restart tracking `.high_water_mark()` from the amount of data currently in the buffer.
+    pub fn reset_high_water_mark(&mut self) {
+        self.high_water = self.buf_len();
+    }
+
+    // bookkeeping after a successful read into the buffer
+    fn after_read(&mut self) {
+        self.high_water = cmp::max(self.high_water, self.buf.len());
+        self.check_auto_shrink();
+    }
+
+    // track how full the buffer is after a read and shrink it if configured
+    fn check_auto_shrink(&mut self) {
+        let shrink_to = match self.auto_shrink {
+            Some(ref mut auto) => {
+                if self.buf.len() >= auto.threshold {
+                    auto.low_reads = 0;
+                    return;
+                }
+
+                auto.low_reads += 1;
+
+                if auto.low_reads < auto.after_reads { return; }
+
+                auto.low_reads = 0;
+                cmp::max(self.buf.capacity() / 2, auto.threshold)
+            },
+            None => return,
+        };
+
+        self.buf.shrink(shrink_to);
+    }
+
+    // resize or compact the buffer as configured before reading into it
+    fn prepare_read(&mut self) {
+        self.regrow();
+
+        let len = self.buf.len();
+
+        if len > 0 && len <= self.move_threshold
+            && self.buf.usable_space() < self.buf.free_space() {
+            self.buf.make_room();
+        }
+    }
+
+    // make sure a read into the buffer can make progress, reclaiming consumed space or growing
+    fn ensure_read_room(&mut self) -> io::Result<()> {
+        if self.buf.usable_space() == 0 {
+            self.buf.make_room();
+        }
+
+        if self.buf.usable_space() == 0 {
+            let grow_by = cmp::max(self.capacity(), MIN_BUF_SIZE);
+            self.reserve_limited(grow_by);
+        }
+
+        if self.buf.usable_space() == 0 {
+            return Err(max_capacity_exceeded());
+        }
+
+        Ok(())
+    }
+
+    // restore the capacity from before `.shrink_to_fit()`
+    #[inline]
+    fn regrow(&mut self) {
+        if self.regrow_cap == 0 { return; }
+
+        let cap = mem::replace(&mut self.regrow_cap, 0);
+
+        let cap = cmp::min(cap, self.max_cap.unwrap_or(cap));
+
+        if cap > self.capacity() {
+            let len = self.buf.len();
+            self.buf.reserve(cap - len);
+        }
+    }
+
+    /// Take the buffer out of this reader as a `Vec` containing the buffered data, e.g. to give
+    /// to an API which wants one; the capacity of the vector is that of the buffer.
+    ///
+    /// This reader is left with an empty buffer, for which memory isn't allocated until it's read
+    /// into again (with the previous capacity) or `.restore_buf()` is called. Any mark set with
+    /// `.mark()` is cleared. See [`Buffer::into_vec()`](Buffer::into_vec) for when this copies.
+    ///
+    /// Data pending in the spill file set by `.set_spill()` is moved to the end of the buffer
+    /// first, so it's included; the spill file stays set.
+    ///
+    /// ### Panics
+    /// If reading the spill file fails.
+    pub fn detach_buf(&mut self) -> Vec<u8> {
+        self.unspill();
+
+        let empty = Buffer { buf: self.buf.buf.empty_like() };
+        let buf = mem::replace(&mut self.buf, empty);
+
+        self.mark = None;
+        self.regrow_cap = cmp::max(self.regrow_cap, buf.capacity());
+
+        buf.into_vec()
+    }
+
+    /// Replace the buffer of this reader with `buf`, all of which is then returned as buffered
+    /// data ahead of the underlying reader, e.g. after `.detach_buf()`.
+    ///
+    /// The allocation of `buf` is adopted without copying, even for ringbuffers or aligned buffers
+    /// which it then replaces. Any data still in the current buffer is discarded, and any mark
+    /// is cleared.
+    pub fn restore_buf(&mut self, buf: Vec<u8>) {
+        let zeroize = self.buf.buf.zeroize();
+        self.buf = Buffer::from_vec(buf);
+        self.buf.buf.set_zeroize(zeroize);
+        self.mark = None;
+        self.inner_offset = None;
+    }
+
+    /// Exchange the buffers of this reader and `other`, along with the data in them, without
+    /// copying; e.g. to hand already buffered bytes off to a new reader during a protocol upgrade.
+    ///
+    /// The readers may have different types; only the buffers are touched. Any marks set with
+    /// `.mark()` are cleared, and data in the spill file is moved into the buffer first so it's
+    /// handed off along with it.
+    ///
+    /// The data handed off counts toward `.total_read()` of the reader receiving it instead, so
+    /// `.consumer_offset()` of either reader doesn't change. As with `.push_bytes()`, the
+    /// positions of the underlying readers are no longer known. `.is_eof()` and `.set_fused()`
+    /// describe the underlying readers and so are kept; a fused reader at EOF returns the data
+    /// it received and then `0`.
+    ///
+    /// ### Panics
+    /// If reading the spill file fails.
+    pub fn swap_buffers<R_, P_>(&mut self, other: &mut BufReader<R_, P_>) {
+        self.unspill();
+        other.unspill();
+
+        let (len, other_len) = (self.buf_len() as u64, other.buf_len() as u64);
+        mem::swap(&mut self.buf, &mut other.buf);
+
+        self.read_count = self.read_count.wrapping_sub(len).wrapping_add(other_len);
+        other.read_count = other.read_count.wrapping_sub(other_len).wrapping_add(len);
+        self.inner_offset = None;
+        other.inner_offset = None;
+
+        self.mark = None;
+        self.buf.retain_consumed(0);
+        other.mark = None;
+        other.buf.retain_consumed(0);
+    }
+
+    /// Get the section of the buffer containing valid data; may be empty.
+    ///
+    /// Call `.consume()` to remove bytes from the beginning of this section.
+    pub fn buffer(&self) -> &[u8] {
+        self.buf.buf()
+    }
+
+    /// Append `bytes` to the buffer, to be returned after the data already in it but before
+    /// anything read from the underlying reader afterwards; e.g. data received out of band.
+    ///
+    /// The buffer is grown if necessary, regardless of `.set_max_capacity()`.
+    pub fn push_bytes(&mut self, bytes: &[u8]) {
+        self.inner_offset = None;
+        self.buf.push_bytes(bytes);
+    }
+
+    /// Insert `bytes` in front of the data in the buffer, to be returned before it; e.g. data
+    /// which logically precedes what the underlying reader will produce next.
+    ///
+    /// See [`Buffer::prepend_bytes()`](Buffer::prepend_bytes) for how room is made; the buffer is
+    /// grown if necessary, regardless of `.set_max_capacity()`. Any mark set with `.mark()` is
+    /// cleared, as the consumed bytes it relies on may be overwritten.
+    pub fn prepend_bytes(&mut self, bytes: &[u8]) {
+        self.inner_offset = None;
+        self.mark = None;
+        self.eof = false;
+        self.buf.retain_consumed(0);
+        self.buf.prepend_bytes(bytes);
+    }
+
+    /// Get the section of the buffer containing valid data mutably, e.g. to decrypt it in place
+    /// before consuming it; may be empty.
+    ///
+    /// The transformation cannot change the length of the data. From the perspective of the
+    /// underlying reader these bytes have already been read, so whatever is written here is what
+    /// later reads from this `BufReader` return.
+    pub fn get_buf_mut(&mut self) -> &mut [u8] {
+        self.buf.buf_mut()
+    }
+
+    /// Get the current number of bytes available in the buffer.
+    ///
+    /// This doesn't include data in the spill file set by `.set_spill()`; see
+    /// `.total_pending()`.
+    pub fn buf_len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Get the number of bytes read from the underlying reader but not consumed yet: the data in
+    /// the buffer plus any in the spill file set by `.set_spill()`.
+    pub fn total_pending(&self) -> u64 {
+        self.buf_len() as u64 + self.spilled()
+    }
+
+    /// Spill lookahead that doesn't fit in the buffer into `file`, which should be a new
+    /// temporary file opened for reading and writing; see `.read_ahead()`.
+    ///
+    /// The buffer is kept to `threshold` bytes of lookahead; beyond that, `.read_ahead()` writes
+    /// the data to `file`. Spilled data is replayed in order when the buffer is refilled, before
+    /// anything else is read from the underlying reader, so the stream is unaffected.
+    ///
+    /// Spilled data is discarded by seeking, and lost if the reader is taken apart with
+    /// `.into_inner()` or similar; it is not reflected by `.buf_len()` or `.buffer()`.
+    ///
+    /// ### Errors
+    /// Returns an error of kind `InvalidInput`, leaving the current spill file in place, if it
+    /// still has data pending.
+    pub fn set_spill(&mut self, file: File, threshold: usize) -> io::Result<()> {
+        if self.spilled() > 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "BufReader spill file still has data pending"));
+        }
+
+        self.spill = Some(Spill::new(file, threshold));
+        Ok(())
+    }
+
+    // the number of bytes pending in the spill file
+    fn spilled(&self) -> u64 {
+        self.spill.as_ref().map_or(0, Spill::pending)
+    }
+
+    // copy the data pending in the buffer and the spill file into a new buffer of the same kind
+    fn copy_pending(&self) -> io::Result<Buffer> {
+        let mut buf = Buffer { buf: self.buf.buf.empty_like() };
+        buf.reserve(self.capacity());
+        buf.push_bytes(self.buffer());
+
+        if let Some(ref spill) = self.spill {
+            buf.push_bytes(&spill.copy_pending()?);
+        }
+
+        Ok(buf)
+    }
+
+    // append the data pending in the spill file to the buffer, for methods which hand out the
+    // buffer; the data was already taken from `inner` so it can't be dropped
+    fn unspill(&mut self) {
+        if let Some(ref mut spill) = self.spill {
+            if spill.pending() > 0 {
+                let data = spill.copy_pending().expect("failed to read the BufReader spill file");
+                self.buf.push_bytes(&data);
+                spill.clear();
+            }
+        }
+    }
+
+    /// Get the number of bytes read from the underlying reader through this `BufReader`.
+    ///
+    /// This counts reads into the buffer (including the spill file) as well as reads bypassing
+    /// it, but not reads made through `.get_mut()`. If the underlying reader started at the
+    /// beginning of the stream, this is its position even if it doesn't implement `Seek`, e.g. for
+    /// error messages. The count wraps around on overflow.
+    ///
+    /// The count is carried over by `.unbuffer()`; see `Unbuffer::total_read()`.
+    pub fn total_read(&self) -> u64 {
+        self.read_count
+    }
+
+    /// Get the number of bytes consumed from this reader so far: `.total_read()` minus
+    /// `.total_pending()`.
+    ///
+    /// Data added to the buffer with `.push_bytes()` or similar reduces this while it's pending;
+    /// it saturates at zero.
+    pub fn consumer_offset(&self) -> u64 {
+        self.read_count.saturating_sub(self.total_pending())
+    }
+
+    // account for `read` bytes read from the underlying reader
+    fn count_read(&mut self, read: usize) {
+        self.read_count = self.read_count.wrapping_add(read as u64);
+    }
+
+    // account for `read` bytes read from the underlying reader past the empty buffer
+    fn count_bypassed(&mut self, read: usize) {
+        self.count_read(read);
+
+        // the consumed bytes no longer come right before the current position
+        if read > 0 { self.buf.clear(); }
+    }
+
+    // track the result of a read from `inner` into a non-empty slice for `.is_eof()`
+    // and `.set_fused()`
+    fn check_eof(&mut self, read: usize) {
+        self.eof = read == 0;
+        if self.eof && self.fused { self.fused_eof = true; }
+    }
+
+    // whether a read of `len` bytes may go directly to the underlying reader
+    fn may_bypass(&self, len: usize) -> bool {
+        // bytes need to be kept for `reset()` or read after the spilled ones; after a fused EOF
+        // the buffer returns 0 without reading
+        if self.mark.is_some() || self.spilled() != 0 || self.fused_eof { return false; }
+
+        self.bypass_threshold().is_some_and(|threshold| len >= threshold)
+    }
+
+    // the position of the next byte to be read from this reader, if known
+    fn known_position(&self) -> Option<u64> {
+        self.inner_offset.map(|offset| {
+            offset.wrapping_add(self.read_count).wrapping_sub(self.total_pending())
+        })
+    }
+
+    /// Get the position of this reader in the stream without querying the underlying reader.
+    ///
+    /// This is the position last reported by seeking or `.stream_position()`, adjusted for the
+    /// bytes consumed since then. If there is none, it's the number of bytes consumed since this
+    /// `BufReader` was created, i.e. the position relative to where the underlying reader started.
+    ///
+    /// Reads made through `.get_mut()` aren't accounted for, and adding data to the buffer with
+    /// e.g. `.push_bytes()` resets the position to the latter kind.
+    pub fn buffered_offset(&self) -> u64 {
+        self.known_position().unwrap_or_else(|| self.consumer_offset())
+    }
+
+    /// Enable or disable serving seeks from the buffer when possible; disabled by default, in
+    /// which case seeking always discards the buffer.
+    ///
+    /// Once a seek or `.stream_position()` has told this reader the position of the underlying
+    /// reader, later seeks with `SeekFrom::Start(_)` or `SeekFrom::Current(_)` whose target lies
+    /// within the buffered data, or within consumed bytes still present in the buffer, just move
+    /// the position in the buffer like `.seek_relative()` without calling `inner.seek()`. Other
+    /// seeks behave as usual.
+    ///
+    /// The position is tracked across all reads from the underlying reader through this
+    /// `BufReader`, but not across reads through `.get_mut()`. Adding data to the buffer by other
+    /// means, e.g. `.push_bytes()`, forgets it until the next seek.
+    pub fn set_buffer_aware_seek(&mut self, enabled: bool) {
+        self.buffer_aware_seek = enabled;
+    }
+
+    /// Enable or disable greedy refills; disabled by default.
+    ///
+    /// When enabled, once the [`ReaderPolicy`](policy::ReaderPolicy) decides `.fill_buf()` should
+    /// read into the buffer, it keeps reading until the buffer is full, a read returns 0 or a read
+    /// fails with `WouldBlock`, instead of returning after the first read. This amortizes the cost
+    /// of each `.fill_buf()` when the underlying reader returns small amounts at a time, like a
+    /// pipe fed in small writes, at the cost of waiting for more data before returning any.
+    ///
+    /// A `WouldBlock` error after some data was read in the same call isn't returned, so no data
+    /// is held back; the next call tries again.
+    pub fn set_fill_full(&mut self, enabled: bool) {
+        self.fill_full = enabled;
+    }
+
+    /// Set how large a read must be to skip the buffer; by default, reads at least as large as
+    /// the capacity of the buffer.
+    ///
+    /// When the buffer is empty, `read()`, `read_vectored()` and `read_exact()` with an output
+    /// buffer of at least `threshold` bytes read directly into it instead of copying through the
+    /// buffer. With `None`, all reads go through the buffer, which is preferable if every read
+    /// from the underlying reader is expensive regardless of size, so it should read as much as
+    /// possible at once. This also makes `read_to_end()` and `read_to_string()` fill the buffer
+    /// instead of forwarding to the underlying reader.
+    ///
+    /// Reads still go through the buffer while a mark is set or data is spilled.
+    pub fn set_bypass_threshold(&mut self, threshold: Option<usize>) {
+        self.bypass = match threshold {
+            Some(threshold) => Bypass::AtLeast(threshold),
+            None => Bypass::Never,
+        };
+    }
+
+    /// Get how large a read must be to skip the buffer; see `.set_bypass_threshold()`.
+    pub fn bypass_threshold(&self) -> Option<usize> {
+        match self.bypass {
+            Bypass::Capacity => Some(self.buf.capacity()),
+            Bypass::AtLeast(threshold) => Some(threshold),
+            Bypass::Never => None,
+        }
+    }
+
+    /// Keep the data searched by `.read_until()` (and so `.read_line()`), `.read_until_limited()`
+    /// and `.read_until_slice()` in the buffer until the delimiter is found, growing it to hold
+    /// up to `max` bytes instead of copying the data out and refilling it.
+    ///
+    /// The whole match is contiguous in the buffer when it's copied out and consumed, so it can
+    /// be restored in one piece with `.unconsume()`; for a borrowed search, see
+    /// `.find_byte_filled()`. The buffer isn't grown beyond the limit set by
+    /// `.set_max_capacity()`; if the delimiter isn't found within the limit, the search
+    /// continues by copying out the data as usual.
+    ///
+    /// Disabled by default; `max == 0` disables it again.
+    pub fn set_grow_on_demand(&mut self, max: usize) {
+        self.grow_on_demand = max;
+    }
+
+    /// Stop reading from the underlying reader once it reports EOF; disabled by default.
+    ///
+    /// When enabled, after a read from the underlying reader returns `Ok(0)`, reads from this
+    /// `BufReader` return whatever is still buffered and then `0` (or an empty slice from
+    /// `.fill_buf()`) without calling it again. This is for readers which misbehave when read
+    /// after EOF, like blocking or returning garbage, and saves the calls when polling a finished
+    /// stream. Reading resumes after `.reset_eof()`, a seek or disabling this.
+    pub fn set_fused(&mut self, fused: bool) {
+        self.fused = fused;
+        self.fused_eof = false;
+    }
+
+    /// Allow reading from the underlying reader again after it reported EOF; see `.set_fused()`.
+    pub fn reset_eof(&mut self) {
+        self.fused_eof = false;
+    }
+
+    /// Read into the buffer with `TrustRead::read_uninit()`, so memory which has never been
+    /// written isn't zeroed first; disabled by default. See [`TrustRead`](TrustRead).
+    ///
+    /// This applies to every read into the buffer, like `.fill_buf()`, `read()` through the buffer
+    /// and `.read_into_buf()`, so it gains most the first time the buffer is filled after it's
+    /// allocated or grown. It's reset when the underlying reader is replaced or wrapped, like by
+    /// `.map_inner()` or `.boxed()`, as the new reader may not implement `TrustRead`.
+    pub fn set_trusted_reads(&mut self, enabled: bool) where R: TrustRead + Sized {
+        self.trusted_read = if enabled { Some(read_uninit_erased::<R>) } else { None };
+    }
+
+    /// Check if the last read from the underlying reader returned 0, i.e. it reported EOF.
+    ///
+    /// This doesn't read anything, so it's `false` until a read actually hits EOF, and it
+    /// doesn't mean no data is left: bytes may still be buffered. Together with
+    /// `.buf_len() == 0`, it tells whether the stream ended cleanly after a parsing loop without
+    /// attempting another read; see also `.has_data_left()`. Reset by seeking and
+    /// `.prepend_bytes()`.
+    pub fn is_eof(&self) -> bool {
+        self.eof
+    }
+
+    /// Search the buffer for `byte` with `memchr`, returning its offset from the start of
+    /// the buffered data.
+    ///
+    /// Does not read from the underlying reader or consume anything; see `.find_byte_filled()`
+    /// for a version that reads into the buffer if necessary.
+    pub fn find_byte(&self, byte: u8) -> Option<usize> {
+        memchr::memchr(byte, self.buffer())
+    }
+
+    /// Get the total buffer capacity.
+    pub fn capacity(&self) -> usize {
+        self.buf.capacity()
+    }
+
+    /// Get the number of bytes that can be read into the buffer before it needs to grow or the
+    /// data in it needs to be moved; see [`Buffer::usable_space()`](Buffer::usable_space).
+    pub fn headroom(&self) -> usize {
+        self.buf.usable_space()
+    }
+
+    /// Get the number of bytes at the head of the buffer that aren't available for reading into
+    /// until `.make_room()` moves the data down; always `0` with the `*_ringbuf()` constructors,
+    /// unless bytes are being retained for `.unconsume()`.
+    pub fn consumed(&self) -> usize {
+        self.buf.free_space() - self.buf.usable_space()
+    }
+
+    /// Get a snapshot of the layout of the buffer; see [`BufferState`](BufferState).
+    pub fn buffer_state(&self) -> BufferState {
+        let pos = self.consumed();
+
+        BufferState {
+            pos,
+            cap: pos + self.buf_len(),
+            capacity: self.capacity(),
+            headroom: self.headroom(),
+        }
+    }
+
+    /// Get an immutable reference to the underlying reader.
+    pub fn get_ref(&self) -> &R { &self.inner }
+
+    /// Get a mutable reference to the underlying reader.
+    ///
+    /// ## Note
+    /// Reading directly from the underlying reader is not recommended, as some
+    /// data has likely already been moved into the buffer. Call `.resync()` or
+    /// `.resync_position()` afterwards to discard the stale buffer.
+    pub fn get_mut(&mut self) -> &mut R { &mut self.inner }
+
+    /// Discard all buffered data, like seeking does, after the underlying reader was used
+    /// directly through `.get_mut()`.
+    ///
+    /// This also discards the mark and any data in the spill file, and forgets the position of the
+    /// underlying reader; see `.resync_position()` to query it again.
+    pub fn resync(&mut self) {
+        self.buf.clear();
+        self.mark = None;
+        self.inner_offset = None;
+
+        if let Some(ref mut spill) = self.spill {
+            spill.clear();
+        }
+    }
+
+    /// Consume `self` and return the inner reader only.
+    pub fn into_inner(self) -> R where R: Sized {
+        self.inner
+    }
+
+    /// Consume `self` and return both the underlying reader and the buffer.
+    ///
+    /// If the buffer came from a [`BufferPool`](BufferPool), it is detached from it and won't
+    /// be returned to it.
+    ///
+    /// Data pending in the spill file set by `.set_spill()` is moved to the end of the buffer
+    /// first, so it's included.
+    ///
+    /// See also: `BufReader::unbuffer()`
+    ///
+    /// ### Panics
+    /// If reading the spill file fails.
+    pub fn into_inner_with_buffer(mut self) -> (R, Buffer) where R: Sized {
+        self.unspill();
+        self.buf.retain_consumed(0);
+        self.buf.buf.detach_pool();
+        (self.inner, self.buf)
+    }
+
+    /// Consume `self` and return the underlying reader, the buffer as a `Vec` of its full
+    /// capacity, and the length of the buffered data, which is moved to the front of the `Vec`.
+    ///
+    /// Pass these to `.from_parts(inner, buf, 0, len)` to reconstruct the reader, or reuse the
+    /// allocation elsewhere once the pending bytes have been read out. The buffer is not copied
+    /// in the cases described for [`Buffer::into_vec()`](Buffer::into_vec). The policy and any
+    /// mark are discarded. Data pending in the spill file set by `.set_spill()` is included.
+    ///
+    /// ### Panics
+    /// If reading the spill file fails.
+    pub fn into_parts(mut self) -> (R, Vec<u8>, usize) where R: Sized {
+        self.unspill();
+        self.buf.retain_consumed(0);
+        let (buf, len) = self.buf.buf.into_vec(true);
+        (self.inner, buf, len)
+    }
+
+    /// Consume `self` and return an adapter which writes every byte read or consumed from it
+    /// to `sink`, exactly once and in order.
+    ///
+    /// See `TeeReader` for details.
+    pub fn tee<W: Write>(self, sink: W) -> TeeReader<R, W, P> where R: Sized {
+        TeeReader {
+            reader: self,
+            sink,
+            err: None,
+        }
+    }
+
+    /// Consume `self` and return an adapter which implements `Read` and will
+    /// empty the buffer before reading directly from the underlying reader.
+    ///
+    /// Data pending in the spill file set by `.set_spill()` is moved into the buffer first.
+    ///
+    /// ### Panics
+    /// If reading the spill file fails.
+    pub fn unbuffer(mut self) -> Unbuffer<R> where R: Sized {
+        self.unspill();
+        self.buf.retain_consumed(0);
+
+        Unbuffer {
+            inner: self.inner,
+            // an empty buffer is released right away
+            buf: if self.buf.is_empty() { None } else { Some(self.buf) },
+            read_count: self.read_count,
+        }
+    }
+
+    /// Replace the underlying reader with `f(inner)`, e.g. to wrap it in a TLS stream after
+    /// peeking at the first bytes.
+    ///
+    /// The buffer, policy and settings carry over unchanged, so the buffered data is still
+    /// returned before anything read from the new reader. What this reader knows about the old
+    /// reader doesn't: EOF (see `.is_eof()` and `.set_fused()`) and its position as last reported
+    /// by seeking (see `.buffered_offset()`) are reset, and so is `.set_trusted_reads()`. See
+    /// `.try_map_inner()` for a fallible version.
+    pub fn map_inner<R_, F: FnOnce(R) -> R_>(self, f: F) -> BufReader<R_, P> where R: Sized {
+        let mut reader = self.wrap_inner(f);
+        reader.inner_offset = None;
+        reader.fused_eof = false;
+        reader.eof = false;
+        reader
+    }
+
+    // replace `inner` with `f(inner)`, which continues the same stream, so only
+    // `.set_trusted_reads()` is reset
+    fn wrap_inner<R_, F: FnOnce(R) -> R_>(self, f: F) -> BufReader<R_, P> where R: Sized {
+        BufReader {
+            inner: f(self.inner),
+            buf: self.buf,
+            policy: self.policy,
+            mark: self.mark,
+            regrow_cap: self.regrow_cap,
+            auto_shrink: self.auto_shrink,
+            max_cap: self.max_cap,
+            move_threshold: self.move_threshold,
+            spill: self.spill,
+            high_water: self.high_water,
+            read_count: self.read_count,
+            inner_offset: self.inner_offset,
+            buffer_aware_seek: self.buffer_aware_seek,
+            fill_full: self.fill_full,
+            bypass: self.bypass,
+            grow_on_demand: self.grow_on_demand,
+            fused: self.fused,
+            fused_eof: self.fused_eof,
+            eof: self.eof,
+            trusted_read: None,
+        }
+    }
+
+    /// Replace the underlying reader with `f(inner)` like `.map_inner()`, if it succeeds.
+    ///
+    /// On failure, `f` must return the original reader along with the error so it can be put
+    /// back; `self` is then returned unchanged along with the error.
+    // `BufReader` is larger than clippy's limit for error types; boxing the error would cost an
+    // allocation on every failure just to hand back `self`, which `IntoInnerError` doesn't either
+    #[allow(clippy::result_large_err)]
+    pub fn try_map_inner<R_, E, F>(self, f: F) -> Result<BufReader<R_, P>, (Self, E)>
+    where R: Sized, F: FnOnce(R) -> Result<R_, (R, E)> {
+        let trusted_read = self.trusted_read;
+        let mut reader = self.wrap_inner(Some);
+        let inner = reader.inner.take().expect("inner reader was just wrapped");
+
+        match f(inner) {
+            Ok(inner) => Ok(reader.map_inner(|_| inner)),
+            Err((inner, e)) => {
+                let mut reader = reader.wrap_inner(|_| inner);
+                reader.trusted_read = trusted_read;
+                Err((reader, e))
+            },
+        }
+    }
+}
+
+impl<R: ?Sized, P: ReaderPolicy> BufReader<R, P> {
+    #[inline]
+    fn should_read(&mut self) -> bool {
+        self.policy.before_read(&mut self.buf).0
+    }
+
+    // the body of `BufRead::consume()`, which doesn't need `R: Read`
+    fn consume_buffered(&mut self, mut amt: usize) {
+        amt = cmp::min(amt, self.buf_len());
+        self.buf.consume(amt);
+        self.mark_consumed(amt);
+        self.policy.after_consume(&mut self.buf, amt);
+    }
+
+    // move the position in the buffer by `offset` if it stays within the buffered or
+    // still-restorable consumed bytes; returns `false` without changing anything otherwise
+    fn move_buffered(&mut self, offset: i64) -> bool {
+        let magnitude = offset.unsigned_abs();
+
+        if offset >= 0 && magnitude <= self.buf_len() as u64 {
+            self.consume_buffered(magnitude as usize);
+            return true;
+        }
+
+        if offset < 0 && magnitude <= usize::MAX as u64 {
+            let restored = self.unconsume(magnitude as usize);
+            if restored as u64 == magnitude { return true; }
+
+            // not all of them were still there
+            self.consume_buffered(restored);
+        }
+
+        false
+    }
+
+    /// Consume up to `n` bytes from the buffer and return the slice that was consumed.
+    ///
+    /// Does not perform any I/O; if fewer than `n` bytes are buffered, all of them are consumed.
+    /// The bytes remain valid until the next call that mutably borrows `self`. Otherwise
+    /// equivalent to `BufRead::consume()`.
+    pub fn consume_and_get(&mut self, n: usize) -> &[u8] {
+        let amt = cmp::min(n, self.buf_len());
+        self.buf.consume(amt);
+        self.mark_consumed(amt);
+
+        // don't let the policy move the consumed bytes out from under us
+        let marked = self.mark.as_ref().map_or(0, |mark| mark.consumed);
+        self.buf.retain_consumed(cmp::max(amt, marked));
+        self.policy.after_consume(&mut self.buf, amt);
+        self.buf.retain_consumed(marked);
+
+        self.buf.consumed_tail(amt)
+    }
+
+    /// Copy up to `n` bytes out of the buffer into a new `Vec` and consume them.
+    ///
+    /// Does not perform any I/O; if fewer than `n` bytes are buffered, all of them are taken.
+    /// See `.split_to_exact()` for a version that reads from the underlying reader if necessary.
+    pub fn split_to(&mut self, n: usize) -> Vec<u8> {
+        self.consume_and_get(n).to_vec()
+    }
+}
+
+impl<R: Read + ?Sized, P> BufReader<R, P> {
+    /// Convert into a `std::io::BufReader` of the same capacity, returning the data remaining in
+    /// the buffer alongside it.
+    ///
+    /// A `std::io::BufReader` can't be given data to return first, so the returned data must be
+    /// read before anything from the new reader to keep the stream intact (e.g. with
+    /// `Read::chain()`). Data pending in the spill file set by `.set_spill()` is included.
+    ///
+    /// ### Panics
+    /// If reading the spill file fails.
+    pub fn into_std(self) -> (io::BufReader<R>, Vec<u8>) where R: Sized {
+        let capacity = self.capacity();
+        let (inner, buf) = self.into_inner_with_buffer();
+        (io::BufReader::with_capacity(capacity, inner), buf.into_vec())
+    }
+
+    /// Unconditionally perform a read into the buffer.
+    ///
+    /// Does not invoke `ReaderPolicy` methods.
+    ///
+    /// If there is no usable space in the buffer, room is made first by moving the data down or,
+    /// if the buffer is completely full, by growing it; so a read is always attempted.
+    /// 
+    /// If the read was successful, returns the number of bytes read by this call, which is only
+    /// `0` if the underlying reader is at EOF. This is *not* the number of bytes now available;
+    /// use `.buf_len()` for that.
+    ///
+    /// ### Errors
+    /// Returns an error of kind `OutOfMemory` if the buffer is full and `.set_max_capacity()`
+    /// doesn't allow it to grow, as well as any error from the underlying reader.
+    pub fn read_into_buf(&mut self) -> io::Result<usize> {
+        if self.fused_eof && self.spilled() == 0 { return Ok(0); }
+
+        self.prepare_read();
+        self.ensure_read_room()?;
+
+        // spilled data comes before anything still in the underlying reader
+        let read = match self.spill {
+            Some(ref mut spill) if spill.pending() > 0 => self.buf.read_from_trusted(spill)?,
+            _ => {
+                let read = match self.trusted_read {
+                    Some(read_uninit) => {
+                        let inner = &mut self.inner as *mut R as *mut u8;
+                        // only set to `read_uninit_erased::<R>`, which `inner` still is after an
+                        // unsizing coercion
+                        unsafe { self.buf.read_uninit_with(|out| read_uninit(inner, out))? }
+                    },
+                    None => self.buf.read_from(&mut self.inner)?,
+                };
+                self.count_read(read);
+                self.check_eof(read);
+                read
+            },
+        };
+
+        if read > 0 {
+            self.after_read();
+        }
+
+        Ok(read)
+    }
+
+    /// Like `.read_into_buf()`, but gives uninitialized memory to the underlying reader directly
+    /// instead of zeroing it first; see [`TrustRead`](TrustRead).
+    ///
+    /// To do this for every read into the buffer, including from `.fill_buf()` and `read()`, use
+    /// `.set_trusted_reads()` instead.
+    pub fn read_into_buf_trusted(&mut self) -> io::Result<usize> where R: TrustRead {
+        if self.fused_eof && self.spilled() == 0 { return Ok(0); }
+
+        self.prepare_read();
+        self.ensure_read_room()?;
+
+        let read = match self.spill {
+            Some(ref mut spill) if spill.pending() > 0 => self.buf.read_from_trusted(spill)?,
+            _ => {
+                let read = self.buf.read_from_trusted(&mut self.inner)?;
+                self.count_read(read);
+                self.check_eof(read);
+                read
+            },
+        };
+
+        if read > 0 {
+            self.after_read();
+        }
+
+        Ok(read)
+    }
+
+    /// Read from the underlying reader until the buffer contains at least `min` bytes
+    /// or the reader reaches EOF, then return the entire section of the buffer containing
+    /// valid data.
+    ///
+    /// If there is not enough usable space at the tail of the buffer, data is moved down
+    /// with `.make_room()`; if `min` exceeds the capacity of the buffer, it will be grown.
+    ///
+    /// If EOF is reached first, the returned slice will be shorter than `min`; this is
+    /// not considered an error. Does not invoke `ReaderPolicy` methods.
+    ///
+    /// ### Errors
+    /// Returns an error of kind `OutOfMemory` if the buffer would have to grow beyond the limit
+    /// set by `.set_max_capacity()`.
+    pub fn fill_buf_min(&mut self, min: usize) -> io::Result<&[u8]> {
+        while self.buf_len() < min {
+            let needed = min - self.buf_len();
+
+            if self.buf.usable_space() < needed {
+                self.buf.make_room();
+            }
+
+            if self.buf.usable_space() < needed && !self.reserve_limited(needed) {
+                return Err(max_capacity_exceeded());
+            }
+
+            match self.read_into_buf() {
+                Ok(0) => break,
+                Ok(_) => (),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(self.buffer())
+    }
+
+    /// Read ahead from the underlying reader until at least `n` bytes are pending, in the buffer
+    /// and the spill file set by `.set_spill()` combined, or it reaches EOF; returns
+    /// `.total_pending()`.
+    ///
+    /// The buffer is filled first, growing it to hold up to `n` bytes but no more than the
+    /// threshold given to `.set_spill()`; the rest goes to the spill file. Without a spill file
+    /// this is like `.fill_buf_min(n)`. Does not invoke `ReaderPolicy` methods.
+    ///
+    /// ### Errors
+    /// Returns an error of kind `OutOfMemory` if the buffer would have to grow beyond the limit
+    /// set by `.set_max_capacity()`, as well as any error from the underlying reader or while
+    /// writing the spill file.
+    pub fn read_ahead(&mut self, n: u64) -> io::Result<u64> {
+        let in_memory = match self.spill {
+            Some(ref spill) => cmp::min(n, spill.threshold() as u64) as usize,
+            None => cmp::min(n, usize::MAX as u64) as usize,
+        };
+
+        self.fill_buf_min(in_memory)?;
+
+        loop {
+            let pending = self.total_pending();
+
+            let spill = match self.spill {
+                Some(ref mut spill) if pending < n && !self.fused_eof => spill,
+                _ => return Ok(pending),
+            };
+
+            match spill.fill_from(&mut self.inner, n - pending) {
+                Ok(0) => {
+                    self.check_eof(0);
+                    return Ok(pending);
+                },
+                Ok(copied) => {
+                    self.read_count = self.read_count.wrapping_add(copied);
+                    self.eof = false;
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Get a slice of *up to* `n` bytes from the buffer without consuming them, reading from
+    /// the underlying reader until that many bytes are available or it reaches EOF.
+    ///
+    /// See `.fill_buf_min()` for details. A subsequent `fill_buf()` will return
+    /// (at least) the same data.
+    pub fn peek(&mut self, n: usize) -> io::Result<&[u8]> {
+        let buf = self.fill_buf_min(n)?;
+        Ok(&buf[..cmp::min(n, buf.len())])
+    }
+
+    /// Check if the stream starts with `prefix`, without consuming anything.
+    ///
+    /// Reads from the underlying reader until at least `prefix.len()` bytes are buffered or it
+    /// reaches EOF; see `.fill_buf_min()`. Returns `false` if the stream is shorter than
+    /// `prefix`.
+    pub fn starts_with(&mut self, prefix: &[u8]) -> io::Result<bool> {
+        Ok(self.fill_buf_min(prefix.len())?.starts_with(prefix))
+    }
+
+    /// Check if there is any data left to read, like the unstable `BufRead::has_data_left()`.
+    ///
+    /// Only reads from the underlying reader if the buffer is empty; see `.fill_buf_min()`.
+    /// Nothing is consumed. See also `.is_eof()`, which doesn't read.
+    pub fn has_data_left(&mut self) -> io::Result<bool> {
+        self.fill_buf_min(1).map(|buf| !buf.is_empty())
+    }
+
+    /// Return the first of `candidates` the stream starts with, if any, without consuming
+    /// anything.
+    ///
+    /// Useful for detecting file formats by their magic numbers:
+    ///
+    /// ```rust
+    /// # use buf_redux::BufReader;
+    /// const GZIP: &[u8] = b"\x1F\x8B";
+    /// const ZIP: &[u8] = b"PK\x03\x04";
+    ///
+    /// let mut reader = BufReader::new(&b"PK\x03\x04..."[..]);
+    /// assert_eq!(reader.detect(&[GZIP, ZIP]).unwrap(), Some(ZIP));
+    /// ```
+    ///
+    /// Reads into the buffer like `.starts_with()` for the longest candidate.
+    pub fn detect<'a>(&mut self, candidates: &'a [&[u8]]) -> io::Result<Option<&'a [u8]>> {
+        let max_len = candidates.iter().map(|c| c.len()).max().unwrap_or(0);
+        let buf = self.fill_buf_min(max_len)?;
+        Ok(candidates.iter().cloned().find(|c| buf.starts_with(c)))
+    }
+
+    /// Search the first `max_fill` bytes of the stream for `byte`, returning its offset from
+    /// the start of the buffered data.
+    ///
+    /// Reads from the underlying reader until `byte` is found, at least `max_fill` bytes are
+    /// buffered or it reaches EOF, growing the buffer if necessary; see `.fill_buf_min()`.
+    /// Nothing is consumed. Only newly read bytes are searched after each read.
+    pub fn find_byte_filled(&mut self, byte: u8, max_fill: usize) -> io::Result<Option<usize>> {
+        let mut searched = 0;
+
+        loop {
+            let buf_len = self.buf_len();
+            let window = &self.buffer()[searched .. cmp::min(buf_len, max_fill)];
+
+            if let Some(i) = memchr::memchr(byte, window) {
+                return Ok(Some(searched + i));
+            }
+
+            searched += window.len();
+
+            if searched >= max_fill { return Ok(None); }
+
+            match self.read_growing(max_fill) {
+                Ok(0) => return Ok(None),
+                Ok(_) => (),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // read into the buffer, making room first; if it's full, its capacity is doubled instead of
+    // growing it for every read, but only so it holds up to `limit` bytes
+    fn read_growing(&mut self, limit: usize) -> io::Result<usize> {
+        if self.buf.usable_space() == 0 {
+            self.buf.make_room();
+        }
+
+        if self.buf.usable_space() == 0 {
+            let grow = cmp::max(self.buf.capacity(), MIN_BUF_SIZE);
+            self.reserve_limited(cmp::min(grow, limit.saturating_sub(self.buf_len())));
+        }
+
+        self.read_into_buf()
+    }
+
+    /// Box the inner reader without losing data.
+    ///
+    /// This erases the type of the underlying reader while keeping the buffer and all settings
+    /// as they are, so nothing is read again or lost. See `.boxed_send()` for a reader which
+    /// can be sent to other threads.
+    pub fn boxed<'a>(self) -> BufReader<Box<dyn Read + 'a>, P> where R: Sized + 'a {
+        self.wrap_inner(|inner| Box::new(inner) as Box<dyn Read + 'a>)
+    }
+
+    /// Box the inner reader without losing data, like `.boxed()`, keeping it `Send`.
+    pub fn boxed_send<'a>(self) -> BufReader<Box<dyn Read + Send + 'a>, P>
+    where R: Sized + Send + 'a {
+        self.wrap_inner(|inner| Box::new(inner) as Box<dyn Read + Send + 'a>)
+    }
+
+
+    /// Wrap the inner reader so that `observer` is called with every slice of bytes read from it,
+    /// exactly once and before they are returned from this reader.
+    ///
+    /// This includes reads which bypass the buffer, but not bytes which are already buffered.
+    /// Useful for hashing or metrics without an extra pass over the data. See `Observed`
+    /// for details.
+    pub fn observe_reads<F: FnMut(&[u8])>(self, observer: F) -> BufReader<Observed<R, F>, P>
+    where R: Sized {
+        self.wrap_inner(|inner| Observed { inner, observer })
+    }
+}
+
+impl<R: Read + ?Sized, P: ReaderPolicy> BufReader<R, P> {
+    /// Discard up to `n` bytes, first from the buffer and then by reading into it from the
+    /// underlying reader, returning the number of bytes actually skipped.
+    ///
+    /// This is a forward seek for readers which don't implement `Seek`, like pipes or sockets.
+    /// It will be less than `n` only if the underlying reader reached EOF. The buffer is reused
+    /// for reading, so this never allocates. Retries on interrupts.
+    ///
+    /// For readers that also implement `Seek`, `.skip_bytes_seek()` seeks past the data which
+    /// isn't buffered instead of reading it.
+    pub fn skip_bytes(&mut self, n: u64) -> io::Result<u64> {
+        let mut skipped = 0;
+
+        while skipped < n {
+            let available = match self.fill_buf() {
+                Ok(buf) => buf.len(),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+
+            if available == 0 { break; }
+
+            let amt = cmp::min(available as u64, n - skipped) as usize;
+            self.consume(amt);
+            skipped += amt as u64;
+        }
+
+        Ok(skipped)
+    }
+
+    /// Skip `n` bytes for readers which don't implement `Seek`; the same as `.skip_bytes()`.
+    pub fn seek_forward(&mut self, n: u64) -> io::Result<u64> {
+        self.skip_bytes(n)
+    }
+
+    /// Discard bytes up to and including the first occurrence of `delim`, returning the number
+    /// of bytes discarded (including the delimiter) and whether the delimiter was found before
+    /// the underlying reader reached EOF.
+    ///
+    /// The buffer is searched directly and reused for reading, so this never copies or
+    /// allocates. Retries on interrupts.
+    pub fn skip_until(&mut self, delim: u8) -> io::Result<(u64, bool)> {
+        let mut skipped = 0;
+
+        loop {
+            let (found, amt) = match self.fill_buf() {
+                Ok(buf) => match memchr::memchr(delim, buf) {
+                    Some(i) => (true, i + 1),
+                    None => (false, buf.len()),
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+
+            self.consume(amt);
+            skipped += amt as u64;
+
+            if found || amt == 0 {
+                return Ok((skipped, found));
+            }
+        }
+    }
+
+    /// Append bytes to `out` for as long as `pred` returns `true` for them, returning the number
+    /// of bytes appended.
+    ///
+    /// Stops at the first byte for which `pred` returns `false`, which is left in the buffer, or
+    /// when the underlying reader reaches EOF. Retries on interrupts.
+    pub fn read_while<F>(&mut self, mut pred: F, out: &mut Vec<u8>) -> io::Result<usize>
+    where F: FnMut(u8) -> bool {
+        let mut read = 0;
+
+        loop {
+            let (done, amt) = match self.fill_buf() {
+                Ok(buf) => {
+                    let amt = buf.iter().position(|&b| !pred(b)).unwrap_or(buf.len());
+                    out.extend_from_slice(&buf[..amt]);
+                    (amt < buf.len() || buf.is_empty(), amt)
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+
+            self.consume(amt);
+            read += amt;
+
+            if done {
+                return Ok(read);
+            }
+        }
+    }
+
+    /// Discard bytes for as long as `pred` returns `true` for them, returning the number
+    /// of bytes discarded.
+    ///
+    /// Like `.read_while()` but without copying the bytes anywhere.
+    pub fn skip_while<F>(&mut self, mut pred: F) -> io::Result<u64> where F: FnMut(u8) -> bool {
+        let mut skipped = 0;
+
+        loop {
+            let (done, amt) = match self.fill_buf() {
+                Ok(buf) => {
+                    let amt = buf.iter().position(|&b| !pred(b)).unwrap_or(buf.len());
+                    (amt < buf.len() || buf.is_empty(), amt)
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+
+            self.consume(amt);
+            skipped += amt as u64;
+
+            if done {
+                return Ok(skipped);
+            }
+        }
+    }
+
+    /// Read a single byte, returning `None` if the underlying reader reached EOF.
+    ///
+    /// The byte is taken directly from the buffer, which is only refilled once empty.
+    /// Retries on interrupts.
+    #[inline]
+    pub fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        if let Some(&byte) = self.buf.buf().first() {
+            self.consume(1);
+            return Ok(Some(byte));
+        }
+
+        self.read_byte_slow()
+    }
+
+    #[inline(never)]
+    fn read_byte_slow(&mut self) -> io::Result<Option<u8>> {
+        let byte = loop {
+            match self.fill_buf() {
+                Ok(buf) => break buf.first().cloned(),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        };
+
+        if byte.is_some() { self.consume(1); }
+
+        Ok(byte)
+    }
+
+    /// Read exactly `N` bytes into an array, copying them directly out of the buffer.
+    ///
+    /// If fewer than `N` bytes are buffered, this reads into the buffer until there are enough,
+    /// growing it if `N` exceeds its capacity. Retries on interrupts.
+    ///
+    /// ### Errors
+    /// If the underlying reader reaches EOF first, an error of kind `UnexpectedEof` is returned
+    /// and the remaining data is consumed, like `Read::read_exact()`.
+    #[inline]
+    pub fn read_array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        if self.buf_len() < N {
+            self.fill_buf_min(N)?;
+        }
+
+        let buf_len = self.buf_len();
+
+        if buf_len < N {
+            self.consume(buf_len);
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "failed to fill whole buffer"));
+        }
+
+        let mut array = [0u8; N];
+        array.copy_from_slice(&self.buffer()[..N]);
+        self.consume(N);
+        Ok(array)
+    }
+
+    /// Decode an unsigned LEB128 varint, as used by Protobuf and WebAssembly.
+    ///
+    /// The value is decoded directly from the buffer, which is only refilled if the encoding
+    /// continues past its end. Retries on interrupts.
+    ///
+    /// ### Errors
+    /// * `InvalidData` if the encoding is longer than 10 bytes or the value overflows `u64`;
+    ///   nothing is consumed in this case.
+    /// * `UnexpectedEof` if the underlying reader reaches EOF before the end of the encoding;
+    ///   the remaining data is consumed, like `Read::read_exact()`.
+    pub fn read_varint_u64(&mut self) -> io::Result<u64> {
+        match self.decode_varint() {
+            Ok((val, len)) => {
+                self.consume(len);
+                Ok(val)
+            },
+            Err(e) => {
+                if e.kind() == io::ErrorKind::UnexpectedEof {
+                    let buf_len = self.buf_len();
+                    self.consume(buf_len);
+                }
+
+                Err(e)
+            }
+        }
+    }
+
+    /// Decode a signed, zig-zag encoded LEB128 varint, as used by Protobuf's `sint64`.
+    ///
+    /// See `.read_varint_u64()` for details.
+    pub fn read_varint_i64(&mut self) -> io::Result<i64> {
+        self.read_varint_u64().map(|n| (n >> 1) as i64 ^ -((n & 1) as i64))
+    }
+
+    /// Decode an unsigned LEB128 varint without consuming it, returning the value and the length
+    /// of its encoding in bytes.
+    ///
+    /// Reads into the buffer like `.read_varint_u64()`, but never consumes anything,
+    /// even on errors.
+    pub fn peek_varint(&mut self) -> io::Result<(u64, usize)> {
+        self.decode_varint()
+    }
+
+    fn decode_varint(&mut self) -> io::Result<(u64, usize)> {
+        loop {
+            let buf_len = self.buf_len();
+
+            if let Some(decoded) = decode_varint(self.buffer())? {
+                return Ok(decoded);
+            }
+
+            // the encoding continues past the end of the buffer
+            if self.fill_buf_min(buf_len + 1)?.len() == buf_len {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                          "reached EOF in the middle of a varint"));
+            }
+        }
+    }
+
+    /// Read a frame prefixed with its length as a 32-bit big-endian integer, returning `None` if
+    /// the underlying reader reached EOF at a frame boundary.
+    ///
+    /// See `.read_frame_with()` for details.
+    pub fn read_frame(&mut self, max_len: usize) -> io::Result<Option<Vec<u8>>> {
+        self.read_frame_with(FramePrefix::U32Be, max_len)
+    }
+
+    /// Read a frame prefixed with its length in the format given by `prefix`, returning `None`
+    /// if the underlying reader reached EOF at a frame boundary.
+    ///
+    /// Frames fully contained in the buffer are copied directly out of it; larger ones are
+    /// assembled like `Read::read_exact()` would. Retries on interrupts.
+    ///
+    /// ### Errors
+    /// * `InvalidData` if the frame is longer than `max_len`; this is checked before allocating
+    ///   anything and nothing is consumed in this case.
+    /// * `UnexpectedEof` if the underlying reader reaches EOF in the middle of a frame or its
+    ///   prefix; the remaining data is consumed.
+    pub fn read_frame_with(&mut self, prefix: FramePrefix, max_len: usize)
+    -> io::Result<Option<Vec<u8>>> {
+        let width = prefix.width();
+
+        let len = {
+            let buf = self.fill_buf_min(width)?;
+
+            if buf.is_empty() { return Ok(None); }
+
+            if buf.len() < width {
+                let buf_len = buf.len();
+                self.consume(buf_len);
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                          "reached EOF in the middle of a frame prefix"));
+            }
+
+            prefix.decode(buf)
+        };
+
+        if len > max_len as u64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      format!("frame length {} exceeds the maximum {}",
+                                              len, max_len)));
+        }
+
+        let len = len as usize;
+        self.consume(width);
+
+        let mut frame = vec![0; len];
+        self.read_exact(&mut frame)?;
+        Ok(Some(frame))
+    }
+
+    /// Read a NUL-terminated string of at most `max_len` bytes, not including the terminator,
+    /// which is consumed but not returned.
+    ///
+    /// The buffer is searched directly with `memchr`, refilling it as needed.
+    /// Retries on interrupts.
+    ///
+    /// ### Errors
+    /// The bytes read so far are consumed in either case.
+    ///
+    /// * `InvalidData` if there is no terminator within `max_len` bytes.
+    /// * `UnexpectedEof` if the underlying reader reaches EOF before the terminator.
+    pub fn read_cstr(&mut self, max_len: usize) -> io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+
+        loop {
+            let remaining = max_len - out.len();
+
+            let (found, amt) = match self.fill_buf() {
+                Ok(buf) => {
+                    if buf.is_empty() {
+                        return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                                  "reached EOF before the NUL terminator"));
+                    }
+
+                    // the terminator may directly follow `remaining` bytes
+                    let window = &buf[..cmp::min(buf.len(), remaining.saturating_add(1))];
+
+                    match memchr::memchr(0, window) {
+                        Some(i) => {
+                            out.extend_from_slice(&window[..i]);
+                            (true, i + 1)
+                        },
+                        None => {
+                            let amt = cmp::min(window.len(), remaining);
+                            out.extend_from_slice(&window[..amt]);
+                            (false, amt)
+                        },
+                    }
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+
+            self.consume(amt);
+
+            if found {
+                return Ok(out);
+            }
+
+            if out.len() == max_len {
+                return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                          format!("no NUL terminator within {} bytes", max_len)));
+            }
+        }
+    }
+
+    /// Read a NUL-terminated string like `.read_cstr()`, replacing invalid UTF-8 sequences
+    /// with `U+FFFD REPLACEMENT CHARACTER`.
+    pub fn read_cstr_lossy(&mut self, max_len: usize) -> io::Result<String> {
+        self.read_cstr(max_len).map(|bytes| String::from_utf8(bytes).unwrap_or_else(|e| {
+            String::from_utf8_lossy(e.as_bytes()).into_owned()
+        }))
+    }
+
+    /// Consume `prefix` if the next bytes in the stream match it exactly, returning whether
+    /// they did.
+    ///
+    /// Reads into the buffer until at least `prefix.len()` bytes are available or the underlying
+    /// reader reaches EOF, growing it if necessary. Nothing is consumed if the bytes don't match,
+    /// including if EOF was reached first.
+    pub fn consume_if(&mut self, prefix: &[u8]) -> io::Result<bool> {
+        let matches = self.starts_with(prefix)?;
+
+        if matches {
+            self.consume(prefix.len());
+        }
+
+        Ok(matches)
+    }
+
+    /// Consume `prefix` if the next bytes in the stream match it, or return an error of kind
+    /// `InvalidData` including the bytes found instead.
+    ///
+    /// See `.consume_if()` for details.
+    pub fn expect(&mut self, prefix: &[u8]) -> io::Result<()> {
+        if self.consume_if(prefix)? {
+            return Ok(());
+        }
+
+        let buf = self.buffer();
+        let found = &buf[..cmp::min(buf.len(), prefix.len())];
+
+        Err(io::Error::new(io::ErrorKind::InvalidData,
+                           format!("expected \"{}\", found \"{}\"",
+                                   prefix.escape_ascii(), found.escape_ascii())))
+    }
+
+    /// Append bytes to `out` up to and including `delim`, but no more than `max` bytes,
+    /// returning how the read ended along with the number of bytes appended.
+    ///
+    /// If the limit is reached first, the rest of the data is left unconsumed so the caller can
+    /// decide whether to skip it or abort. Otherwise this behaves like `BufRead::read_until()`.
+    pub fn read_until_limited(&mut self, delim: u8, max: usize, out: &mut Vec<u8>)
+    -> io::Result<UntilOutcome> {
+        self.fill_contiguous(max, 0, |buf| memchr::memchr(delim, buf).is_some())?;
+
+        let mut read = 0;
+
+        loop {
+            let (outcome, used) = match self.fill_buf() {
+                Ok(buf) => {
+                    let window = &buf[..cmp::min(buf.len(), max - read)];
+
+                    match memchr::memchr(delim, window) {
+                        Some(i) => {
+                            out.extend_from_slice(&window[..=i]);
+                            (Some(UntilOutcome::Found(read + i + 1)), i + 1)
+                        },
+                        None => {
+                            out.extend_from_slice(window);
+
+                            let outcome = if buf.is_empty() {
+                                Some(UntilOutcome::Eof(read))
+                            } else if read + window.len() == max {
+                                Some(UntilOutcome::LimitReached(max))
+                            } else {
+                                None
+                            };
+
+                            (outcome, window.len())
+                        },
+                    }
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+
+            self.consume(used);
+            read += used;
+
+            if let Some(outcome) = outcome {
+                return Ok(outcome);
+            }
+        }
+    }
+
+    /// Append a line to `out`, up to and including the newline, but no more than `max` bytes,
+    /// returning how the read ended along with the number of bytes appended.
+    ///
+    /// If the limit is reached first, the rest of the line is left unconsumed. Only the appended
+    /// bytes are validated as UTF-8; if the limit cuts a multi-byte character, this stops
+    /// before it, so fewer than `max` bytes may be appended.
+    ///
+    /// ### Errors
+    /// If the bytes of the line are not valid UTF-8, an error of kind `InvalidData` is returned
+    /// and `out` is left unchanged.
+    pub fn read_line_limited(&mut self, max: usize, out: &mut String)
+    -> io::Result<LineOutcome> {
+        let start = out.len();
+        let mut read = 0;
+        // the length of an incomplete character at the head of the buffer
+        let mut pending = 0;
+
+        loop {
+            let (outcome, used) = {
+                let res = if pending > 0 { self.fill_buf_min(pending + 1) } else { self.fill_buf() };
+
+                let buf = match res {
+                    Ok(buf) => buf,
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                };
+
+                let window = &buf[..cmp::min(buf.len(), max - read)];
+
+                let (chunk, found) = match memchr::memchr(b'\n', window) {
+                    Some(i) => (&window[..=i], true),
+                    None => (window, false),
+                };
+
+                // leave an incomplete character in the buffer unless we're at EOF
+                let valid = match str::from_utf8(chunk) {
+                    Ok(valid) => valid.len(),
+                    Err(ref e) if e.error_len().is_none() && buf.len() > pending => e.valid_up_to(),
+                    Err(_) => {
+                        out.truncate(start);
+                        return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                                  "stream did not contain valid UTF-8"));
+                    },
+                };
+
+                out.push_str(unsafe { str::from_utf8_unchecked(&chunk[..valid]) });
+                pending = chunk.len() - valid;
+
+                let outcome = if found {
+                    Some(LineOutcome::Found(read + valid))
+                } else if buf.is_empty() {
+                    Some(LineOutcome::Eof(read))
+                } else if read + chunk.len() == max {
+                    Some(LineOutcome::LimitReached(read + valid))
+                } else {
+                    None
+                };
+
+                (outcome, valid)
+            };
+
+            self.consume(used);
+            read += used;
+
+            if let Some(outcome) = outcome {
+                return Ok(outcome);
+            }
+        }
+    }
+
+    /// Append bytes to `out` up to and including the first occurrence of the byte string
+    /// `delim`, returning the number of bytes appended and whether the delimiter was found
+    /// before the underlying reader reached EOF.
+    ///
+    /// The buffer is searched directly with `memchr::memmem`. Bytes which may be the start of
+    /// a delimiter split across reads are kept in the buffer until more data is available,
+    /// so the buffer is grown if `delim` is longer than its capacity. Retries on interrupts.
+    ///
+    /// ### Errors
+    /// Returns an error of kind `InvalidInput` if `delim` is empty.
+    pub fn read_until_slice(&mut self, delim: &[u8], out: &mut Vec<u8>)
+    -> io::Result<(usize, bool)> {
+        if delim.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "read_until_slice() called with an empty delimiter"));
+        }
+
+        match self.read_until_finder(&memchr::memmem::Finder::new(delim), usize::MAX, out)? {
+            UntilOutcome::Found(read) => Ok((read, true)),
+            UntilOutcome::Eof(read) | UntilOutcome::LimitReached(read) => Ok((read, false)),
+        }
+    }
+
+    // with `.set_grow_on_demand()`, read into the buffer until `found()` is true for the buffered
+    // data, it holds `limit` bytes or EOF; `overlap` bytes before the new data are searched again
+    fn fill_contiguous<F>(&mut self, limit: usize, overlap: usize, mut found: F)
+    -> io::Result<()> where F: FnMut(&[u8]) -> bool {
+        if self.grow_on_demand == 0 { return Ok(()); }
+
+        let limit = cmp::min(cmp::min(limit, self.grow_on_demand),
+                             self.max_cap.unwrap_or(usize::MAX));
+        let mut searched: usize = 0;
+
+        while !found(&self.buffer()[searched.saturating_sub(overlap)..]) {
+            let buf_len = self.buf_len();
+            if buf_len >= limit { break; }
+            searched = buf_len;
+
+            match self.read_growing(limit) {
+                Ok(0) => break,
+                Ok(_) => (),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+
+    // append bytes up to and including the needle of `finder`, but no more than `max` bytes
+    // preceding it
+    fn read_until_finder(&mut self, finder: &memchr::memmem::Finder, max: usize,
+                         out: &mut Vec<u8>) -> io::Result<UntilOutcome> {
+        let delim_len = finder.needle().len();
+        // the longest prefix of the delimiter that may be at the end of the buffer
+        let keep = delim_len - 1;
+        // not including the delimiter
+        let mut read = 0;
+
+        self.fill_contiguous(max.saturating_add(delim_len), keep,
+                             |buf| finder.find(buf).is_some())?;
+
+        loop {
+            let (found, eof, len) = {
+                let buf = self.fill_buf_min(keep + 1)?;
+
+                match finder.find(buf) {
+                    Some(i) => (true, false, i),
+                    None if buf.len() <= keep => (false, true, buf.len()),
+                    None => (false, false, buf.len() - keep),
+                }
+            };
+
+            if len > max - read {
+                let amt = max - read;
+                out.extend_from_slice(&self.buffer()[..amt]);
+                self.consume(amt);
+                return Ok(UntilOutcome::LimitReached(max));
+            }
+
+            let used = if found { len + delim_len } else { len };
+            out.extend_from_slice(&self.buffer()[..used]);
+            self.consume(used);
+            read += len;
+
+            if found {
+                return Ok(UntilOutcome::Found(read + delim_len));
+            }
+
+            if eof {
+                return Ok(UntilOutcome::Eof(read));
+            }
+        }
+    }
+
+    /// Get an iterator over the segments of this reader separated by the byte string `delim`,
+    /// like `BufRead::split()` but for multi-byte delimiters.
+    ///
+    /// Segments are yielded without the delimiter. Like `slice::split()`, the segment after
+    /// the last delimiter is always yielded, even if it's empty. Delimiters are searched for
+    /// like `.read_until_slice()`.
+    ///
+    /// ### Panics
+    /// If `delim` is empty.
+    pub fn split_slice(self, delim: Vec<u8>) -> SplitSlice<R, P> where R: Sized {
+        assert!(!delim.is_empty(), "split_slice() called with an empty delimiter");
+
+        SplitSlice {
+            reader: self,
+            finder: memchr::memmem::Finder::new(&delim).into_owned(),
+            max_segment_len: usize::MAX,
+            done: false,
+        }
+    }
+
+    /// Call `f` with each line of the stream, without the trailing newline, until the underlying
+    /// reader reaches EOF or `f` returns an error, which is then returned.
+    ///
+    /// Lines are borrowed directly from the buffer where possible; only lines which cross the
+    /// end of the buffer are copied to an internal `Vec` first. A carriage return before
+    /// the newline is not removed; see `.for_each_line_crlf()` for that.
+    /// The last line is passed to `f` even if it isn't terminated.
+    ///
+    /// A line is consumed even if `f` returns an error for it. Retries on interrupts.
+    pub fn for_each_line<F>(&mut self, f: F) -> io::Result<()>
+    where F: FnMut(&[u8]) -> io::Result<()> {
+        self.for_each_line_with(false, false, f)
+    }
+
+    /// Like `.for_each_line()`, but a single carriage return before the newline is also removed.
+    ///
+    /// If `legacy_mac` is `true`, a lone carriage return terminates a line as well, so `\n`,
+    /// `\r\n` and `\r` may be mixed. A line ending in a carriage return at the end of the buffer
+    /// is passed to `f` only after the next read shows whether a newline follows, so the whole
+    /// terminator is consumed with it even if `f` returns an error.
+    pub fn for_each_line_crlf<F>(&mut self, legacy_mac: bool, f: F) -> io::Result<()>
+    where F: FnMut(&[u8]) -> io::Result<()> {
+        self.for_each_line_with(true, legacy_mac, f)
+    }
+
+    fn for_each_line_with<F>(&mut self, strip_cr: bool, legacy_mac: bool, mut f: F)
+    -> io::Result<()> where F: FnMut(&[u8]) -> io::Result<()> {
+        fn strip(line: &[u8], strip_cr: bool) -> &[u8] {
+            match line.split_last() {
+                Some((&b'\r', rest)) if strip_cr => rest,
+                _ => line,
+            }
+        }
+
+        let mut spill = Vec::new();
+        // `spill` holds a line ended by a `\r` at the end of the buffer; a `\n` at the head of
+        // the buffer belongs to it
+        let mut cr_pending = false;
+
+        loop {
+            let (res, used) = {
+                let buf = match self.fill_buf() {
+                    Ok(buf) => buf,
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                };
+
+                if buf.is_empty() {
+                    return if spill.is_empty() && !cr_pending { Ok(()) } else { f(&spill) };
+                }
+
+                if mem::replace(&mut cr_pending, false) {
+                    let res = f(&spill);
+                    spill.clear();
+                    (res, if buf[0] == b'\n' { 1 } else { 0 })
+                } else {
+                    let term = if legacy_mac {
+                        memchr::memchr2(b'\n', b'\r', buf)
+                    } else {
+                        memchr::memchr(b'\n', buf)
+                    };
+
+                    match term {
+                        Some(i) if buf[i] == b'\r' && i + 1 == buf.len() => {
+                            spill.extend_from_slice(&buf[..i]);
+                            cr_pending = true;
+                            (Ok(()), buf.len())
+                        },
+                        Some(i) => {
+                            let mut used = i + 1;
+
+                            if buf[i] == b'\r' && buf.get(i + 1) == Some(&b'\n') {
+                                used += 1;
+                            }
+
+                            let res = if spill.is_empty() {
+                                f(strip(&buf[..i], strip_cr))
+                            } else {
+                                spill.extend_from_slice(&buf[..i]);
+                                let res = f(strip(&spill, strip_cr));
+                                spill.clear();
+                                res
+                            };
+
+                            (res, used)
+                        },
+                        None => {
+                            spill.extend_from_slice(buf);
+                            (Ok(()), buf.len())
+                        },
+                    }
+                }
+            };
+
+            self.consume(used);
+            res?;
+        }
+    }
+
+    /// Clear `buf` and read the next line into it, returning it or `None` if the underlying
+    /// reader reached EOF.
+    ///
+    /// Like `BufRead::lines()`, the trailing `\n` or `\r\n` is removed, from `buf` as well.
+    /// Reusing the same `buf` for each call means only one allocation is needed for the whole
+    /// stream:
+    ///
+    /// ```rust
+    /// # use buf_redux::BufReader;
+    /// let mut reader = BufReader::new(&b"foo\r\nbar\n"[..]);
+    /// let mut buf = String::new();
+    ///
+    /// while let Some(line) = reader.read_line_reuse(&mut buf).unwrap() {
+    ///     assert_eq!(line.len(), 3);
+    /// }
+    /// ```
+    ///
+    /// ### Errors
+    /// Like `BufRead::read_line()`; `buf` is left empty if the line is not valid UTF-8.
+    pub fn read_line_reuse<'a>(&mut self, buf: &'a mut String) -> io::Result<Option<&'a str>> {
+        buf.clear();
+
+        if self.read_line(buf)? == 0 {
+            return Ok(None);
+        }
+
+        if buf.ends_with('\n') {
+            buf.pop();
+
+            if buf.ends_with('\r') {
+                buf.pop();
+            }
+        }
+
+        Ok(Some(buf))
+    }
+
+    /// Call `f` with each consecutive `n`-byte chunk of the stream, borrowed directly from
+    /// the buffer, until the underlying reader reaches EOF or `f` returns an error, which is
+    /// then returned.
+    ///
+    /// Each chunk is contiguous; data is moved down or the buffer grown as necessary,
+    /// see `.fill_buf_min()`. The second argument to `f` is `true` only for a final chunk that is
+    /// shorter than `n` because EOF was reached.
+    ///
+    /// A chunk is consumed even if `f` returns an error for it. Retries on interrupts.
+    ///
+    /// ### Errors
+    /// Returns an error of kind `InvalidInput` if `n` is zero.
+    pub fn for_each_chunk<F>(&mut self, n: usize, mut f: F) -> io::Result<()>
+    where F: FnMut(&[u8], bool) -> io::Result<()> {
+        if n == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "for_each_chunk() called with a chunk size of zero"));
+        }
+
+        loop {
+            let (res, used) = {
+                let buf = self.fill_buf_min(n)?;
+
+                if buf.is_empty() { return Ok(()); }
+
+                let used = cmp::min(buf.len(), n);
+                (f(&buf[..used], used < n), used)
+            };
+
+            self.consume(used);
+            res?;
+        }
+    }
+
+    /// Get an iterator over the lines of this reader, like `BufRead::lines()` but replacing
+    /// invalid UTF-8 sequences with `U+FFFD REPLACEMENT CHARACTER` instead of returning an error.
+    ///
+    /// Lines are read with `BufRead::read_until()`, so invalid bytes don't affect where
+    /// subsequent lines begin.
+    pub fn lines_lossy(self) -> LinesLossy<R, P> where R: Sized {
+        LinesLossy { reader: self }
+    }
+
+    /// Consume a byte order mark at the head of the stream, returning which one it was, if any.
+    ///
+    /// Reads into the buffer until at least 4 bytes are available or the underlying reader
+    /// reaches EOF. Nothing is consumed if no BOM is recognized.
+    pub fn strip_bom(&mut self) -> io::Result<Option<Bom>> {
+        let bom = Bom::detect(self.fill_buf_min(4)?);
+
+        if let Some(bom) = bom {
+            self.consume(bom.as_bytes().len());
+        }
+
+        Ok(bom)
+    }
+
+    /// Write bytes to `dest` until the first occurrence of `delim`, returning the number of bytes
+    /// written and whether the delimiter was found before the underlying reader reached EOF.
+    ///
+    /// The delimiter is consumed but not written. Bytes are written directly from the buffer
+    /// with `Write::write_all()` and consumed once written. Retries on interrupts.
+    ///
+    /// ### Errors
+    /// Returns the first error from reading or writing; bytes may have been copied either way.
+    pub fn copy_until<W: Write + ?Sized>(&mut self, delim: u8, dest: &mut W)
+    -> io::Result<(u64, bool)> {
+        let mut written = 0;
+
+        loop {
+            let (found, len) = {
+                let buf = match self.fill_buf() {
+                    Ok(buf) => buf,
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                };
+
+                if buf.is_empty() {
+                    return Ok((written, false));
+                }
+
+                let (found, len) = match memchr::memchr(delim, buf) {
+                    Some(i) => (true, i),
+                    None => (false, buf.len()),
+                };
+
+                dest.write_all(&buf[..len])?;
+                (found, len)
+            };
+
+            self.consume(if found { len + 1 } else { len });
+            written += len as u64;
+
+            if found {
+                return Ok((written, true));
+            }
+        }
+    }
+
+    /// Write exactly `n` bytes to `dest`, first from the buffer and then by reading into it from
+    /// the underlying reader.
+    ///
+    /// The buffer is used as the staging area, so this never allocates. Bytes are written
+    /// with `Write::write_all()` and consumed once written. Retries on interrupts.
+    ///
+    /// ### Errors
+    /// If the underlying reader reaches EOF first, an error of kind `UnexpectedEof` is returned
+    /// wrapping a `ShortCopyError`, which reports how many bytes were copied.
+    /// Otherwise returns the first error from reading or writing.
+    pub fn copy_exact<W: Write + ?Sized>(&mut self, n: u64, dest: &mut W) -> io::Result<()> {
+        let mut copied = 0;
+
+        while copied < n {
+            let len = {
+                let buf = match self.fill_buf() {
+                    Ok(buf) => buf,
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                };
+
+                if buf.is_empty() {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                              ShortCopyError { copied, expected: n }));
+                }
+
+                let len = cmp::min(buf.len() as u64, n - copied) as usize;
+                dest.write_all(&buf[..len])?;
+                len
+            };
+
+            self.consume(len);
+            copied += len as u64;
+        }
+
+        Ok(())
+    }
+
+    /// Read exactly `n` bytes into a new `Vec`, reading from the underlying reader if fewer are
+    /// buffered.
+    ///
+    /// ### Errors
+    /// Like `Read::read_exact()`; returns an error of kind `UnexpectedEof` if the underlying reader
+    /// reaches EOF first.
+    pub fn split_to_exact(&mut self, n: usize) -> io::Result<Vec<u8>> {
+        let mut out = vec![0; n];
+        self.read_exact(&mut out)?;
+        Ok(out)
+    }
+
+    /// Write all currently buffered bytes to `dest`, returning the number of bytes written.
+    ///
+    /// Does not read from the underlying reader, so this leaves the buffer empty, ready for
+    /// the underlying reader to be used directly with `.get_mut()`. Retries on interrupts.
+    ///
+    /// ### Errors
+    /// Returns the first error from writing, or an error of kind `WriteZero` if `dest` stops
+    /// accepting bytes. Only the bytes actually written are consumed, so nothing is lost
+    /// or duplicated.
+    pub fn drain_buf_to<W: Write + ?Sized>(&mut self, dest: &mut W) -> io::Result<usize> {
+        let mut drained = 0;
+
+        while !self.buf.is_empty() {
+            match dest.write(self.buf.buf()) {
+                Ok(0) => return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                                   "failed to write whole buffer")),
+                Ok(written) => {
+                    self.consume(written);
+                    drained += written;
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(drained)
+    }
+
+    /// Get an adapter which reads at most `limit` bytes from this reader, like `Read::take()`,
+    /// but still implements `BufRead` using this reader's buffer.
+    ///
+    /// Once the adapter is dropped, this reader continues exactly where it stopped; any bytes
+    /// buffered beyond the limit remain available.
+    pub fn take_buffered<'a>(&'a mut self, limit: u64) -> TakeBuffered<'a, R, P> {
+        TakeBuffered { reader: self, limit }
+    }
+
+    /// Get an iterator over the bytes of this reader, using `.read_byte()`.
+    ///
+    /// This is considerably faster than `Read::bytes()`, which performs a full `read()` call
+    /// per byte. Unlike `Bytes`, this only borrows the reader.
+    pub fn bytes_buffered<'a>(&'a mut self) -> ByteIter<'a, R, P> {
+        ByteIter { reader: self }
+    }
+}
+
+impl<R: Read + ?Sized, P: ReaderPolicy> Read for BufReader<R, P> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        // If we don't have any buffered data and we're doing a read matching
+        // or exceeding the bypass threshold, bypass the buffer.
+        if self.buf.is_empty() && self.may_bypass(out.len()) {
+            let read = self.inner.read(out)?;
+            self.count_bypassed(read);
+            if !out.is_empty() { self.check_eof(read); }
+            return Ok(read);
+        }
+
+        let nread = self.fill_buf()?.read(out)?;
+        self.consume(nread);
+        Ok(nread)
+    }
+
+    /// Scatter buffered data across `bufs`; if the buffer is empty and `bufs` total at least the
+    /// bypass threshold, the read is forwarded to `inner.read_vectored()` instead, like `read()`.
+    ///
+    /// Whether this is more efficient than individual reads thus mostly depends on the inner
+    /// reader's implementation of `read_vectored()`.
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut]) -> io::Result<usize> {
+        let total_len = bufs.iter().map(|buf| buf.len()).sum::<usize>();
+
+        if self.buf.is_empty() && self.may_bypass(total_len) {
+            let read = self.inner.read_vectored(bufs)?;
+            self.count_bypassed(read);
+            if total_len > 0 { self.check_eof(read); }
+            return Ok(read);
+        }
+
+        let nread = self.fill_buf()?.read_vectored(bufs)?;
+        self.consume(nread);
+        Ok(nread)
+    }
+
+    fn read_exact(&mut self, mut out: &mut [u8]) -> io::Result<()> {
+        // fast path: serve the whole read from the buffer with a single copy
+        if self.buf_len() >= out.len() {
+            let len = out.len();
+            out.copy_from_slice(&self.buffer()[..len]);
+            self.consume(len);
+            return Ok(());
+        }
+
+        // drain the buffer and read the rest directly if it wouldn't fit anyway
+        if self.may_bypass(out.len()) {
+            let buf_len = self.buf_len();
+            let (buffered, rest) = { out }.split_at_mut(buf_len);
+            buffered.copy_from_slice(self.buffer());
+            self.consume(buf_len);
+
+            // read the rest directly, keeping count even if it fails partway
+            let mut rest = rest;
+
+            while !rest.is_empty() {
+                match self.inner.read(rest) {
+                    // like `Read::read_exact()`, don't read past the EOF
+                    Ok(0) => {
+                        self.check_eof(0);
+                        return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                                  "failed to fill whole buffer"));
+                    },
+                    Ok(n) => {
+                        self.count_bypassed(n);
+                        self.check_eof(n);
+                        let tmp = rest;
+                        rest = &mut tmp[n..];
+                    },
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                    Err(e) => return Err(e),
+                }
+            }
+
+            out = rest;
+        }
+
+        while !out.is_empty() {
+            match self.read(out) {
+                Ok(0) => break,
+                Ok(n) => { let tmp = out; out = &mut tmp[n..]; },
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+
+        if out.is_empty() {
+            Ok(())
+        } else {
+            Err(io::Error::new(io::ErrorKind::UnexpectedEof, "failed to fill whole buffer"))
+        }
+    }
+
+    fn read_to_end(&mut self, out: &mut Vec<u8>) -> io::Result<usize> {
+        let buf_len = self.buf_len();
+        out.extend_from_slice(self.buffer());
+        self.consume(buf_len);
+
+        // the remaining bytes can bypass the buffer unless they need to be kept for `reset()`,
+        // some were spilled or bypassing is disabled
+        if self.may_bypass(usize::MAX) {
+            let start = out.len();
+            let res = self.inner.read_to_end(out);
+            // some bytes may have been read even if it failed
+            self.count_bypassed(out.len() - start);
+            if res.is_ok() { self.check_eof(0); }
+            return res.map(|read| buf_len + read);
+        }
+
+        let mut total = buf_len;
+
+        loop {
+            let read = match self.fill_buf() {
+                Ok(buf) => { out.extend_from_slice(buf); buf.len() },
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+
+            if read == 0 { return Ok(total); }
+
+            self.consume(read);
+            total += read;
+        }
+    }
+
+    fn read_to_string(&mut self, out: &mut String) -> io::Result<usize> {
+        // truncates the string back to its valid length, even on panic
+        struct Guard<'a> {
+            buf: &'a mut Vec<u8>,
+            len: usize,
+        }
+
+        impl<'a> Drop for Guard<'a> {
+            fn drop(&mut self) {
+                self.buf.truncate(self.len);
+            }
+        }
+
+        let start = out.len();
+        let mut guard = Guard { len: start, buf: unsafe { out.as_mut_vec() } };
+        let ret = self.read_to_end(guard.buf);
+
+        // validate everything appended at once, as a character may straddle
+        // the buffered bytes and the ones read directly
+        if str::from_utf8(&guard.buf[start..]).is_err() {
+            ret.and_then(|_| Err(io::Error::new(io::ErrorKind::InvalidData,
+                                                "stream did not contain valid UTF-8")))
+        } else {
+            guard.len = guard.buf.len();
+            ret
+        }
+    }
+}
+
+impl<R: Read + ?Sized, P: ReaderPolicy> BufRead for BufReader<R, P> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        // If we've reached the end of our internal buffer then we need to fetch
+        // some more data from the underlying reader.
+        // This execution order is important; the policy may want to resize the buffer or move data
+        // before reading into it.
+        self.regrow();
+
+        let mut refilled = false;
+
+        // with `fill_full`, keep going once a refill has started
+        while (refilled && self.fill_full && self.buf.usable_space() > 0) || self.should_read() {
+            // the policy wants more data but the tail is full; reclaim the consumed bytes
+            if self.buf.usable_space() == 0 {
+                self.buf.make_room();
+            }
+
+            // a zero-capacity buffer can't return any data, which would look like EOF
+            if self.buf.capacity() == 0 {
+                self.reserve_limited(MIN_BUF_SIZE);
+
+                if self.buf.capacity() == 0 {
+                    return Err(max_capacity_exceeded());
+                }
+            }
+
+            if self.buf.usable_space() == 0 { break; }
+
+            match self.read_into_buf() {
+                Ok(0) => break,
+                Ok(_) => refilled = true,
+                // return what we have, it's no use waiting
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock && refilled => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(self.buffer())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.consume_buffered(amt);
+    }
+
+    /// Same semantics as the default implementation, but searches the buffer with `memchr`
+    /// and copies and consumes whole runs of bytes at once.
+    fn read_until(&mut self, delim: u8, out: &mut Vec<u8>) -> io::Result<usize> {
+        self.fill_contiguous(usize::MAX, 0, |buf| memchr::memchr(delim, buf).is_some())?;
+
+        let mut read = 0;
+
+        loop {
+            let (done, used) = match self.fill_buf() {
+                Ok(buf) => match memchr::memchr(delim, buf) {
+                    Some(i) => {
+                        out.extend_from_slice(&buf[..=i]);
+                        (true, i + 1)
+                    },
+                    None => {
+                        out.extend_from_slice(buf);
+                        (buf.is_empty(), buf.len())
+                    },
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+
+            self.consume(used);
+            read += used;
+
+            if done {
+                return Ok(read);
+            }
+        }
+    }
+}
+
+impl<R: fmt::Debug + ?Sized, P: fmt::Debug> fmt::Debug for BufReader<R, P> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("buf_redux::BufReader")
+            .field("reader", &&self.inner)
+            .field("buf_len", &self.buf_len())
+            .field("capacity", &self.capacity())
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
+
+/// Clones the underlying reader and the pending data, which includes any data in the spill file;
+/// the clone has its own buffer and no spill file.
+///
+/// The mark and the consumed bytes which could be restored with `.unconsume()` aren't copied.
+///
+/// For readers which share state between clones, e.g. a cursor, the two `BufReader`s will
+/// interfere when reading from their underlying readers; see `.try_clone()`.
+///
+/// ### Panics
+/// If reading the spill file set by `.set_spill()` fails.
+impl<R: Clone, P: Clone> Clone for BufReader<R, P> {
+    fn clone(&self) -> Self {
+        let buf = self.copy_pending().expect("failed to read the BufReader spill file");
+        let high_water = cmp::max(self.high_water, buf.len());
+
+        BufReader {
+            buf,
+            inner: self.inner.clone(),
+            policy: self.policy.clone(),
+            mark: None,
+            regrow_cap: self.regrow_cap,
+            auto_shrink: self.auto_shrink.clone(),
+            max_cap: self.max_cap,
+            move_threshold: self.move_threshold,
+            spill: None,
+            high_water,
+            read_count: self.read_count,
+            inner_offset: self.inner_offset,
+            buffer_aware_seek: self.buffer_aware_seek,
+            fill_full: self.fill_full,
+            bypass: self.bypass,
+            grow_on_demand: self.grow_on_demand,
+            fused: self.fused,
+            fused_eof: self.fused_eof,
+            eof: self.eof,
+            trusted_read: self.trusted_read,
+        }
+    }
+}
+
+impl<R: TryCloneable, P> BufReader<R, P> {
+    /// Create a new `BufReader` with a clone of the underlying reader and a copy of the data
+    /// in the buffer, as well as the same settings; the policy is reset to `StdPolicy`.
+    ///
+    /// The clone starts out with the same unconsumed data as this reader, including any data in
+    /// the spill file, which is moved into the buffer of the clone. The mark and the consumed
+    /// bytes which could be restored with `.unconsume()` aren't copied.
+    ///
+    /// ### Note
+    /// For all the implementations of `TryCloneable` in this crate, the cloned handle refers to
+    /// the same underlying file or socket as the original, including its cursor: reading from
+    /// either reader advances the other's underlying reader as well. The two only observe the
+    /// same stream until one of them reads from the underlying reader again.
+    pub fn try_clone(&self) -> io::Result<BufReader<R>> {
+        let inner = self.inner.try_clone()?;
+        let buf = self.copy_pending()?;
+        let high_water = buf.len();
+
+        Ok(BufReader {
+            buf, inner,
+            policy: StdPolicy,
+            mark: None,
+            regrow_cap: self.regrow_cap,
+            auto_shrink: self.auto_shrink.clone(),
+            max_cap: self.max_cap,
+            move_threshold: self.move_threshold,
+            spill: None,
+            high_water,
+            read_count: self.read_count,
+            // the cursor is shared, so the position isn't known
+            inner_offset: None,
+            buffer_aware_seek: self.buffer_aware_seek,
+            fill_full: self.fill_full,
+            bypass: self.bypass,
+            grow_on_demand: self.grow_on_demand,
+            fused: self.fused,
+            fused_eof: self.fused_eof,
+            eof: self.eof,
+            trusted_read: self.trusted_read,
+        })
+    }
+}
+
+impl<R: Read + Seek + ?Sized, P: ReaderPolicy> BufReader<R, P> {
+    /// Seek relative to the current position, keeping the buffer if possible.
+    ///
+    /// If the target lies within the buffered data, or within consumed bytes still present in the
+    /// buffer (see `.unconsume()`), this just moves the position within the buffer; otherwise
+    /// it's equivalent to `.seek(SeekFrom::Current(offset))`, which discards the buffer.
+    pub fn seek_relative(&mut self, offset: i64) -> io::Result<()> {
+        if self.move_buffered(offset) { return Ok(()); }
+        self.seek(SeekFrom::Current(offset)).map(|_| ())
+    }
+
+    /// Discard `n` bytes like `.skip_bytes()`, but seek the underlying reader past any of them
+    /// that aren't buffered instead of reading them, returning the number of bytes skipped.
+    ///
+    /// If all `n` bytes are buffered (or spilled), they're just consumed. Otherwise the buffer
+    /// is discarded and the underlying reader is seeked once, with `SeekFrom::Current`. Seeking
+    /// past the end of the underlying reader generally succeeds, so the count is `n` even then;
+    /// the next read returns `0`.
+    ///
+    /// While a mark is set, this reads like `.skip_bytes()` so `.reset()` can still return to it.
+    ///
+    /// ### Errors
+    /// Returns an error of kind `InvalidInput` if `n` doesn't fit in an `i64` and must be seeked,
+    /// as well as any error from seeking the underlying reader.
+    pub fn skip_bytes_seek(&mut self, n: u64) -> io::Result<u64> {
+        if self.mark.is_some() || n <= self.total_pending() {
+            return self.skip_bytes(n);
+        }
+
+        if n > i64::MAX as u64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "skip too large to seek the underlying reader"));
+        }
+
+        // this seeks `inner` by `n - total_pending()`, as the target is past the buffer
+        self.seek(SeekFrom::Current(n as i64))?;
+        Ok(n)
+    }
+}
+
+impl<R: Seek + ?Sized, P: ReaderPolicy> BufReader<R, P> {
+    /// Discard all buffered data like `.resync()`, then query the position of the underlying
+    /// reader and return it.
+    ///
+    /// The count returned by `.total_read()` is reset to this position, so `.total_read()`,
+    /// `.consumer_offset()` and `.buffered_offset()` all reflect the true position afterwards.
+    pub fn resync_position(&mut self) -> io::Result<u64> {
+        self.resync();
+
+        let pos = self.inner.stream_position()?;
+        self.read_count = pos;
+        self.inner_offset = Some(0);
+        Ok(pos)
+    }
+
+    /// Seek back to the start of the stream, keeping the buffer if it still holds the data there.
+    ///
+    /// If the start of the stream is (consumed) data in the buffer, this just moves the position
+    /// in the buffer like `.unconsume()`, without seeking the underlying reader or re-reading the
+    /// data; otherwise it's equivalent to `.seek(SeekFrom::Start(0))`.
+    ///
+    /// The data read into the buffer first stays there until the buffer is moved down by
+    /// `.make_room()`, grown or read into after being emptied, so a header of up to `.capacity()`
+    /// bytes can be inspected and then read again by a parser for free.
+    ///
+    /// The position of the underlying reader is queried if it isn't known from a previous seek.
+    pub fn rewind_buffered(&mut self) -> io::Result<()> {
+        let pos = match self.known_position() {
+            Some(pos) => pos,
+            None => self.stream_position()?,
+        };
+
+        if pos <= i64::MAX as u64 && self.move_buffered(-(pos as i64)) {
+            return Ok(());
+        }
+
+        self.seek(SeekFrom::Start(0)).map(|_| ())
+    }
+
+    // seek `inner` as if it were unbuffered and discard the buffer
+    fn seek_discarding(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let result: u64;
+        if let SeekFrom::Current(n) = pos {
+            let remainder = self.total_pending() as i64;
+            // it should be safe to assume that remainder fits within an i64 as the alternative
+            // means we managed to allocate 8 ebibytes and that's absurd.
+            // But it's not out of the realm of possibility for some weird underlying reader to
+            // support seeking by i64::min_value() so we need to handle underflow when subtracting
+            // remainder.
+            if let Some(offset) = n.checked_sub(remainder) {
+                result = self.inner.seek(SeekFrom::Current(offset))?;
+            } else {
+                // seek backwards by our remainder, and then by the offset
+                self.inner.seek(SeekFrom::Current(-remainder))?;
+                self.buf.clear(); // empty the buffer
+                result = self.inner.seek(SeekFrom::Current(n))?;
+            }
+        } else {
+            // Seeking with Start/End doesn't care about our buffer length.
+            result = self.inner.seek(pos)?;
+        }
+        self.buf.clear();
+        self.mark = None;
+
+        if let Some(ref mut spill) = self.spill {
+            spill.clear();
+        }
+
+        Ok(result)
+    }
+}
+
+impl<R: Seek + ?Sized, P: ReaderPolicy> Seek for BufReader<R, P> {
+    /// Seek to an ofPet, in bytes, in the underlying reader.
+    ///
+    /// The position used for seeking with `SeekFrom::Current(_)` is the
+    /// position the underlying reader would be at if the `BufReader` had no
+    /// internal buffer.
+    ///
+    /// By default, seeking always discards the internal buffer, even if the
+    /// seek position would otherwise fall within it. This guarantees that
+    /// calling `.unwrap()` immediately after a seek yields the underlying
+    /// reader at the same position. See `.set_buffer_aware_seek()` for
+    /// serving seeks from the buffer instead.
+    ///
+    /// See `std::io::Seek` for more details.
+    ///
+    /// Note: In the edge case where you're seeking with `SeekFrom::Current(n)`
+    /// where `n` minus the internal buffer length underflows an `i64`, two
+    /// seeks will be performed instead of one. If the second seek returns
+    /// `Err`, the underlying reader will be left at the same position it would
+    /// have if you seeked to `SeekFrom::Current(0)`.
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        // more data may be readable at the new position
+        self.fused_eof = false;
+        self.eof = false;
+
+        if let (true, Some(cur)) = (self.buffer_aware_seek, self.known_position()) {
+            let offset = match pos {
+                // the difference is correct as long as it fits in an `i64`, which it must
+                // to be within the buffer
+                SeekFrom::Start(n) => Some(n.wrapping_sub(cur) as i64),
+                SeekFrom::Current(n) => Some(n),
+                SeekFrom::End(_) => None,
+            };
+
+            if let Some(offset) = offset {
+                if self.move_buffered(offset) {
+                    return Ok(cur.wrapping_add(offset as u64));
+                }
+            }
+        }
+
+        match self.seek_discarding(pos) {
+            Ok(result) => {
+                self.inner_offset = Some(result.wrapping_sub(self.read_count));
+                Ok(result)
+            },
+            Err(e) => {
+                // we don't know where `inner` ended up
+                self.inner_offset = None;
+                Err(e)
+            },
+        }
+    }
+
+    /// Get the current position in the stream without discarding the buffer.
+    ///
+    /// Queries the position of the underlying reader and subtracts the number of bytes read from
+    /// it but not consumed yet.
+    fn stream_position(&mut self) -> io::Result<u64> {
+        let inner_pos = self.inner.stream_position()?;
+        self.inner_offset = Some(inner_pos.wrapping_sub(self.read_count));
+
+        // possible if data was added with `.push_bytes()` or similar
+        inner_pos.checked_sub(self.total_pending()).ok_or_else(|| io::Error::new(
+            io::ErrorKind::InvalidData,
+            "BufReader has more data pending than the position of the underlying reader"
+        ))
+    }
+}
+
+/// An iterator over the lines of a `BufReader`, returned by `BufReader::lines_lossy()`.
+///
+/// Yields each line without its trailing `\n` or `\r\n`, with invalid UTF-8 sequences replaced.
+pub struct LinesLossy<R, P = StdPolicy> {
+    reader: BufReader<R, P>,
+}
+
+impl<R, P> LinesLossy<R, P> {
+    /// Return the underlying `BufReader`.
+    pub fn into_inner(self) -> BufReader<R, P> {
+        self.reader
+    }
+}
+
+impl<R: Read, P: ReaderPolicy> Iterator for LinesLossy<R, P> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<io::Result<String>> {
+        let mut line = Vec::new();
+
+        match self.reader.read_until(b'\n', &mut line) {
+            Ok(0) => return None,
+            Ok(_) => (),
+            Err(e) => return Some(Err(e)),
+        }
+
+        if line.ends_with(b"\n") {
+            line.pop();
+
+            if line.ends_with(b"\r") {
+                line.pop();
+            }
         }
+
+        Some(Ok(String::from_utf8(line).unwrap_or_else(|e| {
+            String::from_utf8_lossy(e.as_bytes()).into_owned()
+        })))
     }
 }
 
-impl<R, P> BufReader<R, P> {
-    /// Apply a new `ReaderPolicy` to this `BufReader`, returning the transformed type.
-    pub fn set_policy<P_: ReaderPolicy>(self, policy: P_) -> BufReader<R, P_> {
-        BufReader {
-            inner: self.inner,
-            buf: self.buf,
-            policy
-        }
+impl<R: fmt::Debug, P: fmt::Debug> fmt::Debug for LinesLossy<R, P> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("buf_redux::LinesLossy")
+            .field("reader", &self.reader)
+            .finish()
     }
+}
 
-    /// Mutate the current [`ReaderPolicy`](policy::ReaderPolicy) in-place.
-    ///
-    /// If you want to change the type, use `.set_policy()`.
-    pub fn policy_mut(&mut self) -> &mut P { &mut self.policy }
+/// A byte order mark recognized by `BufReader::strip_bom()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Bom {
+    /// `EF BB BF`
+    Utf8,
+    /// `FF FE`
+    Utf16Le,
+    /// `FE FF`
+    Utf16Be,
+    /// `FF FE 00 00`
+    Utf32Le,
+    /// `00 00 FE FF`
+    Utf32Be,
+}
 
-    /// Inspect the current `ReaderPolicy`.
-    pub fn policy(&self) -> &P {
-        &self.policy
+impl Bom {
+    /// Get the bytes of this byte order mark.
+    pub fn as_bytes(self) -> &'static [u8] {
+        match self {
+            Bom::Utf8 => b"\xEF\xBB\xBF",
+            Bom::Utf16Le => b"\xFF\xFE",
+            Bom::Utf16Be => b"\xFE\xFF",
+            Bom::Utf32Le => b"\xFF\xFE\x00\x00",
+            Bom::Utf32Be => b"\x00\x00\xFE\xFF",
+        }
     }
 
-    /// Move data to the start of the buffer, making room at the end for more 
-    /// reading.
+    /// Detect a byte order mark at the start of `bytes`.
+    pub fn detect(bytes: &[u8]) -> Option<Bom> {
+        // UTF-32 LE must be checked before UTF-16 LE, which is its prefix
+        [Bom::Utf32Le, Bom::Utf32Be, Bom::Utf8, Bom::Utf16Le, Bom::Utf16Be].iter().cloned()
+            .find(|bom| bytes.starts_with(bom.as_bytes()))
+    }
+}
+
+/// An iterator over the segments of a `BufReader` separated by a byte string,
+/// returned by `BufReader::split_slice()`.
+pub struct SplitSlice<R, P = StdPolicy> {
+    reader: BufReader<R, P>,
+    finder: memchr::memmem::Finder<'static>,
+    max_segment_len: usize,
+    done: bool,
+}
+
+impl<R, P> SplitSlice<R, P> {
+    /// Set the maximum length of a segment, not including the delimiter.
     ///
-    /// This is a no-op with the `*_ringbuf()` constructors (requires `slice-deque` feature).
-    pub fn make_room(&mut self) {
-        self.buf.make_room();
+    /// If a segment is longer, an error of kind `InvalidData` is yielded and iteration stops,
+    /// so hostile input cannot cause unbounded allocation. Unlimited by default.
+    pub fn max_segment_len(mut self, max: usize) -> Self {
+        self.max_segment_len = max;
+        self
     }
 
-    /// Ensure room in the buffer for *at least* `additional` bytes. May not be
-    /// quite exact due to implementation details of the buffer's allocator.
-    pub fn reserve(&mut self, additional: usize) {
-        self.buf.reserve(additional);
+    /// Return the underlying `BufReader`.
+    pub fn into_inner(self) -> BufReader<R, P> {
+        self.reader
     }
+}
 
-    // RFC: pub fn shrink(&mut self, new_len: usize) ?
+impl<R: Read, P: ReaderPolicy> Iterator for SplitSlice<R, P> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<io::Result<Vec<u8>>> {
+        if self.done { return None; }
+
+        let mut segment = Vec::new();
+
+        match self.reader.read_until_finder(&self.finder, self.max_segment_len, &mut segment) {
+            Ok(UntilOutcome::Found(read)) => {
+                segment.truncate(read - self.finder.needle().len());
+                Some(Ok(segment))
+            },
+            Ok(UntilOutcome::Eof(_)) => {
+                self.done = true;
+                Some(Ok(segment))
+            },
+            Ok(UntilOutcome::LimitReached(_)) => {
+                self.done = true;
+                Some(Err(io::Error::new(io::ErrorKind::InvalidData,
+                                        "segment exceeds the maximum length")))
+            },
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
 
-    /// Get the section of the buffer containing valid data; may be empty.
-    ///
-    /// Call `.consume()` to remove bytes from the beginning of this section.
-    pub fn buffer(&self) -> &[u8] {
-        self.buf.buf()
+impl<R: fmt::Debug, P: fmt::Debug> fmt::Debug for SplitSlice<R, P> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("buf_redux::SplitSlice")
+            .field("reader", &self.reader)
+            .field("delim", &self.finder.needle())
+            .field("max_segment_len", &self.max_segment_len)
+            .finish()
     }
+}
 
-    /// Get the current number of bytes available in the buffer.
-    pub fn buf_len(&self) -> usize {
-        self.buf.len()
+/// A length-limited view of a `BufReader`, returned by `BufReader::take_buffered()`.
+pub struct TakeBuffered<'a, R: 'a + ?Sized, P: 'a = StdPolicy> {
+    reader: &'a mut BufReader<R, P>,
+    limit: u64,
+}
+
+impl<'a, R: ?Sized, P> TakeBuffered<'a, R, P> {
+    /// Get the number of bytes that can still be read before the limit is reached.
+    pub fn limit(&self) -> u64 {
+        self.limit
     }
 
-    /// Get the total buffer capacity.
-    pub fn capacity(&self) -> usize {
-        self.buf.capacity()
+    /// Get the section of the parent's buffer within the limit; may be empty.
+    pub fn buffer(&self) -> &[u8] {
+        let buf = self.reader.buffer();
+        &buf[..cmp::min(buf.len() as u64, self.limit) as usize]
     }
 
-    /// Get an immutable reference to the underlying reader.
-    pub fn get_ref(&self) -> &R { &self.inner }
+    /// Get a reference to the parent `BufReader`.
+    pub fn get_ref(&self) -> &BufReader<R, P> {
+        self.reader
+    }
+}
 
-    /// Get a mutable reference to the underlying reader.
-    ///
-    /// ## Note
-    /// Reading directly from the underlying reader is not recommended, as some
-    /// data has likely already been moved into the buffer.
-    pub fn get_mut(&mut self) -> &mut R { &mut self.inner }
+impl<'a, R: Read + ?Sized, P: ReaderPolicy> Read for TakeBuffered<'a, R, P> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        // an empty read from the `BufReader` could still refill its buffer
+        if self.limit == 0 {
+            return Ok(0);
+        }
 
-    /// Consume `self` and return the inner reader only.
-    pub fn into_inner(self) -> R {
-        self.inner
+        let max = cmp::min(out.len() as u64, self.limit) as usize;
+        let read = self.reader.read(&mut out[..max])?;
+        self.limit -= read as u64;
+        Ok(read)
     }
+}
 
-    /// Consume `self` and return both the underlying reader and the buffer.
-    ///
-    /// See also: `BufReader::unbuffer()`
-    pub fn into_inner_with_buffer(self) -> (R, Buffer) {
-        (self.inner, self.buf)
+impl<'a, R: Read + ?Sized, P: ReaderPolicy> BufRead for TakeBuffered<'a, R, P> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.limit == 0 {
+            return Ok(&[]);
+        }
+
+        let buf = self.reader.fill_buf()?;
+        Ok(&buf[..cmp::min(buf.len() as u64, self.limit) as usize])
     }
 
-    /// Consume `self` and return an adapter which implements `Read` and will
-    /// empty the buffer before reading directly from the underlying reader.
-    pub fn unbuffer(self) -> Unbuffer<R> {
-        Unbuffer {
-            inner: self.inner,
-            buf: Some(self.buf),
-        }
+    fn consume(&mut self, amt: usize) {
+        let amt = cmp::min(amt as u64, self.limit) as usize;
+        self.reader.consume(amt);
+        self.limit -= amt as u64;
     }
 }
 
-impl<R, P: ReaderPolicy> BufReader<R, P> {
-    #[inline]
-    fn should_read(&mut self) -> bool {
-        self.policy.before_read(&mut self.buf).0
+impl<'a, R: fmt::Debug + ?Sized, P: fmt::Debug> fmt::Debug for TakeBuffered<'a, R, P> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("buf_redux::TakeBuffered")
+            .field("reader", &self.reader)
+            .field("limit", &self.limit)
+            .finish()
     }
 }
 
-impl<R: Read, P> BufReader<R, P> {
-    /// Unconditionally perform a read into the buffer.
-    ///
-    /// Does not invoke `ReaderPolicy` methods.
-    /// 
-    /// If the read was successful, returns the number of bytes read.
-    pub fn read_into_buf(&mut self) -> io::Result<usize> {
-        self.buf.read_from(&mut self.inner)
+/// The format of the length prefix of a frame read by `BufReader::read_frame_with()`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum FramePrefix {
+    /// A single byte.
+    U8,
+    /// A 16-bit little-endian integer.
+    U16Le,
+    /// A 16-bit big-endian integer.
+    U16Be,
+    /// A 32-bit little-endian integer.
+    U32Le,
+    /// A 32-bit big-endian integer; the default.
+    #[default]
+    U32Be,
+    /// A 64-bit little-endian integer.
+    U64Le,
+    /// A 64-bit big-endian integer.
+    U64Be,
+}
+
+impl FramePrefix {
+    /// The width of the prefix in bytes.
+    pub fn width(self) -> usize {
+        use self::FramePrefix::*;
+
+        match self {
+            U8 => 1,
+            U16Le | U16Be => 2,
+            U32Le | U32Be => 4,
+            U64Le | U64Be => 8,
+        }
     }
 
-    /// Box the inner reader without losing data.
-    pub fn boxed<'a>(self) -> BufReader<Box<Read + 'a>, P> where R: 'a {
-        let inner: Box<Read + 'a> = Box::new(self.inner);
-        
-        BufReader {
-            inner,
-            buf: self.buf,
-            policy: self.policy,
+    // decode the length from the start of `bytes`, which must be at least `.width()` long
+    fn decode(self, bytes: &[u8]) -> u64 {
+        use self::FramePrefix::*;
+
+        let bytes = &bytes[..self.width()];
+        let shift = |len, &byte| len << 8 | byte as u64;
+
+        match self {
+            U8 | U16Be | U32Be | U64Be => bytes.iter().fold(0, shift),
+            U16Le | U32Le | U64Le => bytes.iter().rev().fold(0, shift),
         }
     }
 }
 
-impl<R: Read, P: ReaderPolicy> Read for BufReader<R, P> {
-    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
-        // If we don't have any buffered data and we're doing a read matching
-        // or exceeding the internal buffer's capacity, bypass the buffer.
-        if self.buf.is_empty() && out.len() >= self.buf.capacity() {
-            return self.inner.read(out);
-        }
+/// A snapshot of the layout of the buffer of a `BufReader`, returned by `.buffer_state()`.
+///
+/// The buffered data is `cap - pos` bytes long; `pos` bytes before it are reclaimed by
+/// `.make_room()`, and `headroom` bytes after it can be read into right away.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BufferState {
+    /// The number of bytes at the head of the buffer before the data; see `.consumed()`.
+    pub pos: usize,
+    /// The offset of the end of the data.
+    pub cap: usize,
+    /// The total capacity of the buffer.
+    pub capacity: usize,
+    /// The space at the tail of the buffer which can be read into; see `.headroom()`.
+    pub headroom: usize,
+}
 
-        let nread = self.fill_buf()?.read(out)?;
-        self.consume(nread);
-        Ok(nread)
-    }
+/// How a call to `BufReader::read_until_limited()` ended, with the number of bytes appended.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UntilOutcome {
+    /// The delimiter was found; the count includes it.
+    Found(usize),
+    /// The limit was reached before the delimiter was found.
+    LimitReached(usize),
+    /// The underlying reader reached EOF before the delimiter was found.
+    Eof(usize),
 }
 
-impl<R: Read, P: ReaderPolicy> BufRead for BufReader<R, P> {
-    fn fill_buf(&mut self) -> io::Result<&[u8]> {
-        // If we've reached the end of our internal buffer then we need to fetch
-        // some more data from the underlying reader.
-        // This execution order is important; the policy may want to resize the buffer or move data
-        // before reading into it.
-        while self.should_read() && self.buf.usable_space() > 0 {
-            if self.read_into_buf()? == 0 { break; };
+impl UntilOutcome {
+    /// The number of bytes appended.
+    pub fn count(self) -> usize {
+        match self {
+            UntilOutcome::Found(n) | UntilOutcome::LimitReached(n) | UntilOutcome::Eof(n) => n,
         }
-
-        Ok(self.buffer())
     }
+}
 
-    fn consume(&mut self, mut amt: usize) {
-        amt = cmp::min(amt, self.buf_len());
-        self.buf.consume(amt);
-        self.policy.after_consume(&mut self.buf, amt);
+/// How a call to `BufReader::read_line_limited()` ended, with the number of bytes appended;
+/// the delimiter is the newline.
+pub type LineOutcome = UntilOutcome;
+
+/// An iterator over the bytes of a `BufReader`, returned by `BufReader::bytes_buffered()`.
+///
+/// Yields `Err` for any I/O error except interrupts, which are retried, and stops at EOF.
+pub struct ByteIter<'a, R: 'a + ?Sized, P: 'a = StdPolicy> {
+    reader: &'a mut BufReader<R, P>,
+}
+
+impl<'a, R: Read + ?Sized, P: ReaderPolicy> Iterator for ByteIter<'a, R, P> {
+    type Item = io::Result<u8>;
+
+    #[inline]
+    fn next(&mut self) -> Option<io::Result<u8>> {
+        match self.reader.read_byte() {
+            Ok(byte) => byte.map(Ok),
+            Err(e) => Some(Err(e)),
+        }
     }
 }
 
-impl<R: fmt::Debug, P: fmt::Debug> fmt::Debug for BufReader<R, P> {
+impl<'a, R: fmt::Debug + ?Sized, P: fmt::Debug> fmt::Debug for ByteIter<'a, R, P> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        fmt.debug_struct("buf_redux::BufReader")
-            .field("reader", &self.inner)
-            .field("buf_len", &self.buf_len())
-            .field("capacity", &self.capacity())
-            .field("policy", &self.policy)
+        fmt.debug_struct("buf_redux::ByteIter")
+            .field("reader", &self.reader)
             .finish()
     }
 }
 
-impl<R: Seek, P: ReaderPolicy> Seek for BufReader<R, P> {
-    /// Seek to an ofPet, in bytes, in the underlying reader.
-    ///
-    /// The position used for seeking with `SeekFrom::Current(_)` is the
-    /// position the underlying reader would be at if the `BufReader` had no
-    /// internal buffer.
-    ///
-    /// Seeking always discards the internal buffer, even if the seek position
-    /// would otherwise fall within it. This guarantees that calling
-    /// `.unwrap()` immediately after a seek yields the underlying reader at
-    /// the same position.
-    ///
-    /// See `std::io::Seek` for more details.
-    ///
-    /// Note: In the edge case where you're seeking with `SeekFrom::Current(n)`
-    /// where `n` minus the internal buffer length underflows an `i64`, two
-    /// seeks will be performed instead of one. If the second seek returns
-    /// `Err`, the underlying reader will be left at the same position it would
-    /// have if you seeked to `SeekFrom::Current(0)`.
-    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
-        let result: u64;
-        if let SeekFrom::Current(n) = pos {
-            let remainder = self.buf_len() as i64;
-            // it should be safe to assume that remainder fits within an i64 as the alternative
-            // means we managed to allocate 8 ebibytes and that's absurd.
-            // But it's not out of the realm of possibility for some weird underlying reader to
-            // support seeking by i64::min_value() so we need to handle underflow when subtracting
-            // remainder.
-            if let Some(offset) = n.checked_sub(remainder) {
-                result = self.inner.seek(SeekFrom::Current(offset))?;
+const MAX_VARINT_LEN: usize = 10;
+
+// decode a LEB128 varint from the start of `buf`, or `None` if the encoding isn't complete
+fn decode_varint(buf: &[u8]) -> io::Result<Option<(u64, usize)>> {
+    let mut val = 0;
+
+    for (i, &byte) in buf.iter().take(MAX_VARINT_LEN).enumerate() {
+        // the last byte may only contribute the highest bit of a `u64`
+        if i == MAX_VARINT_LEN - 1 && byte > 1 {
+            let msg = if byte & 0x80 != 0 {
+                "varint encoding exceeds 10 bytes"
             } else {
-                // seek backwards by our remainder, and then by the offset
-                self.inner.seek(SeekFrom::Current(-remainder))?;
-                self.buf.clear(); // empty the buffer
-                result = self.inner.seek(SeekFrom::Current(n))?;
-            }
-        } else {
-            // Seeking with Start/End doesn't care about our buffer length.
-            result = self.inner.seek(pos)?;
+                "varint overflows u64"
+            };
+
+            return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+        }
+
+        val |= ((byte & 0x7F) as u64) << (7 * i);
+
+        if byte & 0x80 == 0 {
+            return Ok(Some((val, i + 1)));
         }
-        self.buf.clear();
-        Ok(result)
     }
+
+    Ok(None)
 }
 
 /// A drop-in replacement for `std::io::BufWriter` with more functionality.
@@ -525,11 +3660,11 @@ impl<W: Write> BufWriter<W> {
     ///  [`FlushExact`](policy::FlushExact) to ensure there is always room to write more data if
     /// necessary, without expensive copying operations.
     ///
-    /// Only available on platforms with virtual memory support and with the `slice-deque` feature
-    /// enabled. The default capacity will differ between Windows and Unix-derivative targets.
+    /// A ringbuffer requires the `slice-deque` feature and a platform with virtual memory support;
+    /// otherwise this falls back to an ordinary buffer, like `::new()`.
+    /// The default capacity will differ between Windows and Unix-derivative targets.
     /// See [`Buffer::new_ringbuf()`](Buffer::new_ringbuf)
     /// or [the crate root docs](index.html#ringbuffers--slice-deque-feature) for more info.
-    #[cfg(feature = "slice-deque")]
     pub fn new_ringbuf(inner: W) -> Self {
         Self::with_buffer(Buffer::new_ringbuf(), inner)
     }
@@ -542,11 +3677,11 @@ impl<W: Write> BufWriter<W> {
     /// [`FlushExact`](policy::FlushExact) to ensure there is always room to write more data if
     /// necessary, without expensive copying operations.
     ///
-    /// Only available on platforms with virtual memory support and with the `slice-deque` feature
-    /// enabled. The capacity will be rounded up to the minimum size for the target platform.
+    /// A ringbuffer requires the `slice-deque` feature and a platform with virtual memory support;
+    /// otherwise this falls back to an ordinary buffer, like `::with_capacity()`.
+    /// The capacity will be rounded up to the minimum size for the target platform.
     /// See [`Buffer::with_capacity_ringbuf()`](Buffer::with_capacity_ringbuf)
     /// or [the crate root docs](index.html#ringbuffers--slice-deque-feature) for more info.
-    #[cfg(feature = "slice-deque")]
     pub fn with_capacity_ringbuf(cap: usize, inner: W) -> Self {
         Self::with_buffer(Buffer::with_capacity_ringbuf(cap), inner)
     }
@@ -752,13 +3887,11 @@ impl<W: Write> LineWriter<W> {
     }
 
     /// Wrap `inner` with the default buffer capacity using a ringbuffer.
-    #[cfg(feature = "slice-deque")]
     pub fn new_ringbuf(inner: W) -> Self {
         Self::with_buffer(Buffer::new_ringbuf(), inner)
     }
 
     /// Wrap `inner` with the given buffer capacity using a ringbuffer.
-    #[cfg(feature = "slice-deque")]
     pub fn with_capacity_ringbuf(cap: usize, inner: W) -> Self {
         Self::with_buffer(Buffer::with_capacity_ringbuf(cap), inner)
     }
@@ -840,42 +3973,182 @@ impl<W: Write + fmt::Debug> fmt::Debug for LineWriter<W> {
     }
 }
 
-/// The error type for `BufWriter::into_inner()`,
-/// contains the `BufWriter` as well as the error that occurred.
-#[derive(Debug)]
-pub struct IntoInnerError<W>(pub W, pub io::Error);
+/// The error type for `BufWriter::into_inner()`,
+/// contains the `BufWriter` as well as the error that occurred.
+#[derive(Debug)]
+pub struct IntoInnerError<W>(pub W, pub io::Error);
+
+impl<W> IntoInnerError<W> {
+    /// Get the error
+    pub fn error(&self) -> &io::Error {
+        &self.1
+    }
+
+    /// Take the writer.
+    pub fn into_inner(self) -> W {
+        self.0
+    }
+}
+
+impl<W> Into<io::Error> for IntoInnerError<W> {
+    fn into(self) -> io::Error {
+        self.1
+    }
+}
+
+impl<W: Any + Send + fmt::Debug> error::Error for IntoInnerError<W> {
+    fn description(&self) -> &str {
+        error::Error::description(self.error())
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        Some(&self.1)
+    }
+}
+
+impl<W> fmt::Display for IntoInnerError<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.error().fmt(f)
+    }
+}
+
+/// The error wrapped by the `UnexpectedEof` error returned from `BufReader::copy_exact()`.
+///
+/// Can be retrieved with `io::Error::get_ref()` and `downcast_ref()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShortCopyError {
+    copied: u64,
+    expected: u64,
+}
+
+impl ShortCopyError {
+    /// The number of bytes copied before the underlying reader reached EOF.
+    pub fn copied(&self) -> u64 {
+        self.copied
+    }
+
+    /// The number of bytes that were supposed to be copied.
+    pub fn expected(&self) -> u64 {
+        self.expected
+    }
+}
+
+impl error::Error for ShortCopyError {}
+
+impl fmt::Display for ShortCopyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "reached EOF after copying {} of {} bytes", self.copied, self.expected)
+    }
+}
+
+/// A reader which can tell how much data it has left, used to size buffers by
+/// `BufReader::new_sized()`.
+///
+/// The hint is advisory: a wrong hint may result in a buffer of a less suitable size, but must
+/// never cause incorrect behavior.
+pub trait SizeHint {
+    /// Get the number of bytes left to be read, if known.
+    fn remaining_hint(&self) -> Option<u64>;
+}
+
+impl SizeHint for &[u8] {
+    fn remaining_hint(&self) -> Option<u64> {
+        Some(self.len() as u64)
+    }
+}
+
+impl<T: AsRef<[u8]>> SizeHint for io::Cursor<T> {
+    fn remaining_hint(&self) -> Option<u64> {
+        Some((self.get_ref().as_ref().len() as u64).saturating_sub(self.position()))
+    }
+}
+
+/// The length of the file, as the position isn't known without seeking.
+impl SizeHint for File {
+    fn remaining_hint(&self) -> Option<u64> {
+        self.metadata().ok().filter(|meta| meta.is_file()).map(|meta| meta.len())
+    }
+}
+
+impl<R: SizeHint> SizeHint for io::Take<R> {
+    fn remaining_hint(&self) -> Option<u64> {
+        let hint = self.get_ref().remaining_hint();
+        Some(hint.map_or(self.limit(), |hint| cmp::min(hint, self.limit())))
+    }
+}
 
-impl<W> IntoInnerError<W> {
-    /// Get the error
-    pub fn error(&self) -> &io::Error {
-        &self.1
+impl<R: SizeHint + ?Sized> SizeHint for &mut R {
+    fn remaining_hint(&self) -> Option<u64> {
+        (**self).remaining_hint()
     }
+}
 
-    /// Take the writer.
-    pub fn into_inner(self) -> W {
-        self.0
+impl<R: SizeHint + ?Sized> SizeHint for Box<R> {
+    fn remaining_hint(&self) -> Option<u64> {
+        (**self).remaining_hint()
     }
 }
 
-impl<W> Into<io::Error> for IntoInnerError<W> {
-    fn into(self) -> io::Error {
-        self.1
+/// A reader which can be cloned fallibly, like a file or socket handle; see
+/// `BufReader::try_clone()`.
+pub trait TryCloneable: Sized {
+    /// Create a new handle to the same underlying reader.
+    fn try_clone(&self) -> io::Result<Self>;
+}
+
+impl TryCloneable for ::std::fs::File {
+    fn try_clone(&self) -> io::Result<Self> {
+        ::std::fs::File::try_clone(self)
     }
 }
 
-impl<W: Any + Send + fmt::Debug> error::Error for IntoInnerError<W> {
-    fn description(&self) -> &str {
-        error::Error::description(self.error())
+impl TryCloneable for ::std::net::TcpStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        ::std::net::TcpStream::try_clone(self)
     }
+}
 
-    fn cause(&self) -> Option<&error::Error> {
-        Some(&self.1)
+#[cfg(unix)]
+impl TryCloneable for ::std::os::unix::net::UnixStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        ::std::os::unix::net::UnixStream::try_clone(self)
     }
 }
 
-impl<W> fmt::Display for IntoInnerError<W> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.error().fmt(f)
+/// Memory supplied by the user to back a `Buffer`, e.g. from a pool or custom allocator.
+///
+/// See [`Buffer::with_storage()`](Buffer::with_storage). The whole of `as_slice()` is used as
+/// the capacity of the buffer, so its length must only change in `reserve()` and `shrink()`.
+pub trait BufStorage: Send + Sync {
+    /// Get the memory of the storage.
+    fn as_slice(&self) -> &[u8];
+
+    /// Get the memory of the storage mutably.
+    fn as_mut_slice(&mut self) -> &mut [u8];
+
+    /// Grow the storage by *at least* `additional` bytes, preserving its contents.
+    fn reserve(&mut self, additional: usize);
+
+    /// Release memory beyond the first `new_cap` bytes, preserving them.
+    ///
+    /// This is a no-op by default.
+    fn shrink(&mut self, _new_cap: usize) {}
+}
+
+/// Uses the whole length of the vector; it's zero-extended to grow it.
+impl BufStorage for Vec<u8> {
+    fn as_slice(&self) -> &[u8] { self }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] { self }
+
+    fn reserve(&mut self, additional: usize) {
+        let len = self.len();
+        self.resize(len + additional, 0);
+    }
+
+    fn shrink(&mut self, new_cap: usize) {
+        self.truncate(new_cap);
+        self.shrink_to_fit();
     }
 }
 
@@ -884,7 +4157,6 @@ impl<W> fmt::Display for IntoInnerError<W> {
 /// Supports interacting via I/O traits like `Read` and `Write`, and direct access.
 pub struct Buffer {
     buf: BufImpl,
-    zeroed: usize,
 }
 
 impl Buffer {
@@ -899,7 +4171,73 @@ impl Buffer {
     pub fn with_capacity(cap: usize) -> Self {
         Buffer {
             buf: BufImpl::with_capacity(cap),
-            zeroed: 0,
+        }
+    }
+
+    /// Create a new buffer with *at least* the given capacity whose memory is aligned to `align`
+    /// bytes, as required for reading from files opened with `O_DIRECT` for example.
+    ///
+    /// The capacity is rounded up to a multiple of `align`, including when the buffer is grown
+    /// or shrunk. Whenever the buffer is read into, the tail is at a multiple of `align` from the
+    /// start, so the usable space starts at an aligned address and has an aligned length; data
+    /// is moved as necessary to keep it that way, e.g. after a short read.
+    ///
+    /// ### Panics
+    /// If `align` is not a power of two.
+    pub fn with_capacity_aligned(cap: usize, align: usize) -> Self {
+        Buffer {
+            buf: BufImpl::with_capacity_aligned(cap, align),
+        }
+    }
+
+    /// Create a new buffer from `vec`, adopting its allocation; its contents are the initial data
+    /// in the buffer and its capacity is the capacity of the buffer.
+    pub fn from_vec(vec: Vec<u8>) -> Self {
+        let len = vec.len();
+
+        Buffer {
+            buf: BufImpl::from_vec(vec, 0, len),
+        }
+    }
+
+    /// Convert this buffer into a `Vec` containing the data in it, with the capacity of the buffer.
+    ///
+    /// This doesn't copy the data or allocate unless the buffer is a ringbuffer, aligned or uses
+    /// custom storage (see [`with_storage()`](Buffer::with_storage)).
+    pub fn into_vec(self) -> Vec<u8> {
+        self.buf.into_vec(false).0
+    }
+
+    /// Set whether this buffer is overwritten with zeroes before its memory is freed, for holding
+    /// secrets like private keys or passphrases. Requires the `secure` feature.
+    ///
+    /// When set, the memory is wiped with volatile writes:
+    ///
+    /// * when the buffer is dropped, before its memory is freed or returned to its
+    ///   [`BufferPool`](BufferPool);
+    /// * when the buffer is reallocated to grow or shrink, which always copies to a new allocation
+    ///   so the old one can be wiped first;
+    /// * when `.make_room()` moves the data, for the part of its old location that wasn't
+    ///   overwritten.
+    ///
+    /// Consumed data is *not* wiped until one of the above happens, as that would slow down every
+    /// read. Memory handed over to the caller, e.g. by `.into_vec()` or
+    /// `BufReader::into_parts()`, is not wiped either; it is the caller's responsibility then.
+    /// Custom storage (see [`with_storage()`](Buffer::with_storage)) is wiped when the buffer is
+    /// dropped, but reallocating it is up to the `BufStorage` implementation.
+    #[cfg(feature = "secure")]
+    pub fn set_zeroize_on_drop(&mut self, zeroize: bool) {
+        self.buf.set_zeroize(zeroize);
+    }
+
+    /// Create a new buffer using `storage` for its memory instead of allocating it; the buffer
+    /// starts empty and its capacity is the length of `storage.as_slice()`.
+    ///
+    /// Growing and shrinking the buffer go through the `BufStorage` methods. The storage is
+    /// dropped with the buffer, e.g. to return its memory to a pool.
+    pub fn with_storage<S: BufStorage + 'static>(storage: S) -> Self {
+        Buffer {
+            buf: BufImpl::with_storage(Box::new(storage)),
         }
     }
 
@@ -912,12 +4250,12 @@ impl Buffer {
     /// `std::io` buffered types)
     /// * Windows: **64KiB** because of legacy reasons, of course (see below)
     ///
-    /// Only available on platforms with virtual memory support and with the `slice-deque` feature
-    /// enabled. The current platforms that are supported/tested are listed
-    /// [in the README for the `slice-deque` crate][slice-deque].
+    /// A ringbuffer requires the `slice-deque` feature and a platform with virtual memory support;
+    /// the current platforms that are supported/tested are listed
+    /// [in the README for the `slice-deque` crate][slice-deque]. Otherwise, this falls back to an
+    /// ordinary buffer like `Buffer::new()`; `.is_ringbuf()` tells which one it is.
     ///
     /// [slice-deque]: https://github.com/gnzlbg/slice_deque#platform-support
-    #[cfg(feature = "slice-deque")]
     pub fn new_ringbuf() -> Self {
         Self::with_capacity_ringbuf(DEFAULT_BUF_SIZE)
     }
@@ -935,16 +4273,15 @@ impl Buffer {
     ///
     /// [Win-why-64k]: https://blogs.msdn.microsoft.com/oldnewthing/20031008-00/?p=42223
     ///
-    /// Only available on platforms with virtual memory support and with the `slice-deque` feature
-    /// enabled. The current platforms that are supported/tested are listed
-    /// [in the README for the `slice-deque` crate][slice-deque].
+    /// A ringbuffer requires the `slice-deque` feature and a platform with virtual memory support;
+    /// the current platforms that are supported/tested are listed
+    /// [in the README for the `slice-deque` crate][slice-deque]. Otherwise, this falls back to an
+    /// ordinary buffer like `Buffer::with_capacity()`; `.is_ringbuf()` tells which one it is.
     ///
     /// [slice-deque]: https://github.com/gnzlbg/slice_deque#platform-support
-    #[cfg(feature = "slice-deque")]
     pub fn with_capacity_ringbuf(cap: usize) -> Self {
         Buffer {
             buf: BufImpl::with_capacity_ringbuf(cap),
-            zeroed: 0,
         }
     }
 
@@ -1006,10 +4343,18 @@ impl Buffer {
     /// ### Panics
     /// If `self.capacity() + additional` overflows.
     pub fn reserve(&mut self, additional: usize) {
-        // Returns `true` if we reallocated out-of-place and thus need to re-zero.
-        if self.buf.reserve(additional) {
-            self.zeroed = 0;
-        }
+        self.buf.reserve(additional);
+    }
+
+    /// Reduce the capacity of the buffer to `new_cap`, releasing the excess memory.
+    ///
+    /// This moves the data in the buffer down first; the capacity will not be reduced below
+    /// `.len()` so no data is discarded. This is a no-op if `new_cap >= self.capacity()`.
+    ///
+    /// For ringbuffers the capacity is rounded up to a multiple of the page size,
+    /// so the buffer may not actually shrink.
+    pub fn shrink(&mut self, new_cap: usize) {
+        self.buf.shrink(new_cap);
     }
 
     /// Get an immutable slice of the available bytes in this buffer.
@@ -1026,8 +4371,8 @@ impl Buffer {
     ///
     /// If there is no more room at the head of the buffer, this will return `Ok(0)`.
     ///
-    /// Uses `Read::initializer()` to initialize the buffer if the `nightly`
-    /// feature is enabled, otherwise the buffer is zeroed if it has never been written.
+    /// Memory is zeroed the first time it is read into; allocating or growing the buffer
+    /// is cheap regardless of its capacity.
     ///
     /// ### Panics
     /// If the returned count from `rdr.read()` overflows the tail cursor of this buffer.
@@ -1036,16 +4381,6 @@ impl Buffer {
             return Ok(0);
         }
 
-        let cap = self.capacity();
-        if self.zeroed < cap {
-            unsafe {
-                let buf = self.buf.write_buf();
-                init_buffer(&rdr, buf);
-            }
-
-            self.zeroed = cap;
-        }
-
         let read = {
             let mut buf = unsafe { self.buf.write_buf() };
             rdr.read(buf)?
@@ -1058,6 +4393,36 @@ impl Buffer {
         Ok(read)
     }
 
+    /// Read from `rdr` with `.read_uninit()` like `.read_from()`, but without zeroing memory
+    /// which has never been written first; see [`TrustRead`](TrustRead).
+    ///
+    /// ### Panics
+    /// If the returned count from `rdr.read_uninit()` overflows the tail cursor of this buffer.
+    pub fn read_from_trusted<R: TrustRead + ?Sized>(&mut self, rdr: &mut R) -> io::Result<usize> {
+        // `TrustRead` guarantees `rdr` only initializes the tail and reports how much it did
+        unsafe { self.read_uninit_with(|buf| rdr.read_uninit(buf)) }
+    }
+
+    // read into the possibly uninitialized tail with `read`, which must return no more than the
+    // number of bytes it initialized at the start of it
+    unsafe fn read_uninit_with<F>(&mut self, read: F) -> io::Result<usize>
+    where F: FnOnce(&mut [MaybeUninit<u8>]) -> io::Result<usize> {
+        if self.usable_space() == 0 {
+            return Ok(0);
+        }
+
+        let read = {
+            let buf = self.buf.write_buf_uninit();
+            let read = read(buf)?;
+            assert!(read <= buf.len(), "TrustRead reader returned a count that overflows the buffer");
+            read
+        };
+
+        self.buf.bytes_written(read);
+
+        Ok(read)
+    }
+
     /// Copy from `src` to the tail of this buffer. Returns the number of bytes copied.
     ///
     /// This will **not** grow the buffer if `src` is larger than `self.usable_space()`; instead,
@@ -1170,15 +4535,80 @@ impl Buffer {
         }
     }
 
+    /// Insert `bytes` before the data in this buffer, so they are returned first.
+    ///
+    /// The consumed space at the head of the buffer is used if it's large enough (overwriting
+    /// consumed bytes, which can no longer be restored with `.unconsume()`); otherwise the data
+    /// is moved towards the tail to make room, growing the buffer if necessary.
+    pub fn prepend_bytes(&mut self, bytes: &[u8]) {
+        let len = bytes.len();
+
+        // the bytes which don't fit in front of the data
+        let shift = len - self.buf.unconsume(len);
+
+        if shift > 0 {
+            if self.usable_space() < shift {
+                self.reserve(shift);
+            }
+
+            unsafe {
+                self.buf.write_buf();
+                self.buf.bytes_written(shift);
+            }
+
+            let buf = self.buf.buf_mut();
+            let data_len = buf.len() - shift;
+            buf.copy_within(..data_len, shift);
+        }
+
+        self.buf.buf_mut()[..len].copy_from_slice(bytes);
+    }
+
     /// Consume `amt` bytes from the head of this buffer.
     pub fn consume(&mut self, amt: usize) {
         self.buf.consume(amt);
     }
 
+    /// Restore up to `amt` of the most recently consumed bytes to the head of this buffer,
+    /// returning the number of bytes actually restored.
+    ///
+    /// Consumed bytes can only be restored while they are still physically present in the
+    /// buffer; they are lost when the buffer is cleared, reallocated, moved down with
+    /// `.make_room()` or overwritten by new data (in the non-ringbuffer case this happens when
+    /// it is written to after being emptied).
+    pub fn unconsume(&mut self, amt: usize) -> usize {
+        self.buf.unconsume(amt)
+    }
+
     /// Empty this buffer by consuming all bytes.
+    ///
+    /// Consumed bytes can no longer be restored with `.unconsume()` afterwards.
     pub fn clear(&mut self) {
-        let buf_len = self.len();
-        self.consume(buf_len);
+        self.buf.clear();
+    }
+
+    // keep the last `amt` consumed bytes from being moved out or overwritten
+    fn retain_consumed(&mut self, amt: usize) {
+        self.buf.set_retain(amt);
+    }
+
+    // get the last `amt` consumed bytes, which must still be in the buffer
+    fn consumed_tail(&mut self, amt: usize) -> &[u8] {
+        let restored = self.buf.unconsume(amt);
+        debug_assert_eq!(restored, amt, "consumed bytes were lost");
+
+        let ptr = self.buf.buf().as_ptr();
+        self.buf.consume(restored);
+
+        // safe because consuming only moves the head cursor, the bytes themselves stay in place
+        unsafe { slice::from_raw_parts(ptr, restored) }
+    }
+}
+
+/// Equivalent to [`Buffer::from_vec()`](Buffer::from_vec).
+impl From<Vec<u8>> for Buffer {
+    fn from(vec: Vec<u8>) -> Self {
+        Buffer::from_vec(vec)
     }
 }
 
@@ -1192,16 +4622,24 @@ impl fmt::Debug for Buffer {
 }
 
 /// A `Read` adapter for a consumed `BufReader` which will empty bytes from the buffer before
-/// reading from `R` directly. Frees the buffer when it has been emptied.
+/// reading from `R` directly. Frees the buffer as soon as it has been emptied.
 pub struct Unbuffer<R> {
     inner: R,
+    /// The remaining data, if any; released as soon as it's used up.
     buf: Option<Buffer>,
+    read_count: u64,
 }
 
 impl<R> Unbuffer<R> {
-    /// Returns `true` if the buffer still has some bytes left, `false` otherwise.
+    /// Returns `true` if the buffer has been used up and released, `false` if it still has some
+    /// bytes left.
     pub fn is_buf_empty(&self) -> bool {
-        !self.buf.is_some()
+        self.buf.is_none()
+    }
+
+    /// Returns the capacity of the buffer, which is zero once it has been used up and released.
+    pub fn buf_capacity(&self) -> usize {
+        self.buf.as_ref().map_or(0, Buffer::capacity)
     }
 
     /// Returns the number of bytes remaining in the buffer.
@@ -1214,25 +4652,161 @@ impl<R> Unbuffer<R> {
         self.buf.as_ref().map_or(&[], Buffer::buf)
     }
 
+    /// Get the number of bytes read from the underlying reader, including those read by the
+    /// `BufReader` this was created from; see `BufReader::total_read()`.
+    pub fn total_read(&self) -> u64 {
+        self.read_count
+    }
+
+    /// Get the number of bytes consumed from this reader so far, including those consumed from
+    /// the `BufReader` this was created from; see `BufReader::consumer_offset()`.
+    pub fn consumer_offset(&self) -> u64 {
+        self.read_count.saturating_sub(self.buf_len() as u64)
+    }
+
+    /// Convert back into a `BufReader` with a capacity of at least `capacity`, which returns the
+    /// remaining buffered data first.
+    ///
+    /// The buffer is reused if there is one left, and grown only if it's smaller than
+    /// `capacity`. The count returned by `.total_read()` is carried over.
+    pub fn rebuffer(self, capacity: usize) -> BufReader<R> {
+        let mut reader = match self.buf {
+            Some(mut buf) => {
+                if buf.capacity() < capacity {
+                    buf.make_room();
+                    buf.reserve(capacity - buf.len());
+                }
+
+                BufReader::with_buffer(buf, self.inner)
+            },
+            None => BufReader::with_capacity(capacity, self.inner),
+        };
+
+        reader.read_count = self.read_count;
+        reader
+    }
+
     /// Return the underlying reader, releasing the buffer.
+    ///
+    /// ### Note
+    /// This **discards** the `.buf_len()` bytes still in the buffer, which were already read from
+    /// the underlying reader. Use `.into_inner_with_buf()` to keep them.
     pub fn into_inner(self) -> R {
         self.inner
     }
+
+    /// Return the underlying reader and the data remaining in the buffer as a `Vec`.
+    ///
+    /// The data is not copied in the cases described for
+    /// [`Buffer::into_vec()`](Buffer::into_vec). The `Vec` is empty if the buffer was used up.
+    pub fn into_inner_with_buf(self) -> (R, Vec<u8>) {
+        (self.inner, self.buf.map_or_else(Vec::new, Buffer::into_vec))
+    }
 }
 
 impl<R: Read> Read for Unbuffer<R> {
     fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
-        if let Some(ref mut buf) = self.buf.as_mut() {
+        if let Some(ref mut buf) = self.buf {
             let read = buf.copy_to_slice(out);
 
-            if out.len() != 0 && read != 0 {
-                return Ok(read);
+            // free the buffer as soon as it's used up
+            if buf.is_empty() {
+                self.buf = None;
             }
+
+            return Ok(read);
         }
 
-        self.buf = None;
+        let read = self.inner.read(out)?;
+        self.read_count = self.read_count.wrapping_add(read as u64);
+        Ok(read)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut]) -> io::Result<usize> {
+        if let Some(ref mut buf) = self.buf {
+            let mut read = 0;
+
+            for out in bufs.iter_mut() {
+                if buf.is_empty() { break; }
+                read += buf.copy_to_slice(out);
+            }
+
+            if buf.is_empty() {
+                self.buf = None;
+            }
+
+            return Ok(read);
+        }
+
+        let read = self.inner.read_vectored(bufs)?;
+        self.read_count = self.read_count.wrapping_add(read as u64);
+        Ok(read)
+    }
+
+    fn read_to_end(&mut self, out: &mut Vec<u8>) -> io::Result<usize> {
+        // append the whole buffer at once
+        let buffered = self.buf.take().map_or(0, |buf| {
+            out.extend_from_slice(buf.buf());
+            buf.len()
+        });
+
+        let start = out.len();
+        let res = self.inner.read_to_end(out);
+        // some bytes may have been read even if it failed
+        self.read_count = self.read_count.wrapping_add((out.len() - start) as u64);
+        res.map(|read| buffered + read)
+    }
+}
+
+/// Returns the remaining buffered data until it has all been consumed, then delegates to the
+/// underlying reader.
+impl<R: BufRead> BufRead for Unbuffer<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match self.buf {
+            Some(ref buf) => Ok(buf.buf()),
+            None => self.inner.fill_buf(),
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if let Some(ref mut buf) = self.buf {
+            buf.consume(amt);
+            if buf.is_empty() { self.buf = None; }
+            return;
+        }
+
+        // counted once consumed, as `fill_buf()` may return the same bytes again
+        self.inner.consume(amt);
+        self.read_count = self.read_count.wrapping_add(amt as u64);
+    }
+}
+
+impl<R: Seek> Seek for Unbuffer<R> {
+    /// Seek to an offset, in bytes, in the underlying reader, discarding the remaining buffered
+    /// data.
+    ///
+    /// As with `BufReader`, the position used for seeking with `SeekFrom::Current(_)` is the
+    /// position the underlying reader would be at if there were no buffer, and if `n` minus the
+    /// buffer length underflows an `i64`, two seeks will be performed instead of one.
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let result: u64;
+        if let SeekFrom::Current(n) = pos {
+            // see `BufReader::seek()`
+            let remainder = self.buf_len() as i64;
 
-        self.inner.read(out)
+            if let Some(offset) = n.checked_sub(remainder) {
+                result = self.inner.seek(SeekFrom::Current(offset))?;
+            } else {
+                // seek backwards by our remainder, and then by the offset
+                self.inner.seek(SeekFrom::Current(-remainder))?;
+                self.buf = None;
+                result = self.inner.seek(SeekFrom::Current(n))?;
+            }
+        } else {
+            result = self.inner.seek(pos)?;
+        }
+        self.buf = None;
+        Ok(result)
     }
 }
 
@@ -1245,6 +4819,163 @@ impl<R: fmt::Debug> fmt::Debug for Unbuffer<R> {
     }
 }
 
+/// A `BufRead` adapter for a `BufReader` which writes every byte returned from `read()` or
+/// passed to `consume()` to a sink, returned by `BufReader::tee()`.
+///
+/// Bytes are only written once consumed, so `fill_buf()` alone doesn't write anything.
+/// This includes bytes read directly from the underlying reader, bypassing the buffer.
+///
+/// An error writing the consumed bytes to the sink is returned by the next call to `read()` or
+/// `fill_buf()` instead, since `consume()` cannot return errors and `read()` has already taken
+/// the bytes from the reader; they are still returned to the caller.
+pub struct TeeReader<R, W, P = StdPolicy> {
+    reader: BufReader<R, P>,
+    sink: W,
+    err: Option<io::Error>,
+}
+
+impl<R, W, P> TeeReader<R, W, P> {
+    /// Get a reference to the `BufReader`.
+    pub fn get_ref(&self) -> &BufReader<R, P> {
+        &self.reader
+    }
+
+    /// Get a mutable reference to the `BufReader`.
+    ///
+    /// Bytes consumed through this reference are not written to the sink.
+    pub fn get_mut(&mut self) -> &mut BufReader<R, P> {
+        &mut self.reader
+    }
+
+    /// Get a reference to the sink.
+    pub fn sink(&self) -> &W {
+        &self.sink
+    }
+
+    /// Get a mutable reference to the sink.
+    pub fn sink_mut(&mut self) -> &mut W {
+        &mut self.sink
+    }
+
+    /// Consume `self` and return the `BufReader` and the sink.
+    pub fn into_inner(self) -> (BufReader<R, P>, W) {
+        (self.reader, self.sink)
+    }
+
+    fn take_err(&mut self) -> io::Result<()> {
+        self.err.take().map_or(Ok(()), Err)
+    }
+}
+
+impl<R: Read, W: Write, P: ReaderPolicy> Read for TeeReader<R, W, P> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        self.take_err()?;
+
+        let read = self.reader.read(out)?;
+
+        // the bytes are consumed, so they must be returned regardless
+        if let Err(e) = self.sink.write_all(&out[..read]) {
+            self.err = Some(e);
+        }
+
+        Ok(read)
+    }
+}
+
+impl<R: Read, W: Write, P: ReaderPolicy> BufRead for TeeReader<R, W, P> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.take_err()?;
+        self.reader.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        let consumed = self.reader.consume_and_get(amt);
+
+        if self.err.is_none() {
+            if let Err(e) = self.sink.write_all(consumed) {
+                self.err = Some(e);
+            }
+        }
+    }
+}
+
+impl<R: fmt::Debug, W: fmt::Debug, P: fmt::Debug> fmt::Debug for TeeReader<R, W, P> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("buf_redux::TeeReader")
+            .field("reader", &self.reader)
+            .field("sink", &self.sink)
+            .finish()
+    }
+}
+
+/// A reader which passes every slice of bytes read from `R` to an observer,
+/// returned by `BufReader::observe_reads()`.
+///
+/// The observer sees bytes in the order they are read from `R`. Seeking is forwarded to `R`
+/// without notifying the observer, so after a seek it will see bytes from the new position;
+/// bytes that are read again are observed again. Since `BufReader` discards its buffer when
+/// seeking, these may include bytes that were observed but never consumed.
+pub struct Observed<R, F> {
+    inner: R,
+    observer: F,
+}
+
+impl<R, F> Observed<R, F> {
+    /// Get a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying reader.
+    ///
+    /// Bytes read through this reference are not observed.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Consume `self` and return the underlying reader and the observer.
+    pub fn into_inner(self) -> (R, F) {
+        (self.inner, self.observer)
+    }
+}
+
+impl<R: Read, F: FnMut(&[u8])> Read for Observed<R, F> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(out)?;
+        (self.observer)(&out[..read]);
+        Ok(read)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut]) -> io::Result<usize> {
+        let mut left = self.inner.read_vectored(bufs)?;
+        let read = left;
+
+        for buf in bufs.iter() {
+            if left == 0 { break; }
+
+            let len = cmp::min(buf.len(), left);
+            (self.observer)(&buf[..len]);
+            left -= len;
+        }
+
+        Ok(read)
+    }
+}
+
+impl<R: Seek, F> Seek for Observed<R, F> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+impl<R: fmt::Debug, F> fmt::Debug for Observed<R, F> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("buf_redux::Observed")
+            .field("reader", &self.inner)
+            .finish()
+    }
+}
+
 /// Copy data between a `BufRead` and a `Write` without an intermediate buffer.
 ///
 /// Retries on interrupts. Returns the total bytes copied or the first error;
@@ -1288,8 +5019,3 @@ where F: Fn(&mut Write, &mut Buffer, io::Error)
     DROP_ERR_HANDLER.with(|deh| *deh.borrow_mut() = Box::new(handler))
 }
 
-#[cfg(not(feature = "nightly"))]
-fn init_buffer<R: Read + ?Sized>(_r: &R, buf: &mut [u8]) {
-    // we can't trust a reader without nightly
-    safemem::write_bytes(buf, 0);
-}
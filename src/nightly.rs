@@ -6,10 +6,10 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-//! Anything requiring unstable features (specialization, `Read::initializer()`, etc)
+//! Anything requiring unstable features (specialization, etc)
 
 use std::fmt;
-use std::io::{Read, Write};
+use std::io::Write;
 
 use super::{BufReader, BufWriter, LineWriter};
 
@@ -46,8 +46,3 @@ impl<W: Write> fmt::Debug for LineWriter<W> {
     }
 }
 
-pub fn init_buffer<R: Read + ?Sized>(rdr: &R, buf: &mut [u8]) {
-    // no invariants for consumers to uphold:
-    // https://doc.rust-lang.org/nightly/std/io/trait.Read.html#method.initializer
-    unsafe { rdr.initializer().initialize(buf) }
-}
@@ -31,7 +31,7 @@ macro_rules! do_read (
 ///
 /// * `BufReader`: only reads when the buffer is empty, does not resize or move data.
 /// * `BufWriter`: only flushes the buffer when there is not enough room for an incoming write.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct StdPolicy;
 
 /// Trait that governs `BufReader`'s behavior.
@@ -42,10 +42,10 @@ pub trait ReaderPolicy {
     /// or `DoRead(false)` to read from the buffer as it is, even if it's empty.
     /// `do_read!()` is provided as a shorthand.
     ///
-    /// If there is no room in the buffer after this method is called,
-    /// the buffer will not be read into (so if the buffer is full but you want more data
-    /// you should call `.make_room()` or reserve more space). If there *is* room, `BufReader` will
-    /// attempt to read into the buffer. If successful (`Ok(x)` where `x > 0` is returned), this
+    /// If there is no room at the tail of the buffer after this method is called, `BufReader`
+    /// calls `.make_room()` to reclaim the bytes consumed from the head; if the buffer is still
+    /// full it will not be read into (so if you want more data you should reserve more space).
+    /// If there *is* room, `BufReader` will attempt to read into the buffer. If successful (`Ok(x)` where `x > 0` is returned), this
     /// method will be consulted again for another read attempt.
     ///
     /// By default, this implements `std::io::BufReader`'s behavior: only read into the buffer if
@@ -72,7 +72,17 @@ impl ReaderPolicy for StdPolicy {}
 /// A policy for [`BufReader`](::BufReader) which ensures there is at least the given number of
 /// bytes in  the buffer, failing this only if the reader is at EOF.
 ///
-/// If the minimum buffer length is greater than the buffer capacity, it will be resized.
+/// If the minimum buffer length is greater than the buffer capacity, it will be resized. If the
+/// free space is large enough but not at the tail of the buffer, the data is moved down first.
+///
+/// Since this applies to every `fill_buf()`, code which only knows the reader as a `BufRead`
+/// (e.g. `&mut dyn BufRead`) always gets at least the minimum before EOF, without calling
+/// `BufReader::fill_buf_min()` itself.
+///
+/// `read()` and `read_vectored()` still bypass the buffer when it is empty and the destination
+/// is at least as large as its capacity: the data goes straight to the caller, who asked for more
+/// than the buffer could hold anyway, and the next `fill_buf()` tops the buffer up as usual. The
+/// minimum only concerns data in the buffer, so no bypass can violate it.
 ///
 /// ### Example
 /// ```rust
@@ -102,7 +112,7 @@ impl ReaderPolicy for StdPolicy {}
 /// // no data left in the reader
 /// assert_eq!(reader.fill_buf().unwrap(), &[13, 14, 15]);
 /// ```
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct MinBuffered(pub usize);
 
 impl MinBuffered {
@@ -115,7 +125,7 @@ impl MinBuffered {
 impl ReaderPolicy for MinBuffered {
     fn before_read(&mut self, buffer: &mut Buffer) -> DoRead {
         // do nothing if we have enough data
-        if buffer.len() >= self.0 { do_read!(false) }
+        if buffer.len() >= self.0 { do_read!(false); }
 
         let cap = buffer.capacity();
 
@@ -173,7 +183,7 @@ pub trait WriterPolicy {
 impl WriterPolicy for StdPolicy {}
 
 /// Flush the buffer if it contains at least the given number of bytes.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct FlushAtLeast(pub usize);
 
 impl WriterPolicy for FlushAtLeast {
@@ -188,7 +198,7 @@ impl WriterPolicy for FlushAtLeast {
 }
 
 /// Only ever flush exactly the given number of bytes, until the writer is empty.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct FlushExact(pub usize);
 
 impl WriterPolicy for FlushExact {
@@ -217,7 +227,7 @@ impl WriterPolicy for FlushExact {
 /// Flush the buffer if it contains the given byte.
 ///
 /// Only scans the buffer after reading. Searches from the end first.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct FlushOn(pub u8);
 
 impl WriterPolicy for FlushOn {
@@ -230,7 +240,7 @@ impl WriterPolicy for FlushOn {
 /// Flush the buffer if it contains a newline (`\n`).
 ///
 /// Equivalent to `FlushOn(b'\n')`.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct FlushOnNewline;
 
 impl WriterPolicy for FlushOnNewline {
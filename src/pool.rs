@@ -0,0 +1,112 @@
+// Copyright 2018 Austin Bonander <austin.bonander@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+//! Recycling buffer allocations across short-lived readers.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use buffer::{AllocBuf, BufImpl};
+
+use {Buffer, BufReader};
+
+/// A pool of buffers which are handed out to new readers and returned to it when they're dropped,
+/// so the memory doesn't have to be allocated and zeroed for each one.
+///
+/// Useful for servers which wrap every connection in a `BufReader`. Cloning the pool is cheap
+/// and the clones share the same buffers; it can be shared between threads.
+///
+/// A buffer is returned to the pool when the `BufReader` or `Buffer` holding it is dropped,
+/// including after `BufReader::into_inner()`, unless its capacity was changed in the meantime
+/// or the pool already holds `max_pooled` buffers. `BufReader::into_inner_with_buffer()`
+/// detaches the buffer from the pool.
+///
+/// ### Example
+/// ```rust
+/// use buf_redux::BufferPool;
+/// use std::io::BufRead;
+///
+/// let pool = BufferPool::new(8192, 16);
+///
+/// let mut reader = pool.reader(&b"hello, world!"[..]);
+/// assert_eq!(reader.fill_buf().unwrap(), b"hello, world!");
+///
+/// drop(reader);
+/// assert_eq!(pool.pooled(), 1);
+/// ```
+#[derive(Clone)]
+pub struct BufferPool {
+    shared: Arc<PoolShared>,
+}
+
+pub struct PoolShared {
+    capacity: usize,
+    max_pooled: usize,
+    buffers: Mutex<Vec<AllocBuf>>,
+}
+
+impl BufferPool {
+    /// Create a pool handing out buffers of `capacity` bytes and keeping up to `max_pooled`
+    /// of them for reuse.
+    pub fn new(capacity: usize, max_pooled: usize) -> Self {
+        BufferPool {
+            shared: Arc::new(PoolShared {
+                capacity,
+                max_pooled,
+                buffers: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Get a buffer from the pool, or allocate one if it's empty.
+    pub fn buffer(&self) -> Buffer {
+        let buf = self.shared.buffers.lock().ok().and_then(|mut buffers| buffers.pop())
+            .unwrap_or_else(|| AllocBuf::with_capacity_aligned(self.shared.capacity, 1));
+
+        Buffer {
+            buf: BufImpl::from_pool(buf, self.shared.clone()),
+        }
+    }
+
+    /// Wrap `inner` in a `BufReader` using a buffer from the pool.
+    pub fn reader<R>(&self, inner: R) -> BufReader<R> {
+        BufReader::with_buffer(self.buffer(), inner)
+    }
+
+    /// Get the capacity of the buffers this pool hands out.
+    pub fn capacity(&self) -> usize {
+        self.shared.capacity
+    }
+
+    /// Get the number of buffers currently held by the pool.
+    pub fn pooled(&self) -> usize {
+        self.shared.buffers.lock().map(|buffers| buffers.len()).unwrap_or(0)
+    }
+}
+
+impl fmt::Debug for BufferPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("buf_redux::BufferPool")
+            .field("capacity", &self.shared.capacity)
+            .field("max_pooled", &self.shared.max_pooled)
+            .field("pooled", &self.pooled())
+            .finish()
+    }
+}
+
+impl PoolShared {
+    /// Keep `buf` for reuse if there's room for it.
+    pub fn give_back(&self, buf: AllocBuf) {
+        if buf.capacity() != self.capacity { return; }
+
+        if let Ok(mut buffers) = self.buffers.lock() {
+            if buffers.len() < self.max_pooled {
+                buffers.push(buf);
+            }
+        }
+    }
+}
@@ -0,0 +1,147 @@
+// Copyright 2018 Austin Bonander <austin.bonander@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+//! Reading a file at an offset tracked in userspace, set up by `BufReader::positional()`.
+
+use std::borrow::Borrow;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::mem::MaybeUninit;
+
+#[cfg(unix)]
+use std::os::unix::fs::FileExt;
+
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
+
+#[cfg(unix)]
+use std::convert::TryFrom;
+
+#[cfg(windows)]
+use std::os::windows::fs::FileExt;
+
+use trust::TrustRead;
+
+#[cfg(windows)]
+use trust::read_zeroed;
+
+/// A reader over a file which keeps its own offset and reads with positioned reads
+/// (`pread()` on Unix, `ReadFile()` with an offset on Windows), returned by
+/// `BufReader::positional()`.
+///
+/// The file can be anything that borrows a `File`, like `&File` or `Arc<File>`, so several
+/// readers may share one file, even across threads, each at its own position.
+///
+/// On Unix, the cursor of the file is never moved. On Windows, each read moves it to the end of
+/// the data read, but it is never used to determine where to read. Seeking just sets the offset
+/// of this reader; seeking relative to the end queries the length of the file.
+#[derive(Debug)]
+pub struct ReadAt<F = File> {
+    file: F,
+    offset: u64,
+}
+
+impl<F: Borrow<File>> ReadAt<F> {
+    /// Read from `file` starting at `offset`.
+    pub fn new(file: F, offset: u64) -> Self {
+        ReadAt { file, offset }
+    }
+
+    /// Get the offset the next read will start at.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Get a reference to the file.
+    pub fn get_ref(&self) -> &F {
+        &self.file
+    }
+
+    /// Return the file, discarding the offset.
+    pub fn into_inner(self) -> F {
+        self.file
+    }
+
+    #[cfg(unix)]
+    fn read_at(&self, out: &mut [u8]) -> io::Result<usize> {
+        self.file.borrow().read_at(out, self.offset)
+    }
+
+    #[cfg(windows)]
+    fn read_at(&self, out: &mut [u8]) -> io::Result<usize> {
+        self.file.borrow().seek_read(out, self.offset)
+    }
+}
+
+impl<F: Borrow<File>> Read for ReadAt<F> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let read = self.read_at(out)?;
+        self.offset += read as u64;
+        Ok(read)
+    }
+}
+
+impl<F: Borrow<File>> Seek for ReadAt<F> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_offset = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::Current(n) => self.offset.checked_add_signed(n),
+            SeekFrom::End(n) => self.file.borrow().metadata()?.len().checked_add_signed(n),
+        };
+
+        self.offset = new_offset.ok_or_else(|| io::Error::new(
+            io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position"
+        ))?;
+
+        Ok(self.offset)
+    }
+
+    fn stream_position(&mut self) -> io::Result<u64> {
+        Ok(self.offset)
+    }
+}
+
+unsafe impl<F: Borrow<File>> TrustRead for ReadAt<F> {
+    #[cfg(unix)]
+    fn read_uninit(&mut self, out: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+        // `pread()` fails with `EINVAL` for lengths over `isize::MAX` on some platforms
+        let len = ::std::cmp::min(out.len(), isize::MAX as usize);
+        let read = unsafe { pread(self.file.borrow().as_raw_fd(), out, len, self.offset)? };
+
+        if read < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        self.offset += read as u64;
+        Ok(read as usize)
+    }
+
+    #[cfg(windows)]
+    fn read_uninit(&mut self, out: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+        read_zeroed(self, out)
+    }
+}
+
+// `off_t` is 32 bits on 32-bit Linux unless `pread64()` is used, like `FileExt::read_at()` does
+#[cfg(any(target_os = "linux", target_os = "android"))]
+unsafe fn pread(fd: RawFd, out: &mut [MaybeUninit<u8>], len: usize, offset: u64)
+-> io::Result<isize> {
+    let offset = ::libc::off64_t::try_from(offset).map_err(|_| offset_overflow())?;
+    Ok(::libc::pread64(fd, out.as_mut_ptr() as *mut _, len, offset))
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+unsafe fn pread(fd: RawFd, out: &mut [MaybeUninit<u8>], len: usize, offset: u64)
+-> io::Result<isize> {
+    let offset = ::libc::off_t::try_from(offset).map_err(|_| offset_overflow())?;
+    Ok(::libc::pread(fd, out.as_mut_ptr() as *mut _, len, offset))
+}
+
+#[cfg(unix)]
+fn offset_overflow() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "offset too large for the file")
+}
@@ -0,0 +1,3384 @@
+// Copyright 2018 Austin Bonander <austin.bonander@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Tests for the functionality `BufReader` provides beyond its `std::io` counterpart.
+//!
+//! These assume exact capacity allocation, like `std_tests`.
+
+use std::io::prelude::*;
+use std::io;
+
+use BufReader;
+
+#[cfg(any(unix, windows))]
+use ReadAt;
+
+use std_tests::ShortReader;
+
+/// Reader which yields at most `max` bytes from `data` per call to `read()`.
+struct Dribble {
+    data: Vec<u8>,
+    pos: usize,
+    max: usize,
+}
+
+impl Dribble {
+    fn new(data: Vec<u8>, max: usize) -> Self {
+        Dribble { data, pos: 0, max }
+    }
+}
+
+impl Read for Dribble {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let mut data = &self.data[self.pos..];
+        if data.len() > self.max { data = &data[..self.max]; }
+        let read = data.read(out)?;
+        self.pos += read;
+        Ok(read)
+    }
+}
+
+/// Create an anonymous file, e.g. for `BufReader::set_spill()`.
+fn spill_file(name: &str) -> ::std::fs::File {
+    use std::fs::{self, OpenOptions};
+    use std::{env, process};
+
+    let path = env::temp_dir().join(format!("buf_redux-{}-{}", name, process::id()));
+    let file = OpenOptions::new().read(true).write(true).create(true).truncate(true)
+        .open(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    file
+}
+
+/// Writer which accepts at most one byte per call to `write()`.
+struct ShortWriter {
+    written: Vec<u8>,
+}
+
+impl Write for ShortWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written.extend_from_slice(&buf[..buf.len().min(1)]);
+        Ok(buf.len().min(1))
+    }
+
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+#[test]
+fn test_peek() {
+    let inner = ShortReader { lengths: vec![1, 1, 1, 1] };
+    let mut reader = BufReader::with_capacity(2, inner);
+
+    // keeps reading until there's enough data, growing the buffer
+    assert_eq!(reader.peek(3).unwrap().len(), 3);
+    assert!(reader.capacity() >= 3);
+
+    // doesn't consume
+    assert_eq!(reader.fill_buf().unwrap().len(), 3);
+    reader.consume(2);
+
+    // short at EOF
+    assert_eq!(reader.peek(4).unwrap().len(), 2);
+    assert_eq!(reader.peek(1).unwrap().len(), 1);
+}
+
+#[test]
+fn test_peek_make_room() {
+    let inner: &[u8] = &[0, 1, 2, 3, 4, 5];
+    let mut reader = BufReader::with_capacity(4, inner);
+
+    assert_eq!(reader.fill_buf().unwrap(), &[0, 1, 2, 3]);
+    reader.consume(3);
+
+    // moves data down instead of growing
+    assert_eq!(reader.peek(3).unwrap(), &[3, 4, 5]);
+    assert_eq!(reader.capacity(), 4);
+}
+
+#[test]
+fn test_fill_buf_min() {
+    let inner = Dribble::new(vec![1, 2, 3, 4, 5], 2);
+    let mut reader = BufReader::with_capacity(2, inner);
+
+    assert_eq!(&reader.fill_buf_min(3).unwrap()[..3], &[1, 2, 3]);
+    assert!(reader.capacity() >= 3);
+
+    // `min` already satisfied; returns everything buffered, not just `min`
+    reader.consume(1);
+    let buf_len = reader.buf_len();
+    assert_eq!(reader.fill_buf_min(1).unwrap().len(), buf_len);
+
+    // EOF returns the short slice
+    assert_eq!(reader.fill_buf_min(8).unwrap(), &[2, 3, 4, 5]);
+
+    // zero-length reads terminate the loop
+    let mut reader = BufReader::new(ShortReader { lengths: vec![] });
+    assert_eq!(reader.fill_buf_min(1).unwrap(), &[]);
+}
+
+#[test]
+fn test_read_into_buf_returns_bytes_read() {
+    let inner = Dribble::new((0 .. 8).collect(), 3);
+    let mut reader = BufReader::with_capacity(8, inner);
+
+    assert_eq!(reader.read_into_buf().unwrap(), 3);
+    reader.consume(2);
+
+    // returns the bytes read by this call, not the total in the buffer
+    assert_eq!(reader.read_into_buf().unwrap(), 3);
+    assert_eq!(reader.buf_len(), 4);
+    assert_eq!(reader.buffer(), &[2, 3, 4, 5]);
+
+    assert_eq!(reader.read_into_buf().unwrap(), 2);
+    assert_eq!(reader.read_into_buf().unwrap(), 0);
+    assert_eq!(reader.buf_len(), 6);
+}
+
+#[test]
+fn test_unconsume() {
+    let inner: &[u8] = &[0, 1, 2, 3, 4, 5, 6, 7];
+    let mut reader = BufReader::with_capacity(4, inner);
+
+    assert_eq!(reader.fill_buf().unwrap(), &[0, 1, 2, 3]);
+    reader.consume(3);
+    assert_eq!(reader.unconsume(2), 2);
+    assert_eq!(reader.fill_buf().unwrap(), &[1, 2, 3]);
+
+    // saturates at the consumed amount
+    assert_eq!(reader.unconsume(5), 1);
+    assert_eq!(reader.fill_buf().unwrap(), &[0, 1, 2, 3]);
+
+    // still restorable after emptying the buffer
+    reader.consume(4);
+    assert_eq!(reader.unconsume(1), 1);
+    assert_eq!(reader.fill_buf().unwrap(), &[3]);
+
+    // but not after the buffer has been read into again
+    reader.consume(1);
+    assert_eq!(reader.fill_buf().unwrap(), &[4, 5, 6, 7]);
+    assert_eq!(reader.unconsume(1), 0);
+
+    // or after compaction
+    reader.consume(2);
+    reader.make_room();
+    assert_eq!(reader.unconsume(1), 0);
+    assert_eq!(reader.fill_buf().unwrap(), &[6, 7]);
+}
+
+#[test]
+fn test_mark_reset() {
+    let inner = Dribble::new((0 .. 16).collect(), 3);
+    let mut reader = BufReader::with_capacity(4, inner);
+
+    assert_eq!(reader.reset().unwrap_err().kind(), io::ErrorKind::InvalidInput);
+
+    let mut buf = [0; 2];
+    reader.read_exact(&mut buf).unwrap();
+    reader.mark(6);
+    // the buffer is grown to fit the marked bytes
+    assert!(reader.capacity() > 6);
+
+    // read across several refills and a compaction
+    let mut buf = [0; 5];
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, [2, 3, 4, 5, 6]);
+    reader.make_room();
+    let mut buf = [0; 1];
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, [7]);
+
+    reader.reset().unwrap();
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf[..6]).unwrap();
+    assert_eq!(&buf[..6], &[2, 3, 4, 5, 6, 7]);
+
+    // the mark is kept after a reset
+    reader.reset().unwrap();
+
+    // consuming more than the limit invalidates the mark
+    reader.read_exact(&mut buf[..7]).unwrap();
+    assert_eq!(&buf[..7], &[2, 3, 4, 5, 6, 7, 8]);
+    assert!(reader.reset().is_err());
+}
+
+#[test]
+fn test_mark_bypass() {
+    let inner = Dribble::new((0 .. 8).collect(), 2);
+    let mut reader = BufReader::with_capacity(4, inner);
+
+    // a read larger than the buffer doesn't bypass it while a mark is set
+    reader.mark(3);
+    let mut buf = [0; 8];
+    assert_eq!(reader.read(&mut buf).unwrap(), 2);
+    reader.reset().unwrap();
+    assert_eq!(reader.fill_buf().unwrap(), &[0, 1]);
+}
+
+#[test]
+fn test_skip_bytes() {
+    let inner = Dribble::new((0 .. 16).collect(), 3);
+    let mut reader = BufReader::with_capacity(4, inner);
+
+    assert_eq!(reader.fill_buf().unwrap(), &[0, 1, 2]);
+    assert_eq!(reader.skip_bytes(2).unwrap(), 2);
+    assert_eq!(reader.buffer(), &[2]);
+
+    // spans several refills without growing the buffer
+    assert_eq!(reader.skip_bytes(10).unwrap(), 10);
+    assert_eq!(reader.capacity(), 4);
+    assert_eq!(reader.fill_buf().unwrap(), &[12, 13, 14]);
+
+    // short at EOF
+    assert_eq!(reader.skip_bytes(10).unwrap(), 4);
+    assert_eq!(reader.skip_bytes(10).unwrap(), 0);
+}
+
+#[test]
+fn test_seek_forward() {
+    let mut reader = BufReader::with_capacity(4, Dribble::new((0 .. 10).collect(), 3));
+
+    assert_eq!(reader.seek_forward(5).unwrap(), 5);
+    assert_eq!(reader.fill_buf().unwrap(), &[5]);
+    assert_eq!(reader.seek_forward(10).unwrap(), 5);
+}
+
+#[test]
+fn test_skip_bytes_seek() {
+    use std::io::SeekFrom;
+
+    /// Counts the reads and seeks of a cursor.
+    struct CountSeeks {
+        inner: io::Cursor<Vec<u8>>,
+        reads: usize,
+        seeks: usize,
+    }
+
+    impl Read for CountSeeks {
+        fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+            self.reads += 1;
+            self.inner.read(out)
+        }
+    }
+
+    impl Seek for CountSeeks {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.seeks += 1;
+            self.inner.seek(pos)
+        }
+    }
+
+    let inner = CountSeeks { inner: io::Cursor::new((0 .. 64).collect()), reads: 0, seeks: 0 };
+    let mut reader = BufReader::with_capacity(4, inner);
+
+    assert_eq!(reader.fill_buf().unwrap(), &[0, 1, 2, 3]);
+
+    // served from the buffer
+    assert_eq!(reader.skip_bytes_seek(3).unwrap(), 3);
+    assert_eq!(reader.buffer(), &[3]);
+    assert_eq!(reader.get_ref().seeks, 0);
+
+    // one seek past the rest, without reading
+    assert_eq!(reader.skip_bytes_seek(40).unwrap(), 40);
+    assert_eq!(reader.get_ref().seeks, 1);
+    assert_eq!(reader.get_ref().reads, 1);
+    assert_eq!(reader.fill_buf().unwrap(), &[43, 44, 45, 46]);
+
+    assert_eq!(reader.skip_bytes_seek(u64::MAX).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+
+    // reads while marked so the data is kept
+    reader.mark(16);
+    assert_eq!(reader.skip_bytes_seek(6).unwrap(), 6);
+    assert_eq!(reader.get_ref().seeks, 1);
+    reader.reset().unwrap();
+    assert_eq!(reader.fill_buf().unwrap()[0], 43);
+}
+
+#[test]
+fn test_skip_until() {
+    let inner: &[u8] = b"abc\ndefghij\nk";
+    let mut reader = BufReader::with_capacity(4, inner);
+
+    assert_eq!(reader.skip_until(b'\n').unwrap(), (4, true));
+    assert_eq!(reader.fill_buf().unwrap(), b"defg");
+
+    // delimiter past the end of the buffer
+    assert_eq!(reader.skip_until(b'\n').unwrap(), (8, true));
+    assert_eq!(reader.fill_buf().unwrap(), b"k");
+
+    // not found before EOF
+    assert_eq!(reader.skip_until(b'\n').unwrap(), (1, false));
+    assert_eq!(reader.skip_until(b'\n').unwrap(), (0, false));
+}
+
+#[test]
+fn test_read_while() {
+    let inner: &[u8] = b"12345abc   6";
+    let mut reader = BufReader::with_capacity(2, inner);
+    let mut out = Vec::new();
+
+    // across buffer boundaries
+    assert_eq!(reader.read_while(|b| b.is_ascii_digit(), &mut out).unwrap(), 5);
+    assert_eq!(out, b"12345");
+    // the non-matching byte is left in the buffer
+    assert_eq!(reader.fill_buf().unwrap(), b"a");
+    assert_eq!(reader.read_while(|b| b.is_ascii_digit(), &mut out).unwrap(), 0);
+
+    assert_eq!(reader.skip_while(|b| b.is_ascii_alphabetic()).unwrap(), 3);
+    assert_eq!(reader.skip_while(|b| b == b' ').unwrap(), 3);
+
+    // terminates cleanly at EOF
+    out.clear();
+    assert_eq!(reader.read_while(|b| b.is_ascii_digit(), &mut out).unwrap(), 1);
+    assert_eq!(out, b"6");
+    assert_eq!(reader.skip_while(|_| true).unwrap(), 0);
+}
+
+#[test]
+fn test_consume_and_get() {
+    let inner: &[u8] = &[0, 1, 2, 3, 4, 5];
+    let mut reader = BufReader::with_capacity(4, inner);
+
+    // no I/O is performed
+    assert_eq!(reader.consume_and_get(2), &[]);
+
+    reader.fill_buf().unwrap();
+    assert_eq!(reader.consume_and_get(3), &[0, 1, 2]);
+    assert_eq!(reader.buf_len(), 1);
+
+    // over-consume is clamped
+    assert_eq!(reader.consume_and_get(8), &[3]);
+    assert_eq!(reader.fill_buf().unwrap(), &[4, 5]);
+}
+
+#[test]
+fn test_consume_and_get_policy_make_room() {
+    use policy::ReaderPolicy;
+    use Buffer;
+
+    struct MakeRoom;
+
+    impl ReaderPolicy for MakeRoom {
+        fn after_consume(&mut self, buffer: &mut Buffer, _amt: usize) {
+            buffer.make_room();
+        }
+    }
+
+    let inner: &[u8] = &[0, 1, 2, 3, 4, 5];
+    let mut reader = BufReader::with_capacity(4, inner).set_policy(MakeRoom);
+
+    reader.fill_buf().unwrap();
+    assert_eq!(reader.consume_and_get(3), &[0, 1, 2]);
+    reader.consume(1);
+    assert_eq!(reader.fill_buf().unwrap(), &[4, 5]);
+}
+
+#[test]
+fn test_read_exact() {
+    let inner = Dribble::new((0 .. 16).collect(), 3);
+    let mut reader = BufReader::with_capacity(4, inner);
+
+    // served from the buffer
+    reader.fill_buf().unwrap();
+    let mut buf = [0; 2];
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, [0, 1]);
+    assert_eq!(reader.buffer(), &[2]);
+
+    // spans the end of the buffer
+    let mut buf = [0; 3];
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, [2, 3, 4]);
+
+    // larger than the buffer
+    let mut buf = [0; 6];
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, [5, 6, 7, 8, 9, 10]);
+
+    let mut buf = [0; 6];
+    assert_eq!(reader.read_exact(&mut buf).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn test_read_exact_stops_at_eof() {
+    /// Reports EOF once between two chunks, like a terminal.
+    struct EofBetween(Vec<&'static [u8]>);
+
+    impl Read for EofBetween {
+        fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() { return Ok(0); }
+            self.0.remove(0).read(out)
+        }
+    }
+
+    let inner = EofBetween(vec![&[1, 2, 3], &[], &[4, 5, 6, 7, 8, 9]]);
+    let mut reader = BufReader::with_capacity(4, inner);
+
+    // large enough to bypass the buffer
+    let mut buf = [0; 8];
+    assert_eq!(reader.read_exact(&mut buf).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    assert!(reader.is_eof());
+
+    // the data after the EOF is left for the next read
+    assert_eq!(reader.fill_buf().unwrap(), &[4, 5, 6, 7]);
+}
+
+/// Reader which yields `data` in one read, then errors.
+struct ErrAfter(Option<Vec<u8>>);
+
+impl Read for ErrAfter {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        match self.0.take() {
+            Some(data) => (&data[..]).read(out),
+            None => Err(io::Error::new(io::ErrorKind::BrokenPipe, "ErrAfter")),
+        }
+    }
+}
+
+#[test]
+fn test_read_to_end() {
+    let inner: &[u8] = &[0, 1, 2, 3, 4, 5, 6, 7];
+    let mut reader = BufReader::with_capacity(4, inner);
+    let mut out = vec![9];
+
+    // partially consumed buffer
+    reader.fill_buf().unwrap();
+    reader.consume(1);
+    assert_eq!(reader.read_to_end(&mut out).unwrap(), 7);
+    assert_eq!(out, [9, 1, 2, 3, 4, 5, 6, 7]);
+
+    // empty buffer at EOF
+    assert_eq!(reader.read_to_end(&mut out).unwrap(), 0);
+    assert_eq!(out.len(), 8);
+
+    // empty buffer, fresh reader
+    let inner: &[u8] = &[0, 1, 2];
+    let mut reader = BufReader::with_capacity(4, inner);
+    out.clear();
+    assert_eq!(reader.read_to_end(&mut out).unwrap(), 3);
+    assert_eq!(out, [0, 1, 2]);
+
+    // the bytes read before the error are kept
+    let mut reader = BufReader::with_capacity(2, ErrAfter(Some(vec![0, 1, 2])));
+    reader.fill_buf().unwrap();
+    out.clear();
+    assert_eq!(reader.read_to_end(&mut out).unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+    assert_eq!(out, [0, 1]);
+
+    // the buffer is still used while a mark is set
+    let inner: &[u8] = &[0, 1, 2, 3, 4, 5];
+    let mut reader = BufReader::with_capacity(4, inner);
+    reader.mark(8);
+    out.clear();
+    assert_eq!(reader.read_to_end(&mut out).unwrap(), 6);
+    reader.reset().unwrap();
+    out.clear();
+    assert_eq!(reader.read_to_end(&mut out).unwrap(), 6);
+    assert_eq!(out, [0, 1, 2, 3, 4, 5]);
+}
+
+#[test]
+fn test_read_to_string() {
+    // 'é' is two bytes, straddling the end of the buffer
+    let inner = "aé€".as_bytes();
+    let mut reader = BufReader::with_capacity(2, inner);
+    let mut out = String::from("x");
+
+    assert_eq!(reader.fill_buf().unwrap(), &inner[..2]);
+    assert_eq!(reader.read_to_string(&mut out).unwrap(), 6);
+    assert_eq!(out, "xaé€");
+
+    // '€' is three bytes, split after the first
+    let inner = "€€".as_bytes();
+    let mut reader = BufReader::with_capacity(4, inner);
+    reader.fill_buf().unwrap();
+    out.clear();
+    assert_eq!(reader.read_to_string(&mut out).unwrap(), 6);
+    assert_eq!(out, "€€");
+
+    // invalid UTF-8 leaves the string unchanged
+    let inner: &[u8] = &[b'a', 0xE2, 0x82, b'b'];
+    let mut reader = BufReader::with_capacity(2, inner);
+    reader.fill_buf().unwrap();
+    out = String::from("x");
+    assert_eq!(reader.read_to_string(&mut out).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    assert_eq!(out, "x");
+
+    // so does a truncated sequence at EOF
+    let inner: &[u8] = &[b'a', 0xE2, 0x82];
+    let mut reader = BufReader::with_capacity(2, inner);
+    assert!(reader.read_to_string(&mut out).is_err());
+    assert_eq!(out, "x");
+}
+
+#[test]
+fn test_read_vectored() {
+    use std::io::IoSliceMut;
+
+    /// Reader which records whether `read_vectored()` was called on it.
+    struct Vectored<'a> {
+        data: &'a [u8],
+        vectored: bool,
+    }
+
+    impl<'a> Read for Vectored<'a> {
+        fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+            self.data.read(out)
+        }
+
+        fn read_vectored(&mut self, bufs: &mut [IoSliceMut]) -> io::Result<usize> {
+            self.vectored = true;
+            self.data.read_vectored(bufs)
+        }
+    }
+
+    let inner = Vectored { data: &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9], vectored: false };
+    let mut reader = BufReader::with_capacity(4, inner);
+
+    // scatters buffered data
+    let (mut a, mut b) = ([0; 1], [0; 2]);
+    assert_eq!(reader.read_vectored(&mut [IoSliceMut::new(&mut a), IoSliceMut::new(&mut b)])
+                   .unwrap(), 3);
+    assert_eq!((a, b), ([0], [1, 2]));
+    assert!(!reader.get_ref().vectored);
+
+    // doesn't read past the buffered data
+    let (mut a, mut b) = ([0; 2], [0; 2]);
+    assert_eq!(reader.read_vectored(&mut [IoSliceMut::new(&mut a), IoSliceMut::new(&mut b)])
+                   .unwrap(), 1);
+    assert_eq!(a, [3, 0]);
+
+    // bypasses the empty buffer when the total length is large enough
+    let (mut a, mut b) = ([0; 2], [0; 3]);
+    assert_eq!(reader.read_vectored(&mut [IoSliceMut::new(&mut a), IoSliceMut::new(&mut b)])
+                   .unwrap(), 5);
+    assert_eq!((a, b), ([4, 5], [6, 7, 8]));
+    assert!(reader.get_ref().vectored);
+    assert_eq!(reader.buf_len(), 0);
+}
+
+#[test]
+fn test_read_byte() {
+    let inner = Dribble::new(vec![0, 1, 2, 3, 4], 2);
+    let mut reader = BufReader::with_capacity(4, inner);
+
+    assert_eq!(reader.read_byte().unwrap(), Some(0));
+    assert_eq!(reader.buffer(), &[1]);
+
+    // refills across read boundaries
+    let bytes = reader.bytes_buffered().collect::<io::Result<Vec<u8>>>().unwrap();
+    assert_eq!(bytes, [1, 2, 3, 4]);
+
+    assert_eq!(reader.read_byte().unwrap(), None);
+    assert_eq!(reader.bytes_buffered().count(), 0);
+}
+
+#[test]
+fn test_read_array() {
+    let inner = Dribble::new((0 .. 10).collect(), 3);
+    let mut reader = BufReader::with_capacity(4, inner);
+
+    assert_eq!(reader.read_array::<2>().unwrap(), [0, 1]);
+    // refills when not enough is buffered
+    assert_eq!(reader.read_array::<3>().unwrap(), [2, 3, 4]);
+    // grows when larger than the buffer
+    assert_eq!(reader.read_array::<4>().unwrap(), [5, 6, 7, 8]);
+
+    let inner: &[u8] = &[1, 0, 2];
+    let mut reader = BufReader::with_capacity(4, inner);
+    assert_eq!(u16::from_le_bytes(reader.read_array().unwrap()), 1);
+    assert_eq!(reader.read_array::<3>().unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    assert_eq!(reader.read_array::<0>().unwrap(), []);
+    assert_eq!(reader.fill_buf().unwrap(), &[]);
+}
+
+#[cfg(feature = "read-ints")]
+#[test]
+fn test_read_ints() {
+    let mut data = vec![0xFF, 0x01, 0x02, 0x02, 0x01];
+    data.extend_from_slice(&0x0102_0304u32.to_be_bytes());
+    data.extend_from_slice(&(-2i64).to_le_bytes());
+    data.extend_from_slice(&1.5f64.to_be_bytes());
+    data.extend_from_slice(&0.25f32.to_le_bytes());
+    data.push(0);
+
+    // values straddle several refills
+    let mut reader = BufReader::with_capacity(4, Dribble::new(data, 3));
+
+    assert_eq!(reader.read_i8().unwrap(), -1);
+    assert_eq!(reader.read_u16_le().unwrap(), 0x0201);
+    assert_eq!(reader.read_u16_be().unwrap(), 0x0201);
+    assert_eq!(reader.read_u32_be().unwrap(), 0x0102_0304);
+    assert_eq!(reader.read_i64_le().unwrap(), -2);
+    assert_eq!(reader.read_f64_be().unwrap(), 1.5);
+    assert_eq!(reader.read_f32_le().unwrap(), 0.25);
+
+    assert_eq!(reader.read_u32_le().unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    assert_eq!(reader.read_u8().unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn test_varint() {
+    let inner: &[u8] = &[
+        0x00,
+        0xAC, 0x02, // 300
+        0x03, // -2, zig-zag
+        0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01, // u64::MAX
+        0x80, 0x01, // 128
+    ];
+    // encodings straddle refills
+    let mut reader = BufReader::with_capacity(4, Dribble::new(inner.to_vec(), 3));
+
+    assert_eq!(reader.read_varint_u64().unwrap(), 0);
+    assert_eq!(reader.peek_varint().unwrap(), (300, 2));
+    assert_eq!(reader.read_varint_u64().unwrap(), 300);
+    assert_eq!(reader.read_varint_i64().unwrap(), -2);
+    // grows the buffer to fit the longest encoding
+    assert_eq!(reader.read_varint_u64().unwrap(), u64::MAX);
+    assert_eq!(reader.read_varint_u64().unwrap(), 128);
+    assert_eq!(reader.read_varint_u64().unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+
+    // too long
+    let inner: &[u8] = &[0x80; 11];
+    let mut reader = BufReader::new(inner);
+    assert_eq!(reader.read_varint_u64().unwrap_err().kind(), io::ErrorKind::InvalidData);
+    assert_eq!(reader.buf_len(), 11);
+
+    // overflow
+    let inner: &[u8] = &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x02];
+    let mut reader = BufReader::new(inner);
+    assert_eq!(reader.peek_varint().unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+    // truncated
+    let inner: &[u8] = &[0x80, 0x80];
+    let mut reader = BufReader::new(inner);
+    assert_eq!(reader.peek_varint().unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    assert_eq!(reader.buf_len(), 2);
+    assert!(reader.read_varint_u64().is_err());
+    assert_eq!(reader.buf_len(), 0);
+}
+
+#[test]
+fn test_read_frame() {
+    use FramePrefix;
+
+    let inner: &[u8] = &[0, 0, 0, 2, b'a', b'b', 0, 0, 0, 5, b'c', b'd', b'e', b'f', b'g'];
+    let mut reader = BufReader::with_capacity(8, Dribble::new(inner.to_vec(), 3));
+
+    assert_eq!(reader.read_frame(16).unwrap().unwrap(), b"ab");
+    // spans several refills
+    assert_eq!(reader.read_frame(16).unwrap().unwrap(), b"cdefg");
+    assert_eq!(reader.read_frame(16).unwrap(), None);
+
+    // too long; nothing is consumed
+    let inner: &[u8] = &[0, 0, 0, 4, b'a', b'b', b'c', b'd'];
+    let mut reader = BufReader::new(inner);
+    assert_eq!(reader.read_frame(3).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    assert_eq!(reader.read_frame(4).unwrap().unwrap(), b"abcd");
+
+    // other prefixes
+    let inner: &[u8] = &[2, 0, b'a', b'b', 1, b'c', 0];
+    let mut reader = BufReader::new(inner);
+    assert_eq!(reader.read_frame_with(FramePrefix::U16Le, 2).unwrap().unwrap(), b"ab");
+    assert_eq!(reader.read_frame_with(FramePrefix::U8, 2).unwrap().unwrap(), b"c");
+    assert_eq!(reader.read_frame_with(FramePrefix::U8, 2).unwrap().unwrap(), b"");
+
+    // truncated frame
+    let inner: &[u8] = &[0, 0, 0, 4, b'a'];
+    let mut reader = BufReader::new(inner);
+    assert_eq!(reader.read_frame(4).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+
+    // truncated prefix
+    let inner: &[u8] = &[0, 0];
+    let mut reader = BufReader::new(inner);
+    assert_eq!(reader.read_frame(4).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    assert_eq!(reader.buf_len(), 0);
+}
+
+#[test]
+fn test_read_cstr() {
+    let inner: &[u8] = b"abc\0\0defghij\0k\xFFl\0mnop";
+    let mut reader = BufReader::with_capacity(4, inner);
+
+    assert_eq!(reader.read_cstr(3).unwrap(), b"abc");
+    assert_eq!(reader.read_cstr(3).unwrap(), b"");
+    // spans refills
+    assert_eq!(reader.read_cstr(16).unwrap(), b"defghij");
+    assert_eq!(reader.read_cstr_lossy(16).unwrap(), "k\u{FFFD}l");
+
+    // no terminator within `max_len`
+    assert_eq!(reader.read_cstr(2).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    assert_eq!(reader.read_byte().unwrap(), Some(b'o'));
+
+    assert_eq!(reader.read_cstr(16).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn test_consume_if() {
+    let inner = Dribble::new(b"OK 200\nOK".to_vec(), 2);
+    let mut reader = BufReader::with_capacity(2, inner);
+
+    // fills past the buffered bytes
+    assert!(reader.consume_if(b"OK ").unwrap());
+    assert!(!reader.consume_if(b"404").unwrap());
+    assert_eq!(reader.buffer(), b"200");
+
+    reader.expect(b"200\n").unwrap();
+
+    // a partial match at EOF consumes nothing
+    let err = reader.expect(b"OK!").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(err.to_string(), r#"expected "OK!", found "OK""#);
+    assert_eq!(reader.buffer(), b"OK");
+    assert!(reader.consume_if(b"").unwrap());
+}
+
+#[test]
+fn test_read_until() {
+    let data = b"abc\ndefghij\n\nk".to_vec();
+    let mut reader = BufReader::with_capacity(4, Dribble::new(data.clone(), 3));
+    let mut std_reader = io::BufReader::with_capacity(4, Dribble::new(data, 3));
+    let (mut out, mut std_out) = (Vec::new(), Vec::new());
+
+    // identical to the default implementation, including at EOF
+    loop {
+        let read = reader.read_until(b'\n', &mut out).unwrap();
+        assert_eq!(read, std_reader.read_until(b'\n', &mut std_out).unwrap());
+        assert_eq!(out, std_out);
+        if read == 0 { break; }
+    }
+
+    assert_eq!(out, b"abc\ndefghij\n\nk");
+
+    // delimiter not found before EOF
+    let inner: &[u8] = b"abcdef";
+    let mut reader = BufReader::with_capacity(4, inner);
+    out.clear();
+    assert_eq!(reader.read_until(b'\n', &mut out).unwrap(), 6);
+    assert_eq!(out, b"abcdef");
+
+    // empty reader
+    let mut reader = BufReader::new(io::empty());
+    assert_eq!(reader.read_until(b'\n', &mut out).unwrap(), 0);
+    assert_eq!(out, b"abcdef");
+}
+
+#[test]
+fn test_read_until_limited() {
+    use UntilOutcome::*;
+
+    let inner: &[u8] = b"abc\ndefghij\nk";
+    let mut reader = BufReader::with_capacity(4, inner);
+    let mut out = Vec::new();
+
+    // the delimiter counts towards the limit
+    assert_eq!(reader.read_until_limited(b'\n', 4, &mut out).unwrap(), Found(4));
+    assert_eq!(out, b"abc\n");
+
+    // the rest is left unconsumed
+    out.clear();
+    assert_eq!(reader.read_until_limited(b'\n', 6, &mut out).unwrap(), LimitReached(6));
+    assert_eq!(out, b"defghi");
+    assert_eq!(reader.read_until_limited(b'\n', 0, &mut out).unwrap(), LimitReached(0));
+
+    out.clear();
+    assert_eq!(reader.read_until_limited(b'\n', 6, &mut out).unwrap(), Found(2));
+    assert_eq!(reader.read_until_limited(b'\n', 6, &mut out).unwrap(), Eof(1));
+    assert_eq!(out, b"j\nk");
+    assert_eq!(reader.read_until_limited(b'\n', 6, &mut out).unwrap().count(), 0);
+}
+
+#[test]
+fn test_read_line_limited() {
+    use UntilOutcome::*;
+
+    let inner = "ab\nc€d\néf".as_bytes();
+    let mut reader = BufReader::with_capacity(4, Dribble::new(inner.to_vec(), 2));
+    let mut out = String::new();
+
+    assert_eq!(reader.read_line_limited(8, &mut out).unwrap(), Found(3));
+    assert_eq!(out, "ab\n");
+
+    // stops before a character cut by the limit, leaving it unconsumed
+    out.clear();
+    assert_eq!(reader.read_line_limited(3, &mut out).unwrap(), LimitReached(1));
+    assert_eq!(out, "c");
+
+    // characters straddling refills are kept whole
+    assert_eq!(reader.read_line_limited(8, &mut out).unwrap(), Found(5));
+    assert_eq!(out, "c€d\n");
+
+    out.clear();
+    assert_eq!(reader.read_line_limited(8, &mut out).unwrap(), Eof(3));
+    assert_eq!(out, "éf");
+
+    // invalid UTF-8 leaves `out` unchanged
+    let inner: &[u8] = &[b'a', b'b', 0xFF, b'\n'];
+    let mut reader = BufReader::with_capacity(2, inner);
+    assert_eq!(reader.read_line_limited(8, &mut out).unwrap_err().kind(),
+               io::ErrorKind::InvalidData);
+    assert_eq!(out, "éf");
+
+    // as does a character truncated by EOF
+    let inner: &[u8] = &[b'a', 0xE2, 0x82];
+    let mut reader = BufReader::with_capacity(2, inner);
+    assert!(reader.read_line_limited(8, &mut out).is_err());
+    assert_eq!(out, "éf");
+}
+
+#[test]
+fn test_read_until_slice() {
+    let data = b"GET / HTTP/1.1\r\nHost: a\r\n\r\nbody\r\n\r\r\n\r".to_vec();
+    // delimiters straddle reads and compactions
+    let mut reader = BufReader::with_capacity(4, Dribble::new(data, 3));
+    let mut out = Vec::new();
+
+    assert_eq!(reader.read_until_slice(b"\r\n\r\n", &mut out).unwrap(), (27, true));
+    assert_eq!(out, b"GET / HTTP/1.1\r\nHost: a\r\n\r\n");
+    assert_eq!(reader.capacity(), 4);
+
+    // a partial match followed by a full one
+    out.clear();
+    assert_eq!(reader.read_until_slice(b"\r\r\n", &mut out).unwrap(), (9, true));
+    assert_eq!(out, b"body\r\n\r\r\n");
+
+    // delimiter longer than the buffer
+    out.clear();
+    assert_eq!(reader.read_until_slice(b"\r\n\r\n\r\n", &mut out).unwrap(), (1, false));
+    assert_eq!(out, b"\r");
+    assert!(reader.capacity() >= 6);
+    assert_eq!(reader.read_until_slice(b"\r\n", &mut out).unwrap(), (0, false));
+
+    assert_eq!(reader.read_until_slice(b"", &mut out).unwrap_err().kind(),
+               io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_for_each_line() {
+    let inner = Dribble::new(b"ab\ncdefg\r\n\nhij".to_vec(), 3);
+    let mut reader = BufReader::with_capacity(4, inner);
+    let mut lines = Vec::new();
+
+    reader.for_each_line(|line| { lines.push(line.to_vec()); Ok(()) }).unwrap();
+    assert_eq!(lines, [&b"ab"[..], b"cdefg\r", b"", b"hij"]);
+
+    // stops early on errors
+    let inner: &[u8] = b"a\nb\nc\n";
+    let mut reader = BufReader::new(inner);
+    let mut count = 0;
+    let err = reader.for_each_line(|line| {
+        count += 1;
+        if line == b"b" { Err(io::Error::new(io::ErrorKind::InvalidData, "b")) } else { Ok(()) }
+    }).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(count, 2);
+    assert_eq!(reader.buffer(), b"c\n");
+}
+
+#[test]
+fn test_lines_lossy() {
+    let inner: &[u8] = b"ab\xFF\r\n\xE2\x82\nc\xE2\x82\xAC";
+    let reader = BufReader::with_capacity(2, inner);
+
+    let lines = reader.lines_lossy().collect::<io::Result<Vec<_>>>().unwrap();
+    assert_eq!(lines, ["ab\u{FFFD}", "\u{FFFD}", "c€"]);
+}
+
+#[test]
+fn test_for_each_line_crlf() {
+    fn lines(data: &[u8], legacy_mac: bool) -> Vec<Vec<u8>> {
+        let mut reader = BufReader::with_capacity(4, Dribble::new(data.to_vec(), 3));
+        let mut lines = Vec::new();
+        reader.for_each_line_crlf(legacy_mac, |line| { lines.push(line.to_vec()); Ok(()) })
+            .unwrap();
+        lines
+    }
+
+    // terminators straddle reads
+    let data = b"ab\ncd\r\nefg\r\rh\r\n\r\ni\rj\r";
+
+    assert_eq!(lines(data, false), [&b"ab"[..], b"cd", b"efg\r\rh", b"", b"i\rj\r"]);
+    assert_eq!(lines(data, true),
+               [&b"ab"[..], b"cd", b"efg", b"", b"h", b"", b"i", b"j"]);
+
+    // the last line is yielded without a terminator
+    assert_eq!(lines(b"a\r\nb", true), [&b"a"[..], b"b"]);
+}
+
+#[test]
+fn test_for_each_line_crlf_err() {
+    // the `\r\n` straddles reads
+    let mut reader = BufReader::with_capacity(4, Dribble::new(b"ab\r\ncd\r".to_vec(), 3));
+
+    let err = reader.for_each_line_crlf(true, |_| {
+        Err(io::Error::new(io::ErrorKind::InvalidData, "bad line"))
+    });
+    assert_eq!(err.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+    // the `\n` went with the failed line, so it doesn't end an empty one
+    let mut lines = Vec::new();
+    reader.for_each_line_crlf(true, |line| { lines.push(line.to_vec()); Ok(()) }).unwrap();
+    assert_eq!(lines, [b"cd"]);
+}
+
+#[test]
+fn test_strip_bom() {
+    use Bom;
+
+    fn strip(data: &[u8]) -> (Option<Bom>, Vec<u8>) {
+        let mut reader = BufReader::with_capacity(2, Dribble::new(data.to_vec(), 1));
+        let bom = reader.strip_bom().unwrap();
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        (bom, rest)
+    }
+
+    assert_eq!(strip(b"\xEF\xBB\xBFa"), (Some(Bom::Utf8), b"a".to_vec()));
+    assert_eq!(strip(b"\xFF\xFEa\x00"), (Some(Bom::Utf16Le), b"a\x00".to_vec()));
+    assert_eq!(strip(b"\xFE\xFF"), (Some(Bom::Utf16Be), vec![]));
+    assert_eq!(strip(b"\xFF\xFE\x00\x00"), (Some(Bom::Utf32Le), vec![]));
+    assert_eq!(strip(b"\x00\x00\xFE\xFFa"), (Some(Bom::Utf32Be), b"a".to_vec()));
+
+    // nothing is consumed otherwise, even for short streams
+    assert_eq!(strip(b"\xEF\xBB"), (None, b"\xEF\xBB".to_vec()));
+    assert_eq!(strip(b"abcde"), (None, b"abcde".to_vec()));
+    assert_eq!(strip(b""), (None, vec![]));
+}
+
+#[test]
+fn test_read_line_reuse() {
+    let inner: &[u8] = b"ab\r\n\ncdefgh\ni";
+    let mut reader = BufReader::with_capacity(2, inner);
+    let mut buf = String::new();
+
+    assert_eq!(reader.read_line_reuse(&mut buf).unwrap(), Some("ab"));
+    assert_eq!(reader.read_line_reuse(&mut buf).unwrap(), Some(""));
+    assert_eq!(reader.read_line_reuse(&mut buf).unwrap(), Some("cdefgh"));
+    let capacity = buf.capacity();
+    assert_eq!(reader.read_line_reuse(&mut buf).unwrap(), Some("i"));
+    assert_eq!(buf.capacity(), capacity);
+    assert_eq!(reader.read_line_reuse(&mut buf).unwrap(), None);
+    assert_eq!(buf, "");
+}
+
+#[test]
+fn test_split_slice() {
+    fn split(data: &[u8], max: usize) -> Vec<io::Result<Vec<u8>>> {
+        let reader = BufReader::with_capacity(4, Dribble::new(data.to_vec(), 3));
+        reader.split_slice(b"\r\n--".to_vec()).max_segment_len(max).collect()
+    }
+
+    fn ok(segments: Vec<io::Result<Vec<u8>>>) -> Vec<Vec<u8>> {
+        segments.into_iter().map(Result::unwrap).collect()
+    }
+
+    // delimiters straddle reads
+    assert_eq!(ok(split(b"abc\r\n--\r\n--defgh\r\n-\r\n--i", 16)),
+               [&b"abc"[..], b"", b"defgh\r\n-", b"i"]);
+    // the final segment is yielded even if empty
+    assert_eq!(ok(split(b"abc\r\n--", 16)), [&b"abc"[..], b""]);
+    assert_eq!(ok(split(b"", 16)), [&b""[..]]);
+
+    let segments = split(b"ab\r\n--abcdefg\r\n--", 4);
+    assert_eq!(segments.len(), 2);
+    assert_eq!(segments[0].as_ref().unwrap(), b"ab");
+    assert_eq!(segments[1].as_ref().unwrap_err().kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_copy_until() {
+    let inner = Dribble::new(b"abcdefg;hi;;j".to_vec(), 3);
+    let mut reader = BufReader::with_capacity(4, inner);
+    let mut dest = Vec::new();
+
+    // spans refills
+    assert_eq!(reader.copy_until(b';', &mut dest).unwrap(), (7, true));
+    assert_eq!(dest, b"abcdefg");
+
+    // partial writes are retried
+    let mut short = ShortWriter { written: Vec::new() };
+    assert_eq!(reader.copy_until(b';', &mut short).unwrap(), (2, true));
+    assert_eq!(short.written, b"hi");
+
+    assert_eq!(reader.copy_until(b';', &mut dest).unwrap(), (0, true));
+    assert_eq!(reader.copy_until(b';', &mut dest).unwrap(), (1, false));
+    assert_eq!(reader.copy_until(b';', &mut dest).unwrap(), (0, false));
+    assert_eq!(dest, b"abcdefgj");
+}
+
+#[test]
+fn test_copy_exact() {
+    use ShortCopyError;
+
+    let inner = Dribble::new((0 .. 10).collect(), 3);
+    let mut reader = BufReader::with_capacity(4, inner);
+    let mut dest = ShortWriter { written: Vec::new() };
+
+    reader.copy_exact(2, &mut dest).unwrap();
+    assert_eq!(reader.buffer(), &[2]);
+    // spans refills without growing the buffer
+    reader.copy_exact(6, &mut dest).unwrap();
+    assert_eq!(dest.written, [0, 1, 2, 3, 4, 5, 6, 7]);
+    assert_eq!(reader.capacity(), 4);
+    reader.copy_exact(0, &mut dest).unwrap();
+
+    let err = reader.copy_exact(5, &mut dest).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    let short = err.get_ref().unwrap().downcast_ref::<ShortCopyError>().unwrap();
+    assert_eq!((short.copied(), short.expected()), (2, 5));
+    assert_eq!(dest.written.len(), 10);
+}
+
+#[test]
+fn test_drain_buf_to() {
+    /// Writer which accepts `limit` bytes, one per call, then errors.
+    struct Failing {
+        written: Vec<u8>,
+        limit: usize,
+    }
+
+    impl Write for Failing {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.written.len() == self.limit {
+                return Err(io::Error::new(io::ErrorKind::BrokenPipe, "Failing"));
+            }
+
+            self.written.push(buf[0]);
+            Ok(1)
+        }
+
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+
+    let inner: &[u8] = &[0, 1, 2, 3, 4, 5];
+    let mut reader = BufReader::with_capacity(4, inner);
+    let mut dest = Vec::new();
+
+    // doesn't read
+    assert_eq!(reader.drain_buf_to(&mut dest).unwrap(), 0);
+
+    reader.fill_buf().unwrap();
+    reader.consume(1);
+
+    // only the bytes written are consumed
+    let mut failing = Failing { written: Vec::new(), limit: 1 };
+    assert_eq!(reader.drain_buf_to(&mut failing).unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+    assert_eq!(failing.written, [1]);
+
+    assert_eq!(reader.drain_buf_to(&mut dest).unwrap(), 2);
+    assert_eq!(dest, [2, 3]);
+    assert_eq!(reader.buf_len(), 0);
+
+    // still usable afterwards
+    assert_eq!(reader.fill_buf().unwrap(), &[4, 5]);
+}
+
+#[test]
+fn test_find_byte() {
+    let inner = Dribble::new(b"abc\ndefghij\nk".to_vec(), 3);
+    let mut reader = BufReader::with_capacity(4, inner);
+
+    assert_eq!(reader.find_byte(b'\n'), None);
+    assert_eq!(reader.find_byte_filled(b'\n', 8).unwrap(), Some(3));
+    assert_eq!(reader.find_byte(b'\n'), Some(3));
+    reader.consume(4);
+
+    // bounded by `max_fill`
+    assert_eq!(reader.find_byte_filled(b'\n', 7).unwrap(), None);
+    assert_eq!(reader.buffer(), b"defghij");
+    assert_eq!(reader.find_byte_filled(b'\n', 8).unwrap(), Some(7));
+    reader.consume(8);
+
+    // at EOF
+    assert_eq!(reader.find_byte_filled(b'\n', 8).unwrap(), None);
+    assert_eq!(reader.buffer(), b"k");
+}
+
+#[test]
+fn test_find_byte_filled_growth() {
+    /// Counts calls to `read()`.
+    struct CountReads<'a>(&'a [u8], usize);
+
+    impl<'a> Read for CountReads<'a> {
+        fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+            self.1 += 1;
+            self.0.read(out)
+        }
+    }
+
+    let mut data = vec![b'a'; 100_000];
+    data.push(b'\n');
+
+    // the buffer grows geometrically rather than a byte at a time
+    let mut reader = BufReader::with_capacity(16, CountReads(&data, 0));
+    assert_eq!(reader.find_byte_filled(b'\n', 1 << 20).unwrap(), Some(100_000));
+    assert!(reader.get_ref().1 < 20, "{} reads", reader.get_ref().1);
+
+    // but not past `max_fill`
+    let mut reader = BufReader::with_capacity(16, CountReads(&data, 0));
+    assert_eq!(reader.find_byte_filled(b'\n', 1000).unwrap(), None);
+    assert_eq!(reader.buf_len(), 1000);
+    assert!(reader.capacity() < 2000);
+}
+
+#[test]
+fn test_starts_with() {
+    let inner = Dribble::new(b"%PDF-1.4".to_vec(), 2);
+    let mut reader = BufReader::with_capacity(2, inner);
+
+    assert!(reader.starts_with(b"%PDF-").unwrap());
+    assert!(!reader.starts_with(b"PK").unwrap());
+    // shorter than the prefix
+    assert!(!reader.starts_with(b"%PDF-1.4 and then some").unwrap());
+    assert!(reader.starts_with(b"").unwrap());
+
+    let candidates: &[&[u8]] = &[b"\x1F\x8B", b"%PDF-1.5", b"%PDF"];
+    assert_eq!(reader.detect(candidates).unwrap(), Some(&b"%PDF"[..]));
+    assert_eq!(reader.detect(&candidates[..2]).unwrap(), None);
+    assert_eq!(reader.detect(&[]).unwrap(), None);
+
+    // nothing was consumed
+    assert_eq!(reader.buffer(), b"%PDF-1.4");
+}
+
+#[test]
+fn test_for_each_chunk() {
+    let inner = Dribble::new((0 .. 11).collect(), 3);
+    let mut reader = BufReader::with_capacity(4, inner);
+    let mut chunks = Vec::new();
+
+    // grows the buffer for chunks larger than it
+    reader.for_each_chunk(5, |chunk, partial| { chunks.push((chunk.to_vec(), partial)); Ok(()) })
+        .unwrap();
+    assert_eq!(chunks, [(vec![0, 1, 2, 3, 4], false), (vec![5, 6, 7, 8, 9], false),
+                        (vec![10], true)]);
+
+    // stops early on errors
+    let inner: &[u8] = &[0, 1, 2, 3];
+    let mut reader = BufReader::new(inner);
+    let err = reader.for_each_chunk(2, |_, _| Err(io::Error::new(io::ErrorKind::InvalidData, "")))
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(reader.buffer(), &[2, 3]);
+
+    assert_eq!(reader.for_each_chunk(0, |_, _| Ok(())).unwrap_err().kind(),
+               io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_split_to() {
+    let inner = Dribble::new((0 .. 10).collect(), 3);
+    let mut reader = BufReader::with_capacity(4, inner);
+
+    // doesn't read
+    assert_eq!(reader.split_to(2), []);
+
+    reader.fill_buf().unwrap();
+    assert_eq!(reader.split_to(2), [0, 1]);
+    assert_eq!(reader.split_to(2), [2]);
+
+    assert_eq!(reader.split_to_exact(5).unwrap(), [3, 4, 5, 6, 7]);
+    assert_eq!(reader.split_to_exact(3).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn test_tee() {
+    let inner = Dribble::new((0 .. 16).collect(), 3);
+    let mut tee = BufReader::with_capacity(4, inner).tee(Vec::new());
+
+    // peeking doesn't write
+    assert_eq!(tee.fill_buf().unwrap(), &[0, 1, 2]);
+    assert_eq!(tee.sink(), &[]);
+
+    tee.consume(2);
+    assert_eq!(tee.sink(), &[0, 1]);
+
+    let mut buf = [0; 4];
+    assert_eq!(tee.read(&mut buf).unwrap(), 1);
+    // bypasses the buffer
+    assert_eq!(tee.read(&mut buf).unwrap(), 3);
+    assert_eq!(tee.get_ref().buf_len(), 0);
+    assert_eq!(tee.sink(), &[0, 1, 2, 3, 4, 5]);
+
+    let mut rest = Vec::new();
+    tee.read_until(10, &mut rest).unwrap();
+    assert_eq!(tee.sink(), &(0 .. 11).collect::<Vec<u8>>()[..]);
+
+    let (mut reader, sink) = tee.into_inner();
+    assert_eq!(sink.len(), 11);
+    assert_eq!(reader.fill_buf().unwrap(), &[11]);
+}
+
+#[test]
+fn test_tee_consume_err() {
+    let inner: &[u8] = &[0, 1, 2, 3];
+    let mut sink = [0u8; 1];
+    let mut tee = BufReader::new(inner).tee(&mut sink[..]);
+
+    // the error is returned by the next call that can
+    tee.fill_buf().unwrap();
+    tee.consume(2);
+    assert_eq!(tee.fill_buf().unwrap_err().kind(), io::ErrorKind::WriteZero);
+    assert_eq!(tee.fill_buf().unwrap(), &[2, 3]);
+}
+
+#[test]
+fn test_tee_read_err() {
+    let inner: &[u8] = &[0, 1, 2, 3];
+    let mut sink = [0u8; 1];
+    let mut tee = BufReader::new(inner).tee(&mut sink[..]);
+
+    // the bytes are returned even though the sink is full
+    let mut out = [0u8; 2];
+    assert_eq!(tee.read(&mut out).unwrap(), 2);
+    assert_eq!(out, [0, 1]);
+
+    assert_eq!(tee.read(&mut out).unwrap_err().kind(), io::ErrorKind::WriteZero);
+    assert_eq!(tee.get_ref().buffer(), &[2, 3]);
+}
+
+#[test]
+fn test_observe_reads() {
+    use std::io::IoSliceMut;
+
+    let mut observed = Vec::new();
+
+    {
+        let inner = Dribble::new((0 .. 16).collect(), 3);
+        let mut reader = BufReader::with_capacity(4, inner)
+            .observe_reads(|bytes| observed.extend_from_slice(bytes));
+
+        // seen before being consumed
+        reader.fill_buf().unwrap();
+        reader.consume(1);
+
+        let mut buf = [0; 8];
+        // from the buffer, then bypassing it
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert_eq!(reader.read(&mut buf).unwrap(), 3);
+
+        // `Dribble` only reads into the first slice
+        let (mut a, mut b) = ([0; 2], [0; 2]);
+        assert_eq!(reader.read_vectored(&mut [IoSliceMut::new(&mut a), IoSliceMut::new(&mut b)])
+                       .unwrap(), 2);
+
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+    }
+
+    assert_eq!(observed, (0 .. 16).collect::<Vec<u8>>());
+}
+
+#[test]
+fn test_take_buffered() {
+    let inner = Dribble::new((0 .. 16).collect(), 3);
+    let mut reader = BufReader::with_capacity(4, inner);
+
+    {
+        let mut take = reader.take_buffered(5);
+        // clamped to the limit
+        assert_eq!(take.fill_buf().unwrap(), &[0, 1, 2]);
+        take.consume(3);
+        assert_eq!(take.fill_buf().unwrap(), &[3, 4]);
+        assert_eq!(take.buffer(), &[3, 4]);
+        assert_eq!(take.limit(), 2);
+
+        let mut rest = Vec::new();
+        assert_eq!(take.read_to_end(&mut rest).unwrap(), 2);
+        assert_eq!(rest, [3, 4]);
+        assert_eq!(take.fill_buf().unwrap(), &[]);
+    }
+
+    // the over-buffered bytes are still available
+    assert_eq!(reader.buffer(), &[5]);
+
+    // reads larger than the limit are clamped
+    let mut buf = [0; 8];
+    reader.consume(1);
+    assert_eq!(reader.take_buffered(2).read(&mut buf).unwrap(), 2);
+    assert_eq!(&buf[..2], &[6, 7]);
+    assert_eq!(reader.fill_buf().unwrap(), &[8]);
+}
+
+#[test]
+fn test_take_buffered_exhausted() {
+    /// Panics if read past the first refill.
+    struct ReadOnce(bool);
+
+    impl Read for ReadOnce {
+        fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+            assert!(!self.0, "read after the limit was reached");
+            self.0 = true;
+            (&[1u8, 2, 3][..]).read(out)
+        }
+    }
+
+    let mut reader = BufReader::with_capacity(4, ReadOnce(false));
+    let mut take = reader.take_buffered(3);
+
+    let mut data = Vec::new();
+    assert_eq!(take.read_to_end(&mut data).unwrap(), 3);
+    assert_eq!(data, [1, 2, 3]);
+
+    // the buffer is empty, but the underlying reader isn't read again
+    assert_eq!(take.read(&mut [0; 4]).unwrap(), 0);
+    assert_eq!(take.read_to_end(&mut data).unwrap(), 0);
+}
+
+#[test]
+fn test_shrink() {
+    let inner: &[u8] = &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+    let mut reader = BufReader::with_capacity(8, inner);
+
+    // no-op
+    reader.shrink(16);
+    assert_eq!(reader.capacity(), 8);
+
+    assert_eq!(reader.fill_buf().unwrap(), &[0, 1, 2, 3, 4, 5, 6, 7]);
+    reader.consume(3);
+
+    // never discards live data
+    reader.shrink(2);
+    assert_eq!(reader.capacity(), 5);
+    assert_eq!(reader.buffer(), &[3, 4, 5, 6, 7]);
+
+    reader.consume(5);
+    reader.shrink(2);
+    assert_eq!(reader.capacity(), 2);
+    assert_eq!(reader.fill_buf().unwrap(), &[8, 9]);
+}
+
+#[test]
+fn test_shrink_to_fit() {
+    let inner = Dribble::new((0 .. 16).collect(), 3);
+    let mut reader = BufReader::with_capacity(8, inner);
+
+    reader.fill_buf().unwrap();
+    reader.consume(1);
+    reader.shrink_to_fit();
+    assert_eq!(reader.capacity(), 2);
+    assert_eq!(reader.buffer(), &[1, 2]);
+
+    // the capacity is restored before reading again
+    reader.consume(2);
+    assert_eq!(reader.fill_buf().unwrap(), &[3, 4, 5]);
+    assert!(reader.capacity() >= 8);
+
+    // even from empty
+    reader.consume(3);
+    reader.shrink_to_fit();
+    assert_eq!(reader.capacity(), 0);
+    assert_eq!(reader.fill_buf().unwrap(), &[6, 7, 8]);
+    assert!(reader.capacity() >= 8);
+
+    // changing the capacity in the meantime cancels this
+    reader.consume(3);
+    reader.shrink_to_fit();
+    reader.reserve(4);
+    assert_eq!(reader.fill_buf().unwrap(), &[9, 10, 11]);
+    assert!(reader.capacity() < 8);
+}
+
+#[test]
+fn test_auto_shrink() {
+    let inner = Dribble::new(vec![0; 64], 2);
+    let mut reader = BufReader::with_capacity(32, inner);
+
+    reader.set_auto_shrink(4, 2);
+
+    // each read leaves 2 bytes buffered
+    reader.fill_buf().unwrap();
+    assert_eq!(reader.capacity(), 32);
+    reader.consume(2);
+    reader.fill_buf().unwrap();
+    assert_eq!(reader.capacity(), 16);
+
+    // a read filling the buffer past the threshold resets the count
+    reader.fill_buf_min(4).unwrap();
+    reader.consume(4);
+    reader.fill_buf().unwrap();
+    assert_eq!(reader.capacity(), 16);
+    assert_eq!(reader.buffer(), &[0, 0]);
+    reader.consume(2);
+
+    // never below the threshold
+    for _ in 0 .. 8 {
+        reader.fill_buf().unwrap();
+        reader.consume(2);
+    }
+
+    assert_eq!(reader.capacity(), 4);
+
+    reader.set_auto_shrink(0, 0);
+}
+
+#[test]
+fn test_max_capacity() {
+    let mut reader = BufReader::with_capacity(64, &[1u8; 64][..]);
+
+    // clamps the existing capacity
+    reader.set_max_capacity(16);
+    assert_eq!(reader.capacity(), 16);
+    assert_eq!(reader.max_capacity(), Some(16));
+
+    assert_eq!(reader.fill_buf_min(16).unwrap(), &[1; 16][..]);
+
+    reader.consume(8);
+    assert_eq!(reader.peek(16).unwrap(), &[1; 16][..]);
+
+    let err = reader.peek(17).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::OutOfMemory);
+    assert_eq!(reader.buf_len(), 16);
+
+    reader.reserve(64);
+    assert_eq!(reader.capacity(), 16);
+
+    // the mark limit is reduced to fit
+    reader.mark(32);
+    reader.consume(15);
+    reader.reset().unwrap();
+    reader.consume(16);
+    assert!(reader.reset().is_err());
+}
+
+#[test]
+fn test_read_into_buf_trusted() {
+    let mut reader = BufReader::with_capacity(4, io::Cursor::new(vec![1, 2, 3, 4, 5, 6]));
+
+    assert_eq!(reader.read_into_buf_trusted().unwrap(), 4);
+    assert_eq!(reader.buffer(), &[1, 2, 3, 4]);
+    reader.consume(3);
+
+    // mixing with the untrusted path; the full tail is reclaimed first
+    assert_eq!(reader.read_into_buf().unwrap(), 2);
+    assert_eq!(reader.buffer(), &[4, 5, 6]);
+
+    reader.consume(3);
+    assert_eq!(reader.read_into_buf_trusted().unwrap(), 0);
+    assert_eq!(reader.capacity(), 4);
+}
+
+#[test]
+fn test_set_trusted_reads() {
+    let mut reader = BufReader::with_capacity(4, io::Cursor::new(vec![1, 2, 3, 4, 5, 6]));
+    reader.set_trusted_reads(true);
+
+    assert_eq!(reader.fill_buf().unwrap(), &[1, 2, 3, 4]);
+    reader.consume(3);
+
+    let mut out = [0; 3];
+    assert_eq!(reader.read(&mut out).unwrap(), 1);
+    assert_eq!(reader.read(&mut out).unwrap(), 2);
+    assert_eq!(out[..2], [5, 6]);
+    assert_eq!(reader.fill_buf().unwrap(), &[]);
+
+    let mut reader = BufReader::with_capacity(4, io::repeat(7).take(6));
+    reader.set_trusted_reads(true);
+
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data).unwrap();
+    assert_eq!(data, [7; 6]);
+
+    // the slower path again
+    let mut reader = BufReader::with_capacity(4, &[1u8, 2, 3, 4, 5][..]);
+    reader.set_trusted_reads(true);
+    reader.set_trusted_reads(false);
+    assert_eq!(reader.fill_buf().unwrap(), &[1, 2, 3, 4]);
+}
+
+#[test]
+fn test_set_trusted_reads_file() {
+    use std::io::SeekFrom;
+
+    let mut file = spill_file("trusted-reads");
+    file.write_all(&(0 .. 20).collect::<Vec<u8>>()).unwrap();
+    file.seek(SeekFrom::Start(2)).unwrap();
+
+    let mut reader = BufReader::with_capacity(8, file);
+    reader.set_trusted_reads(true);
+    assert_eq!(reader.fill_buf().unwrap(), &[2, 3, 4, 5, 6, 7, 8, 9]);
+    reader.consume(8);
+
+    // spilled data is read back from its file the same way
+    reader.set_spill(spill_file("trusted-reads-spill"), 4).unwrap();
+    reader.read_ahead(6).unwrap();
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data).unwrap();
+    assert_eq!(data, (10 .. 20).collect::<Vec<u8>>());
+
+    #[cfg(any(unix, windows))] {
+        let file = reader.into_inner();
+        let mut reader = BufReader::with_capacity(8, ReadAt::new(&file, 15));
+        reader.set_trusted_reads(true);
+        assert_eq!(reader.fill_buf().unwrap(), &[15, 16, 17, 18, 19]);
+        reader.consume(5);
+        assert_eq!(reader.fill_buf().unwrap(), &[]);
+        assert_eq!(reader.get_ref().offset(), 20);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_set_trusted_reads_offset_overflow() {
+    let file = spill_file("trusted-reads-overflow");
+    let mut reader = BufReader::with_capacity(8, ReadAt::new(&file, u64::MAX));
+    reader.set_trusted_reads(true);
+
+    // not truncated to some offset which does fit
+    assert_eq!(reader.fill_buf().unwrap_err().kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_ensure_capacity() {
+    let mut reader = BufReader::with_capacity(4, &[1u8, 2, 3, 4, 5, 6][..]);
+    reader.fill_buf().unwrap();
+    reader.consume(1);
+
+    reader.ensure_capacity(16);
+    let cap = reader.capacity();
+    let ptr = reader.buffer().as_ptr();
+    assert!((16 .. 32).contains(&cap), "capacity: {}", cap);
+    assert_eq!(reader.buffer(), &[2, 3, 4]);
+
+    // doesn't depend on the buffered data, so no reallocation
+    for _ in 0 .. 4 {
+        reader.ensure_capacity(16);
+        reader.ensure_capacity(8);
+    }
+
+    assert_eq!(reader.capacity(), cap);
+    assert_eq!(reader.buffer().as_ptr(), ptr);
+
+    reader.set_max_capacity(cap);
+    reader.ensure_capacity(cap * 2);
+    assert_eq!(reader.capacity(), cap);
+}
+
+#[test]
+fn test_move_threshold() {
+    // returns at most 2 bytes per read
+    struct Chunked(Vec<u8>);
+
+    impl Read for Chunked {
+        fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+            let len = ::std::cmp::min(::std::cmp::min(out.len(), 2), self.0.len());
+            out[..len].copy_from_slice(&self.0[..len]);
+            self.0.drain(..len);
+            Ok(len)
+        }
+    }
+
+    // 6 bytes buffered of 8, with `consume` of them consumed
+    let reader = |consume, threshold| {
+        let mut reader = BufReader::with_capacity(8, Chunked((0 .. 16).collect()));
+        reader.set_move_threshold(threshold);
+
+        for _ in 0 .. 3 {
+            reader.read_into_buf().unwrap();
+        }
+
+        reader.consume(consume);
+        reader
+    };
+
+    // no proactive compaction by default
+    let mut default = reader(4, 0);
+    assert_eq!(default.read_into_buf().unwrap(), 2);
+    assert_eq!(default.consumed(), 4);
+    assert_eq!(default.buffer(), &[4, 5, 6, 7]);
+
+    // no more than the threshold remains
+    let mut small = reader(4, 2);
+    assert_eq!(small.read_into_buf().unwrap(), 2);
+    assert_eq!(small.consumed(), 0);
+    assert_eq!(small.buffer(), &[4, 5, 6, 7]);
+
+    // more than the threshold remains
+    let mut large = reader(3, 2);
+    assert_eq!(large.read_into_buf().unwrap(), 2);
+    assert_eq!(large.consumed(), 3);
+    assert_eq!(large.buffer(), &[3, 4, 5, 6, 7]);
+}
+
+#[test]
+fn test_fill_buf_full_partially_consumed() {
+    // always wants more data, even with a full buffer
+    use policy::{DoRead, ReaderPolicy};
+    use Buffer;
+
+    struct AlwaysRead;
+
+    impl ReaderPolicy for AlwaysRead {
+        fn before_read(&mut self, _: &mut Buffer) -> DoRead { DoRead(true) }
+    }
+
+    // the delimiter is just past the first buffer's worth of data
+    let data = b"abcdefgh\nij";
+    let mut reader = BufReader::with_capacity(8, &data[..]).set_policy(AlwaysRead);
+
+    assert_eq!(reader.fill_buf().unwrap(), b"abcdefgh");
+    reader.consume(4);
+
+    // the consumed bytes are reclaimed to read more
+    assert_eq!(reader.fill_buf().unwrap(), b"efgh\nij");
+
+    let mut reader = BufReader::with_capacity(8, &data[..]).set_policy(AlwaysRead);
+    reader.fill_buf().unwrap();
+    reader.consume(1);
+
+    let mut line = Vec::new();
+    assert_eq!(reader.read_until(b'\n', &mut line).unwrap(), 8);
+    assert_eq!(line, b"bcdefgh\n");
+    assert_eq!(reader.fill_buf().unwrap(), b"ij");
+}
+
+#[test]
+fn test_with_capacity_aligned() {
+    // checks the buffer it reads into like `O_DIRECT` would
+    struct AlignedReader<'a>(&'a [u8]);
+
+    impl<'a> Read for AlignedReader<'a> {
+        fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+            assert_eq!(out.as_ptr() as usize % 512, 0, "unaligned address");
+            assert_eq!(out.len() % 512, 0, "unaligned length");
+            // short reads leave the tail unaligned
+            let len = ::std::cmp::min(out.len(), 700);
+            self.0.read(&mut out[..len])
+        }
+    }
+
+    let data = (0 .. 4096).map(|i| i as u8).collect::<Vec<u8>>();
+    let mut reader = BufReader::with_capacity_aligned(1000, 512, AlignedReader(&data));
+    assert_eq!(reader.capacity(), 1024);
+
+    let mut out = Vec::new();
+
+    while !reader.fill_buf_min(900).unwrap().is_empty() {
+        let amt = ::std::cmp::min(reader.buf_len(), 300);
+        out.extend_from_slice(&reader.buffer()[..amt]);
+        reader.consume(amt);
+    }
+
+    assert_eq!(out, data);
+
+    // grown to fit the data before the aligned tail
+    let (_, buf) = reader.into_inner_with_buffer();
+    assert_eq!(buf.capacity() % 512, 0);
+}
+
+#[test]
+fn test_buffer_with_storage() {
+    use std::sync::{Arc, Mutex};
+
+    use {Buffer, BufStorage};
+
+    // records its capacity when dropped, like a pool reclaiming it
+    struct Pooled(Vec<u8>, Arc<Mutex<Option<usize>>>);
+
+    impl BufStorage for Pooled {
+        fn as_slice(&self) -> &[u8] { &self.0 }
+        fn as_mut_slice(&mut self) -> &mut [u8] { &mut self.0 }
+        fn reserve(&mut self, additional: usize) { let len = self.0.len(); self.0.resize(len + additional, 0); }
+    }
+
+    impl Drop for Pooled {
+        fn drop(&mut self) {
+            *self.1.lock().unwrap() = Some(self.0.len());
+        }
+    }
+
+    let returned = Arc::new(Mutex::new(None));
+    let storage = Pooled(vec![0; 4], returned.clone());
+
+    let data = (0 .. 10).collect::<Vec<u8>>();
+    let mut reader = BufReader::with_buffer(Buffer::with_storage(storage), &data[..]);
+    assert_eq!(reader.capacity(), 4);
+    assert_eq!(reader.fill_buf().unwrap(), &[0, 1, 2, 3]);
+    reader.consume(3);
+
+    reader.make_room();
+    assert_eq!(reader.read_into_buf().unwrap(), 3);
+    assert_eq!(reader.buffer(), &[3, 4, 5, 6]);
+
+    drop(reader);
+    assert_eq!(*returned.lock().unwrap(), Some(4));
+
+    // growing goes through the storage
+    let mut reader = BufReader::with_buffer(Buffer::with_storage(vec![0u8; 4]), &data[..]);
+    assert_eq!(reader.fill_buf_min(6).unwrap(), &data[..6]);
+    assert!(reader.capacity() >= 6);
+    reader.shrink_to_fit();
+    assert_eq!(reader.capacity(), 6);
+}
+
+#[cfg(feature = "secure")]
+#[test]
+fn test_zeroize_on_drop() {
+    use std::sync::{Arc, Mutex};
+
+    use {Buffer, BufStorage};
+
+    // records its contents when shrunk or dropped
+    struct Recorded(Vec<u8>, Arc<Mutex<Vec<u8>>>);
+
+    impl BufStorage for Recorded {
+        fn as_slice(&self) -> &[u8] { &self.0 }
+        fn as_mut_slice(&mut self) -> &mut [u8] { &mut self.0 }
+        fn reserve(&mut self, additional: usize) { let len = self.0.len(); self.0.resize(len + additional, 0); }
+        fn shrink(&mut self, new_cap: usize) {
+            *self.1.lock().unwrap() = self.0.clone();
+            self.0.truncate(new_cap);
+        }
+    }
+
+    impl Drop for Recorded {
+        fn drop(&mut self) {
+            *self.1.lock().unwrap() = self.0.clone();
+        }
+    }
+
+    let recorded = Arc::new(Mutex::new(Vec::new()));
+    let mut buf = Buffer::with_storage(Recorded(vec![0; 8], recorded.clone()));
+    buf.set_zeroize_on_drop(true);
+
+    let mut reader = BufReader::with_buffer(buf, &b"abcdefgh"[..]);
+    assert_eq!(reader.fill_buf().unwrap(), b"abcdefgh");
+    reader.consume(6);
+
+    // the old location of the moved data is wiped, but not the consumed bytes
+    reader.make_room();
+    reader.shrink_to_fit();
+    assert_eq!(*recorded.lock().unwrap(), b"ghcdef\0\0");
+    assert_eq!(reader.buffer(), b"gh");
+
+    drop(reader);
+    assert_eq!(*recorded.lock().unwrap(), [0, 0]);
+
+    // not wiped unless enabled
+    let mut reader = BufReader::with_buffer(Buffer::with_storage(Recorded(vec![0; 4], recorded.clone())),
+                                            &b"abcd"[..]);
+    reader.fill_buf().unwrap();
+    drop(reader);
+    assert_eq!(*recorded.lock().unwrap(), b"abcd");
+
+    // the setting carries over to a restored buffer
+    let mut reader = BufReader::new_secure(&b"abcd"[..]);
+    reader.restore_buf(vec![1, 2, 3]);
+    assert_eq!(reader.buffer(), &[1, 2, 3]);
+    let (_, buf) = reader.into_inner_with_buffer();
+    assert!(buf.buf.zeroize());
+
+    // reallocating copies the data out before wiping the old memory
+    let mut bufs = vec![Buffer::with_capacity(16)];
+    #[cfg(feature = "slice-deque")]
+    bufs.push(Buffer::with_capacity_ringbuf(16));
+
+    for mut buf in bufs {
+        buf.set_zeroize_on_drop(true);
+        buf.copy_from_slice(b"hello");
+        buf.consume(1);
+
+        let cap = buf.capacity();
+        buf.reserve(cap * 2);
+        assert!(buf.capacity() > cap);
+        assert_eq!(buf.buf(), b"ello");
+
+        buf.shrink(0);
+        assert_eq!(buf.buf(), b"ello");
+    }
+}
+
+#[test]
+fn test_buffer_pool() {
+    use std::thread;
+
+    use BufferPool;
+
+    let pool = BufferPool::new(16, 2);
+
+    let mut reader = pool.reader(&b"abc"[..]);
+    assert_eq!(reader.capacity(), 16);
+    let ptr = reader.fill_buf().unwrap().as_ptr();
+    assert_eq!(reader.into_inner(), &b""[..]);
+    assert_eq!(pool.pooled(), 1);
+
+    // the same allocation is reused
+    let mut reader = pool.reader(&b"def"[..]);
+    assert_eq!(reader.fill_buf().unwrap(), b"def");
+    assert_eq!(reader.buffer().as_ptr(), ptr);
+    assert_eq!(pool.pooled(), 0);
+
+    // detached
+    let (_, buf) = reader.into_inner_with_buffer();
+    drop(buf);
+    assert_eq!(pool.pooled(), 0);
+
+    // bounded
+    let readers = (0 .. 4).map(|_| pool.reader(io::empty())).collect::<Vec<_>>();
+    drop(readers);
+    assert_eq!(pool.pooled(), 2);
+
+    // resized buffers are not kept
+    let pool = BufferPool::new(16, 2);
+    let mut reader = pool.reader(io::empty());
+    reader.reserve(32);
+    drop(reader);
+    assert_eq!(pool.pooled(), 0);
+
+    let threads = (0 .. 4).map(|_| {
+        let pool = pool.clone();
+        thread::spawn(move || {
+            let mut reader = pool.reader(&b"thread"[..]);
+            assert_eq!(reader.fill_buf().unwrap(), b"thread");
+        })
+    }).collect::<Vec<_>>();
+
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    assert!(pool.pooled() > 0 && pool.pooled() <= 2);
+}
+
+#[test]
+fn test_detach_restore_buf() {
+    let data = (0 .. 32).collect::<Vec<u8>>();
+    let mut reader = BufReader::with_capacity(8, &data[..]);
+
+    reader.fill_buf().unwrap();
+    reader.consume(3);
+
+    let mut buf = reader.detach_buf();
+    assert_eq!(buf, [3, 4, 5, 6, 7]);
+    assert_eq!(buf.capacity(), 8);
+    assert_eq!(reader.buf_len(), 0);
+
+    // the caller can change the data
+    buf.retain(|&b| b % 2 == 0);
+    reader.restore_buf(buf);
+    assert_eq!(reader.buffer(), &[4, 6]);
+    reader.consume(1);
+
+    let mut out = [0; 4];
+    reader.read_exact(&mut out).unwrap();
+    assert_eq!(out, [6, 8, 9, 10]);
+
+    assert_eq!(reader.buffer(), &data[11 .. 16]);
+    reader.consume(5);
+
+    // nothing buffered
+    assert_eq!(reader.detach_buf(), []);
+    assert_eq!(reader.capacity(), 0);
+
+    // reallocated with the previous capacity
+    assert_eq!(reader.fill_buf().unwrap(), &data[16 .. 24]);
+
+    let buf = reader.detach_buf();
+    reader.restore_buf(buf);
+
+    let mut rest = Vec::new();
+    reader.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, &data[16 ..]);
+}
+
+#[test]
+fn test_swap_buffers() {
+    use policy::MinBuffered;
+
+    let mut reader = BufReader::with_capacity(4, &b"abcdef"[..]);
+    let mut other = BufReader::with_capacity(8, io::repeat(b'x')).set_policy(MinBuffered(2));
+
+    assert_eq!(reader.fill_buf().unwrap(), b"abcd");
+    reader.consume(1);
+    reader.mark(2);
+    reader.consume(1);
+
+    reader.swap_buffers(&mut other);
+    assert!(reader.reset().is_err());
+    assert_eq!(reader.capacity(), 8);
+    assert_eq!(other.capacity(), 4);
+
+    // the buffered bytes come first
+    let mut out = [0; 4];
+    other.read_exact(&mut out).unwrap();
+    assert_eq!(out, *b"cdxx");
+    assert_eq!(reader.fill_buf().unwrap(), b"ef");
+}
+
+#[test]
+fn test_swap_buffers_positions() {
+    let mut reader = BufReader::with_capacity(4, &b"abcdefgh"[..]);
+    let mut other = BufReader::with_capacity(4, &b"xyz"[..]);
+
+    assert_eq!(reader.fill_buf().unwrap(), b"abcd");
+    reader.consume(1);
+    reader.set_spill(spill_file("swap-buffers"), 4).unwrap();
+    assert_eq!(reader.read_ahead(6).unwrap(), 6);
+    assert_eq!(reader.total_read(), 7);
+
+    other.set_fused(true);
+    let mut data = Vec::new();
+    other.read_to_end(&mut data).unwrap();
+    assert!(other.is_eof());
+
+    // the spilled data goes along with the buffer
+    reader.swap_buffers(&mut other);
+    assert_eq!(reader.total_pending(), 0);
+    assert_eq!((reader.total_read(), reader.consumer_offset()), (1, 1));
+    assert_eq!((other.total_read(), other.consumer_offset()), (9, 3));
+
+    data.clear();
+    other.read_to_end(&mut data).unwrap();
+    assert_eq!(data, b"bcdefg");
+    assert_eq!(other.consumer_offset(), 9);
+
+    // still fused, while the other reader continues where it left off
+    assert_eq!(other.fill_buf().unwrap(), b"");
+    assert_eq!(reader.fill_buf().unwrap(), b"h");
+}
+
+#[test]
+fn test_with_buffer_vec() {
+    let mut received = Vec::with_capacity(8);
+    received.extend_from_slice(b"hel");
+
+    // the contents are returned first
+    let mut reader = BufReader::with_buffer(received, &b"lo"[..]);
+    assert_eq!(reader.capacity(), 8);
+
+    let mut out = String::new();
+    reader.read_to_string(&mut out).unwrap();
+    assert_eq!(out, "hello");
+
+    let (_, buf) = reader.into_inner_with_buffer();
+    let recycled = buf.into_vec();
+    assert_eq!(recycled.capacity(), 8);
+
+    let mut reader = BufReader::with_empty_buffer(recycled, &b"world"[..]);
+    assert_eq!(reader.capacity(), 8);
+    assert_eq!(reader.fill_buf().unwrap(), b"world");
+}
+
+#[test]
+fn test_from_parts_into_parts() {
+    let mut buf = vec![0u8; 8];
+    buf[..5].copy_from_slice(b"xhell");
+
+    let mut reader = BufReader::from_parts(&b"o world"[..], buf, 1, 5).unwrap();
+    assert_eq!(reader.capacity(), 8);
+    assert_eq!(reader.buffer(), b"hell");
+
+    let mut out = [0u8; 2];
+    reader.read_exact(&mut out).unwrap();
+    assert_eq!(out, *b"he");
+
+    let (inner, buf, len) = reader.into_parts();
+    // the full capacity is returned with the pending bytes at the front
+    assert_eq!(buf.len(), 8);
+    assert_eq!(&buf[..len], b"ll");
+    assert_eq!(inner, b"o world");
+
+    let mut reader = BufReader::from_parts(inner, buf, 0, len).unwrap();
+    assert_eq!(reader.capacity(), 8);
+
+    let mut out = String::new();
+    reader.read_to_string(&mut out).unwrap();
+    assert_eq!(out, "llo world");
+
+    assert_eq!(BufReader::from_parts(&b""[..], vec![0; 4], 3, 2).unwrap_err().kind(),
+               io::ErrorKind::InvalidInput);
+    assert_eq!(BufReader::from_parts(&b""[..], vec![0; 4], 0, 5).unwrap_err().kind(),
+               io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_buffer_state() {
+    use BufferState;
+
+    let mut reader = BufReader::with_capacity(16, &b"hello world"[..]);
+    assert_eq!(reader.buffer_state(),
+               BufferState { pos: 0, cap: 0, capacity: 16, headroom: 16 });
+
+    reader.fill_buf().unwrap();
+    reader.consume(6);
+    assert_eq!(reader.consumed(), 6);
+    assert_eq!(reader.headroom(), 5);
+    assert_eq!(reader.buffer_state(),
+               BufferState { pos: 6, cap: 11, capacity: 16, headroom: 5 });
+
+    reader.make_room();
+    assert_eq!(reader.buffer_state(),
+               BufferState { pos: 0, cap: 5, capacity: 16, headroom: 11 });
+}
+
+#[test]
+fn test_get_buf_mut() {
+    const KEY: u8 = 0x5A;
+
+    let plaintext = (0 .. 100).collect::<Vec<u8>>();
+    let ciphertext = plaintext.iter().map(|b| b ^ KEY).collect::<Vec<u8>>();
+
+    let mut reader = BufReader::with_capacity(16, &ciphertext[..]);
+    let mut out = Vec::new();
+    // the number of bytes at the front of the buffer which were already decrypted
+    let mut decrypted = 0;
+
+    loop {
+        if reader.read_into_buf().unwrap() == 0 && reader.buf_len() == 0 { break; }
+
+        // decrypt only what was just read, in place
+        for byte in &mut reader.get_buf_mut()[decrypted ..] {
+            *byte ^= KEY;
+        }
+
+        decrypted = reader.buf_len();
+
+        // keep some of it buffered across the next refill
+        let amt = ::std::cmp::min(decrypted, 7);
+        out.extend_from_slice(&reader.buffer()[..amt]);
+        reader.consume(amt);
+        decrypted -= amt;
+    }
+
+    assert_eq!(out, plaintext);
+}
+
+#[test]
+fn test_push_prepend_bytes() {
+    let mut reader = BufReader::with_capacity(8, &b"socket data"[..]);
+
+    // before anything is read
+    reader.prepend_bytes(b"early ");
+    reader.push_bytes(b"| ");
+    assert_eq!(reader.buffer(), b"early | ");
+
+    let mut out = [0u8; 8];
+    reader.read_exact(&mut out).unwrap();
+    assert_eq!(out, *b"early | ");
+
+    // into the consumed space at the head
+    assert_eq!(reader.fill_buf().unwrap(), b"socket d");
+    reader.consume(7);
+    reader.prepend_bytes(b"<<");
+    assert_eq!(reader.consumed(), 5);
+    assert_eq!(reader.buffer(), b"<<d");
+
+    // shifting the data and growing the buffer
+    reader.prepend_bytes(b"0123456");
+    assert!(reader.capacity() >= 10);
+    assert_eq!(reader.buffer(), b"0123456<<d");
+
+    // appended data comes after what's buffered but before the next read
+    reader.push_bytes(b">>");
+    reader.consume(9);
+    assert_eq!(reader.buffer(), b"d>>");
+
+    let mut out = String::new();
+    reader.read_to_string(&mut out).unwrap();
+    assert_eq!(out, "d>>ata");
+}
+
+#[test]
+fn test_zero_capacity() {
+    let data = b"one\ntwo\nthree";
+
+    // reads go straight through without allocating
+    let mut reader = BufReader::with_capacity(0, &data[..]);
+    let mut out = [0u8; 4];
+    assert_eq!(reader.read(&mut out).unwrap(), 4);
+    assert_eq!(out, *b"one\n");
+    assert_eq!(reader.capacity(), 0);
+
+    // buffered reads allocate instead of returning nothing forever
+    let lines = reader.lines().map(Result::unwrap).collect::<Vec<_>>();
+    assert_eq!(lines, ["two", "three"]);
+
+    let mut reader = BufReader::with_capacity(0, &data[..]);
+    let mut line = Vec::new();
+    assert_eq!(reader.read_until(b'\n', &mut line).unwrap(), 4);
+    assert_eq!(line, b"one\n");
+    assert!(reader.capacity() > 0);
+
+    // it's an error if the buffer may not grow at all
+    let mut reader = BufReader::with_capacity(0, &data[..]);
+    reader.set_max_capacity(0);
+    assert_eq!(reader.fill_buf().unwrap_err().kind(), io::ErrorKind::OutOfMemory);
+    assert!(reader.lines().next().unwrap().is_err());
+}
+
+#[test]
+fn test_read_into_buf_full() {
+    // a frame one byte larger than the buffer
+    let frame = (0 .. 9).collect::<Vec<u8>>();
+
+    let mut reader = BufReader::with_capacity(8, &frame[..]);
+    assert_eq!(reader.read_into_buf().unwrap(), 8);
+
+    // the full buffer is grown rather than reading nothing
+    while reader.buf_len() < frame.len() {
+        assert!(reader.read_into_buf().unwrap() > 0);
+    }
+
+    assert!(reader.capacity() > 8);
+    assert_eq!(reader.buffer(), &frame[..]);
+
+    // unless it may not grow
+    let mut reader = BufReader::with_capacity(8, &frame[..]);
+    reader.set_max_capacity(8);
+    assert_eq!(reader.read_into_buf().unwrap(), 8);
+    assert_eq!(reader.read_into_buf().unwrap_err().kind(), io::ErrorKind::OutOfMemory);
+    assert_eq!(reader.buffer(), &frame[..8]);
+}
+
+#[test]
+fn test_spill() {
+    use std::io::SeekFrom;
+
+    let data = (0 .. 100).collect::<Vec<u8>>();
+
+    let mut reader = BufReader::with_capacity(8, io::Cursor::new(data.clone()));
+    reader.set_spill(spill_file("spill"), 16).unwrap();
+
+    // only the threshold is kept in memory
+    assert_eq!(reader.read_ahead(60).unwrap(), 60);
+    assert_eq!(reader.buffer(), &data[..16]);
+    assert_eq!(reader.total_pending(), 60);
+    assert_eq!(reader.get_ref().position(), 60);
+
+    // can't be replaced while data is pending
+    assert_eq!(reader.set_spill(spill_file("spill-2"), 16).unwrap_err().kind(),
+               io::ErrorKind::InvalidInput);
+
+    // replayed in order before reading further
+    let mut out = [0u8; 20];
+    reader.read_exact(&mut out).unwrap();
+    assert_eq!(out[..], data[..20]);
+    assert_eq!(reader.total_pending(), 40);
+
+    // more lookahead while some is still spilled
+    assert_eq!(reader.read_ahead(70).unwrap(), 70);
+
+    let mut out = Vec::new();
+    reader.read_to_end(&mut out).unwrap();
+    assert_eq!(out[..], data[20..]);
+    assert_eq!(reader.total_pending(), 0);
+
+    // seeking accounts for and discards the spilled data
+    let mut reader = BufReader::with_capacity(8, io::Cursor::new(data.clone()));
+    reader.set_spill(spill_file("spill-3"), 8).unwrap();
+    assert_eq!(reader.read_ahead(50).unwrap(), 50);
+    reader.consume(3);
+    assert_eq!(reader.seek(SeekFrom::Current(2)).unwrap(), 5);
+    assert_eq!(reader.total_pending(), 0);
+    assert_eq!(reader.fill_buf().unwrap(), &data[5 .. 13]);
+}
+
+#[test]
+fn test_spill_handed_out() {
+    let data = (0 .. 40).collect::<Vec<u8>>();
+
+    let spilled = |name| {
+        let mut reader = BufReader::with_capacity(8, io::Cursor::new(data.clone()));
+        reader.set_spill(spill_file(name), 8).unwrap();
+        assert_eq!(reader.read_ahead(30).unwrap(), 30);
+        reader.consume(2);
+        reader
+    };
+
+    let mut reader = spilled("spill-detach");
+    assert_eq!(reader.detach_buf(), &data[2 .. 30]);
+    assert_eq!(reader.total_pending(), 0);
+    assert_eq!(reader.fill_buf().unwrap(), &data[30..]);
+
+    let (_, buf) = spilled("spill-into-inner").into_inner_with_buffer();
+    assert_eq!(buf.buf(), &data[2 .. 30]);
+
+    let (_, buf, len) = spilled("spill-into-parts").into_parts();
+    assert_eq!(buf[..len], data[2 .. 30]);
+
+    let (mut std_reader, buf) = spilled("spill-into-std").into_std();
+    assert_eq!(buf, &data[2 .. 30]);
+    assert_eq!(std_reader.fill_buf().unwrap(), &data[30 .. 38]);
+
+    let mut out = Vec::new();
+    spilled("spill-unbuffer").unbuffer().read_to_end(&mut out).unwrap();
+    assert_eq!(out, &data[2..]);
+}
+
+#[test]
+fn test_spill_read_error() {
+    /// Returns each chunk in turn, then EOF.
+    struct Chunks(Vec<io::Result<Vec<u8>>>);
+
+    impl Read for Chunks {
+        fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() { return Ok(0); }
+
+            let chunk = self.0.remove(0)?;
+            assert!(chunk.len() <= out.len());
+            out[..chunk.len()].copy_from_slice(&chunk);
+            Ok(chunk.len())
+        }
+    }
+
+    let data = (0 .. 25).collect::<Vec<u8>>();
+    let chunks = vec![
+        Ok(data[..8].to_vec()),
+        Ok(data[8 .. 20].to_vec()),
+        Err(io::Error::new(io::ErrorKind::WouldBlock, "would block")),
+        Err(io::Error::new(io::ErrorKind::WouldBlock, "would block")),
+        Ok(data[20..].to_vec()),
+    ];
+
+    let mut reader = BufReader::with_capacity(8, Chunks(chunks));
+    reader.set_spill(spill_file("spill-error"), 8).unwrap();
+
+    // the first error only ends the copy, the second is returned; no data is lost
+    assert_eq!(reader.read_ahead(100).unwrap_err().kind(), io::ErrorKind::WouldBlock);
+    assert_eq!(reader.total_pending(), 20);
+
+    let mut out = Vec::new();
+    reader.read_to_end(&mut out).unwrap();
+    assert_eq!(out, data);
+}
+
+#[test]
+fn test_high_water_mark() {
+    let data = (0 .. 32).collect::<Vec<u8>>();
+    let mut reader = BufReader::with_capacity(16, &data[..]);
+    assert_eq!(reader.high_water_mark(), 0);
+
+    reader.fill_buf_min(10).unwrap();
+    assert_eq!(reader.high_water_mark(), 16);
+
+    reader.consume(12);
+    reader.read_into_buf().unwrap();
+    assert_eq!(reader.buf_len(), 16);
+    reader.consume(14);
+    assert_eq!(reader.high_water_mark(), 16);
+
+    // restarts from what's buffered now
+    reader.reset_high_water_mark();
+    assert_eq!(reader.high_water_mark(), 2);
+    // the last 4 bytes
+    reader.fill_buf_min(3).unwrap();
+    assert_eq!(reader.high_water_mark(), 6);
+}
+
+#[test]
+fn test_seek_relative() {
+    use std::io::SeekFrom;
+
+    let data = (0 .. 64).collect::<Vec<u8>>();
+    let mut reader = BufReader::with_capacity(16, io::Cursor::new(data.clone()));
+    let mut discarding = BufReader::with_capacity(16, io::Cursor::new(data.clone()));
+
+    // (offset, bytes to read afterwards); within the buffer, just behind it and beyond it
+    let steps = [(2, 3), (-4, 2), (5, 1), (-9, 4), (20, 3), (-20, 5), (-2, 2), (40, 8), (-1, 4)];
+
+    for &(offset, len) in &steps {
+        reader.seek_relative(offset).unwrap();
+        discarding.seek(SeekFrom::Current(offset)).unwrap();
+
+        let (mut out, mut expected) = (vec![0; len], vec![0; len]);
+        reader.read_exact(&mut out).unwrap();
+        discarding.read_exact(&mut expected).unwrap();
+        assert_eq!(out, expected, "after seeking by {}", offset);
+    }
+
+    // the buffer is kept for targets within it
+    let mut reader = BufReader::with_capacity(16, io::Cursor::new(data.clone()));
+    reader.fill_buf().unwrap();
+    reader.consume(4);
+    reader.seek_relative(6).unwrap();
+    reader.seek_relative(-8).unwrap();
+    assert_eq!(reader.get_ref().position(), 16);
+    assert_eq!(reader.buffer(), &data[2 .. 16]);
+
+    // otherwise it's discarded
+    reader.seek_relative(20).unwrap();
+    assert_eq!(reader.buf_len(), 0);
+    assert_eq!(reader.get_ref().position(), 22);
+
+    reader.fill_buf().unwrap();
+    reader.consume(1);
+    reader.seek_relative(-2).unwrap();
+    assert_eq!(reader.buf_len(), 0);
+    assert_eq!(reader.get_ref().position(), 21);
+}
+
+#[test]
+fn test_buffer_aware_seek() {
+    use std::io::SeekFrom;
+
+    let data = (0 .. 200).collect::<Vec<u8>>();
+    let mut reader = BufReader::with_capacity(32, io::Cursor::new(data.clone()));
+    reader.set_buffer_aware_seek(true);
+    let mut discarding = BufReader::with_capacity(32, io::Cursor::new(data.clone()));
+
+    // nothing is known about the position until the first seek
+    reader.fill_buf().unwrap();
+    assert_eq!(reader.seek(SeekFrom::Start(0)).unwrap(), 0);
+    assert_eq!(reader.buf_len(), 0);
+
+    reader.fill_buf().unwrap();
+    reader.consume(10);
+    assert_eq!(reader.seek(SeekFrom::Start(4)).unwrap(), 4);
+    assert_eq!(reader.get_ref().position(), 32);
+    assert_eq!(reader.buffer(), &data[4 .. 32]);
+    assert_eq!(reader.seek(SeekFrom::Current(20)).unwrap(), 24);
+    assert_eq!(reader.get_ref().position(), 32);
+    assert_eq!(reader.buffer(), &data[24 .. 32]);
+
+    // `End` is always a real seek
+    assert_eq!(reader.seek(SeekFrom::End(-8)).unwrap(), 192);
+    assert_eq!(reader.buf_len(), 0);
+
+    reader.seek(SeekFrom::Start(0)).unwrap();
+
+    // random seeks and reads, checked against the plain behavior
+    let mut state = 0x2545_f491_u32;
+    let mut next = |max: u32| {
+        state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        (state >> 16) % max
+    };
+
+    for _ in 0 .. 500 {
+        let pos = match next(3) {
+            0 => SeekFrom::Start(u64::from(next(190))),
+            1 => SeekFrom::Current(i64::from(next(60)) - 30),
+            _ => SeekFrom::End(-i64::from(next(100))),
+        };
+
+        let (res, expected_res) = (reader.seek(pos), discarding.seek(pos));
+        assert_eq!(res.is_ok(), expected_res.is_ok(), "seeking to {:?}", pos);
+        let res = match res { Ok(res) => res, Err(_) => continue };
+        assert_eq!(res, expected_res.unwrap(), "seeking to {:?}", pos);
+
+        let len = u64::from(next(40)).min((data.len() as u64).saturating_sub(res)) as usize;
+        let (mut out, mut expected) = (vec![0; len], vec![0; len]);
+        reader.read_exact(&mut out).unwrap();
+        discarding.read_exact(&mut expected).unwrap();
+        assert_eq!(out, expected, "reading after seeking to {:?}", pos);
+    }
+}
+
+#[test]
+fn test_stream_position() {
+    let data = (0 .. 64).collect::<Vec<u8>>();
+    let mut reader = BufReader::with_capacity(16, io::Cursor::new(data.clone()));
+
+    reader.fill_buf().unwrap();
+    reader.consume(5);
+    assert_eq!(reader.buffered_offset(), 5);
+    assert_eq!(reader.stream_position().unwrap(), 5);
+    assert_eq!(reader.buffer(), &data[5 .. 16]);
+
+    let mut out = [0; 20];
+    reader.read_exact(&mut out).unwrap();
+    assert_eq!(reader.stream_position().unwrap(), 25);
+    assert_eq!(reader.buffered_offset(), 25);
+
+    // the offset is relative to where the underlying reader started until it's known
+    let mut cursor = io::Cursor::new(data.clone());
+    cursor.set_position(10);
+    let mut reader = BufReader::with_capacity(16, cursor);
+    reader.read_exact(&mut out[.. 3]).unwrap();
+    assert_eq!(reader.buffered_offset(), 3);
+    assert_eq!(reader.stream_position().unwrap(), 13);
+    assert_eq!(reader.buffered_offset(), 13);
+
+    reader.push_bytes(&[0; 32]);
+    assert_eq!(reader.buffered_offset(), 0);
+    assert_eq!(reader.stream_position().unwrap_err().kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_rewind_buffered() {
+    let data = (0 .. 64).collect::<Vec<u8>>();
+    let mut reader = BufReader::with_capacity(16, io::Cursor::new(data.clone()));
+
+    // sniff a header, then read it again
+    let mut header = [0; 4];
+    reader.read_exact(&mut header).unwrap();
+    reader.rewind_buffered().unwrap();
+    assert_eq!(reader.get_ref().position(), 16);
+    assert_eq!(reader.buffer(), &data[.. 16]);
+
+    let mut out = vec![0; 40];
+    reader.read_exact(&mut out).unwrap();
+    assert_eq!(out, &data[.. 40]);
+
+    // the start of the stream is gone from the buffer by now
+    reader.rewind_buffered().unwrap();
+    assert_eq!(reader.buf_len(), 0);
+    assert_eq!(reader.get_ref().position(), 0);
+    reader.read_exact(&mut out).unwrap();
+    assert_eq!(out, &data[.. 40]);
+}
+
+#[test]
+fn test_total_read() {
+    let data = (0 .. 100).collect::<Vec<u8>>();
+    let mut reader = BufReader::with_capacity(16, &data[..]);
+
+    reader.fill_buf().unwrap();
+    reader.consume(6);
+    assert_eq!(reader.total_read(), 16);
+    assert_eq!(reader.consumer_offset(), 6);
+
+    // reads bypassing the buffer are counted too
+    let mut out = [0; 40];
+    reader.read_exact(&mut out).unwrap();
+    assert_eq!(reader.total_read(), 46);
+    assert_eq!(reader.consumer_offset(), 46);
+
+    reader.make_room();
+    reader.reserve(32);
+    reader.fill_buf().unwrap();
+    reader.consume(2);
+    assert_eq!(reader.consumer_offset(), 48);
+    let total = reader.total_read();
+    assert_eq!(total, 48 + reader.buf_len() as u64);
+
+    let mut unbuffer = reader.unbuffer();
+    assert_eq!(unbuffer.total_read(), total);
+    assert_eq!(unbuffer.consumer_offset(), 48);
+
+    let mut rest = Vec::new();
+    unbuffer.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, &data[48 ..]);
+    assert_eq!(unbuffer.total_read(), 100);
+    assert_eq!(unbuffer.consumer_offset(), 100);
+}
+
+#[test]
+fn test_unbuffer_seek() {
+    use std::io::SeekFrom;
+
+    let data = (0 .. 64).collect::<Vec<u8>>();
+    let mut reader = BufReader::with_capacity(16, io::Cursor::new(data.clone()));
+    reader.fill_buf().unwrap();
+    reader.consume(4);
+
+    let mut unbuffer = reader.unbuffer();
+    assert_eq!(unbuffer.seek(SeekFrom::Current(2)).unwrap(), 6);
+    assert!(unbuffer.is_buf_empty());
+
+    let mut out = [0; 4];
+    unbuffer.read_exact(&mut out).unwrap();
+    assert_eq!(out, [6, 7, 8, 9]);
+
+    assert_eq!(unbuffer.seek(SeekFrom::End(-2)).unwrap(), 62);
+    assert_eq!(unbuffer.seek(SeekFrom::Start(30)).unwrap(), 30);
+    unbuffer.read_exact(&mut out).unwrap();
+    assert_eq!(out, [30, 31, 32, 33]);
+}
+
+#[test]
+fn test_resync() {
+    let data = (0 .. 64).collect::<Vec<u8>>();
+    let mut reader = BufReader::with_capacity(16, io::Cursor::new(data.clone()));
+    reader.fill_buf().unwrap();
+    reader.consume(4);
+
+    let mut out = [0; 8];
+    reader.get_mut().read_exact(&mut out).unwrap();
+    assert_eq!(out, &data[16 .. 24]);
+
+    reader.resync();
+    assert_eq!(reader.buf_len(), 0);
+    reader.read_exact(&mut out).unwrap();
+    assert_eq!(out, &data[24 .. 32]);
+
+    reader.get_mut().read_exact(&mut out).unwrap();
+    assert_eq!(reader.resync_position().unwrap(), 48);
+    assert_eq!(reader.buf_len(), 0);
+    assert_eq!(reader.total_read(), 48);
+    assert_eq!(reader.consumer_offset(), 48);
+    assert_eq!(reader.buffered_offset(), 48);
+
+    reader.read_exact(&mut out[.. 2]).unwrap();
+    assert_eq!(out[.. 2], data[48 .. 50]);
+    assert_eq!(reader.buffered_offset(), 50);
+    assert_eq!(reader.stream_position().unwrap(), 50);
+}
+
+#[test]
+fn test_try_clone() {
+    use std::fs::{self, OpenOptions};
+    use std::io::SeekFrom;
+    use std::{env, process};
+
+    let path = env::temp_dir().join(format!("buf_redux-try-clone-{}", process::id()));
+    let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(true)
+        .open(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let data = (0 .. 64).collect::<Vec<u8>>();
+    file.write_all(&data).unwrap();
+    file.seek(SeekFrom::Start(0)).unwrap();
+
+    let mut reader = BufReader::with_capacity(16, file);
+    reader.fill_buf().unwrap();
+    reader.consume(4);
+
+    let mut clone = reader.try_clone().unwrap();
+    assert_eq!(clone.buffer(), &data[4 .. 16]);
+    assert_eq!(clone.capacity(), reader.capacity());
+
+    // the cursor of the file is shared
+    let mut out = [0; 20];
+    clone.read_exact(&mut out).unwrap();
+    assert_eq!(out, &data[4 .. 24]);
+    reader.read_exact(&mut out).unwrap();
+    assert_eq!(out[.. 12], data[4 .. 16]);
+    assert_eq!(out[12 ..], data[24 .. 32]);
+}
+
+#[cfg(any(unix, windows))]
+#[test]
+fn test_positional() {
+    use std::fs::{self, OpenOptions};
+    use std::io::SeekFrom;
+    use std::{env, process};
+
+    let path = env::temp_dir().join(format!("buf_redux-positional-{}", process::id()));
+    let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(true)
+        .open(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let data = (0 .. 64).collect::<Vec<u8>>();
+    file.write_all(&data).unwrap();
+    file.seek(SeekFrom::Start(3)).unwrap();
+
+    // two readers sharing the file, each at its own offset
+    let mut first = BufReader::positional(&file, 0);
+    let mut second = BufReader::with_capacity(8, ReadAt::new(&file, 40));
+
+    let mut out = [0; 10];
+    first.read_exact(&mut out).unwrap();
+    assert_eq!(out, &data[.. 10]);
+    second.read_exact(&mut out).unwrap();
+    assert_eq!(out, &data[40 .. 50]);
+    first.read_exact(&mut out).unwrap();
+    assert_eq!(out, &data[10 .. 20]);
+
+    assert_eq!(second.seek(SeekFrom::End(-4)).unwrap(), 60);
+    let mut rest = Vec::new();
+    second.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, &data[60 ..]);
+    assert_eq!(second.get_ref().offset(), 64);
+    assert_eq!(second.seek(SeekFrom::Current(-100)).unwrap_err().kind(),
+               io::ErrorKind::InvalidInput);
+
+    #[cfg(unix)]
+    assert_eq!(file.stream_position().unwrap(), 3);
+}
+
+#[test]
+fn test_unbuffer_bufread() {
+    let data = b"first\nsecond\nthird\nfourth\n";
+    let mut reader = BufReader::with_capacity(8, io::BufReader::with_capacity(4, &data[..]));
+    reader.fill_buf().unwrap();
+    reader.consume(2);
+
+    let mut unbuffer = reader.unbuffer();
+    assert_eq!(unbuffer.fill_buf().unwrap(), b"rst\nse");
+
+    // lines continue seamlessly from the buffer into the underlying reader
+    let lines = unbuffer.lines().map(Result::unwrap).collect::<Vec<_>>();
+    assert_eq!(lines, ["rst", "second", "third", "fourth"]);
+}
+
+#[test]
+fn test_unbuffer_into_inner_with_buf() {
+    let data = (0 .. 32).collect::<Vec<u8>>();
+    let mut reader = BufReader::with_capacity(16, &data[..]);
+    reader.fill_buf().unwrap();
+    reader.consume(4);
+
+    let mut unbuffer = reader.unbuffer();
+    let mut out = [0; 2];
+    unbuffer.read_exact(&mut out).unwrap();
+
+    let (inner, buf) = unbuffer.into_inner_with_buf();
+    assert_eq!(buf, &data[6 .. 16]);
+    assert_eq!(inner, &data[16 ..]);
+
+    // nothing is left once the buffer is used up
+    let mut unbuffer = BufReader::with_capacity(16, &data[..]).unbuffer();
+    let mut out = [0; 20];
+    unbuffer.read_exact(&mut out).unwrap();
+    let (inner, buf) = unbuffer.into_inner_with_buf();
+    assert!(buf.is_empty());
+    assert_eq!(inner, &data[20 ..]);
+}
+
+#[test]
+fn test_unbuffer_releases_buffer() {
+    let data = (0 .. 32).collect::<Vec<u8>>();
+    let mut reader = BufReader::with_capacity(16, &data[..]);
+    reader.fill_buf().unwrap();
+    reader.consume(10);
+
+    let mut unbuffer = reader.unbuffer();
+    assert!(!unbuffer.is_buf_empty());
+    assert!(unbuffer.buf_capacity() >= 16);
+
+    // an empty read doesn't lose the buffered data
+    assert_eq!(unbuffer.read(&mut []).unwrap(), 0);
+    assert_eq!(unbuffer.buf(), &data[10 .. 16]);
+
+    let mut out = [0; 6];
+    unbuffer.read_exact(&mut out).unwrap();
+    assert_eq!(out, &data[10 .. 16]);
+    assert!(unbuffer.is_buf_empty());
+    assert_eq!(unbuffer.buf_len(), 0);
+    assert_eq!(unbuffer.buf_capacity(), 0);
+
+    unbuffer.read_exact(&mut out).unwrap();
+    assert_eq!(out, &data[16 .. 22]);
+
+    // an empty buffer is released right away
+    let unbuffer = BufReader::new(&data[..]).unbuffer();
+    assert!(unbuffer.is_buf_empty());
+    assert_eq!(unbuffer.buf_capacity(), 0);
+}
+
+#[test]
+fn test_rebuffer() {
+    let data = (0 .. 100).collect::<Vec<u8>>();
+    let mut reader = BufReader::with_capacity(16, &data[..]);
+    reader.fill_buf().unwrap();
+    reader.consume(5);
+
+    let mut unbuffer = reader.unbuffer();
+    let mut out = [0; 3];
+    unbuffer.read_exact(&mut out).unwrap();
+
+    // the partially drained buffer is kept
+    let reader = unbuffer.rebuffer(8);
+    assert_eq!(reader.buffer(), &data[8 .. 16]);
+    assert!(reader.capacity() >= 16);
+    assert_eq!(reader.total_read(), 16);
+
+    let mut unbuffer = reader.unbuffer();
+    unbuffer.read_exact(&mut out).unwrap();
+
+    // and grown if necessary
+    let mut reader = unbuffer.rebuffer(64);
+    assert_eq!(reader.buffer(), &data[11 .. 16]);
+    assert!(reader.capacity() >= 64);
+
+    let mut rest = Vec::new();
+    reader.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, &data[11 ..]);
+
+    let reader = BufReader::with_capacity(16, &data[..]).unbuffer().rebuffer(32);
+    assert_eq!(reader.buf_len(), 0);
+    assert_eq!(reader.capacity(), 32);
+}
+
+#[test]
+fn test_unbuffer_read_to_end_vectored() {
+    let data = (0 .. 64).collect::<Vec<u8>>();
+
+    let mut reader = BufReader::with_capacity(16, &data[..]);
+    reader.fill_buf().unwrap();
+    reader.consume(2);
+
+    let mut unbuffer = reader.unbuffer();
+    let mut out = vec![0xFF];
+    assert_eq!(unbuffer.read_to_end(&mut out).unwrap(), 62);
+    assert_eq!(out[0], 0xFF);
+    assert_eq!(out[1 ..], data[2 ..]);
+    assert!(unbuffer.is_buf_empty());
+    assert_eq!(unbuffer.total_read(), 64);
+
+    // the buffered data is larger than the first slice
+    let mut reader = BufReader::with_capacity(16, &data[..]);
+    reader.fill_buf().unwrap();
+    reader.consume(2);
+
+    let mut unbuffer = reader.unbuffer();
+    let (mut first, mut second, mut third) = ([0; 4], [0; 8], [0; 8]);
+    let read = {
+        let mut bufs = [io::IoSliceMut::new(&mut first), io::IoSliceMut::new(&mut second),
+                        io::IoSliceMut::new(&mut third)];
+        unbuffer.read_vectored(&mut bufs).unwrap()
+    };
+    assert_eq!(read, 14);
+    assert_eq!(first, &data[2 .. 6]);
+    assert_eq!(second, &data[6 .. 14]);
+    assert_eq!(third[.. 2], data[14 .. 16]);
+    assert!(unbuffer.is_buf_empty());
+
+    // then it reads from the underlying reader
+    let read = {
+        let mut bufs = [io::IoSliceMut::new(&mut first), io::IoSliceMut::new(&mut second)];
+        unbuffer.read_vectored(&mut bufs).unwrap()
+    };
+    assert_eq!(read, 12);
+    assert_eq!(first, &data[16 .. 20]);
+    assert_eq!(second, &data[20 .. 28]);
+}
+
+#[test]
+fn test_std_conversions() {
+    let data = (0 .. 64).collect::<Vec<u8>>();
+
+    let mut std_reader = io::BufReader::with_capacity(16, &data[..]);
+    std_reader.fill_buf().unwrap();
+    std_reader.consume(3);
+
+    let mut reader = BufReader::from(std_reader);
+    assert_eq!(reader.capacity(), 16);
+    assert_eq!(reader.buffer(), &data[3 .. 16]);
+
+    let mut out = [0; 5];
+    reader.read_exact(&mut out).unwrap();
+    assert_eq!(out, &data[3 .. 8]);
+
+    let (std_reader, buffered) = reader.into_std();
+    assert_eq!(std_reader.capacity(), 16);
+    assert_eq!(buffered, &data[8 .. 16]);
+
+    let mut rest = Vec::new();
+    (&buffered[..]).chain(std_reader).read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, &data[8 ..]);
+}
+
+#[test]
+fn test_unsized_inner() {
+    let data = b"first line\nsecond line\n";
+    let mut reader: Box<BufReader<dyn Read + Send>> =
+        Box::new(BufReader::with_capacity(8, &data[..]));
+
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    assert_eq!(line, "first line\n");
+    assert!(reader.buffer().starts_with(b"sec"));
+
+    let mut rest = Vec::new();
+    reader.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, b"second line\n");
+    assert_eq!(reader.total_read(), data.len() as u64);
+}
+
+#[test]
+fn test_boxed() {
+    use std::thread;
+
+    let data = (0 .. 64).collect::<Vec<u8>>();
+    let mut reader = BufReader::with_capacity(16, io::Cursor::new(data.clone()));
+    reader.fill_buf().unwrap();
+    reader.consume(5);
+
+    let reader = reader.boxed();
+    assert_eq!(reader.buffer(), &data[5 .. 16]);
+    assert_eq!(reader.total_read(), 16);
+
+    // the buffer and settings carry over, nothing is read again
+    let mut reader = BufReader::with_capacity(16, io::Cursor::new(data.clone()));
+    reader.set_max_capacity(16);
+    reader.fill_buf().unwrap();
+    reader.consume(5);
+
+    let mut reader = reader.boxed_send();
+    reader.reserve(64);
+    assert_eq!(reader.capacity(), 16);
+
+    let rest = thread::spawn(move || {
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        rest
+    }).join().unwrap();
+
+    assert_eq!(rest, &data[5 ..]);
+}
+
+#[test]
+fn test_open() {
+    use std::fs;
+    use std::{env, process};
+
+    let path = env::temp_dir().join(format!("buf_redux-open-{}", process::id()));
+
+    for &(len, cap) in &[(0, 8 * 1024), (100, 256), (1000, 1024), (5000, 8 * 1024),
+                         (100_000, 32 * 1024)] {
+        let data = vec![7; len];
+        fs::write(&path, &data).unwrap();
+
+        let mut reader = BufReader::open(&path).unwrap();
+        assert_eq!(reader.capacity(), cap, "file length {}", len);
+
+        let mut read = Vec::new();
+        reader.read_to_end(&mut read).unwrap();
+        assert_eq!(read, data);
+    }
+
+    assert_eq!(BufReader::open_with_capacity(100, &path).unwrap().capacity(), 100);
+
+    fs::remove_file(&path).unwrap();
+    assert_eq!(BufReader::open(&path).unwrap_err().kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_new_sized() {
+    use SizeHint;
+
+    let data = (0 .. 50).collect::<Vec<u8>>();
+
+    let mut reader = BufReader::new_sized(io::Cursor::new(&data[..]));
+    assert_eq!(reader.capacity(), 50);
+    assert_eq!(reader.fill_buf().unwrap(), &data[..]);
+
+    let mut cursor = io::Cursor::new(&data[..]);
+    cursor.set_position(45);
+    assert_eq!(BufReader::new_sized(cursor).capacity(), 5);
+
+    assert_eq!(BufReader::new_sized(&data[10 ..]).capacity(), 40);
+    assert_eq!(BufReader::new_sized((&data[..]).take(20)).capacity(), 20);
+
+    // large hints are capped at the default
+    let large = vec![0; 100 * 1024];
+    assert_eq!(BufReader::new_sized(&large[..]).capacity(),
+               BufReader::new(&large[..]).capacity());
+
+    // a hint that's too small only affects the capacity
+    struct Lying<'a>(&'a [u8]);
+
+    impl<'a> Read for Lying<'a> {
+        fn read(&mut self, out: &mut [u8]) -> io::Result<usize> { self.0.read(out) }
+    }
+
+    impl<'a> SizeHint for Lying<'a> {
+        fn remaining_hint(&self) -> Option<u64> { Some(3) }
+    }
+
+    let mut reader = BufReader::new_sized(Lying(&data));
+    assert_eq!(reader.capacity(), 3);
+    let mut read = Vec::new();
+    reader.read_to_end(&mut read).unwrap();
+    assert_eq!(read, data);
+}
+
+#[test]
+fn test_clone() {
+    use std::fs::{self, OpenOptions};
+    use std::{env, process};
+
+    let data = (0 .. 100).collect::<Vec<u8>>();
+    let mut reader = BufReader::with_capacity(16, io::Cursor::new(&data[..]));
+    reader.fill_buf().unwrap();
+    reader.consume(7);
+
+    let mut clone = reader.clone();
+    assert_eq!(clone.buffer(), reader.buffer());
+
+    // both read the rest of the stream independently
+    let (mut first, mut second) = (Vec::new(), Vec::new());
+    reader.read_to_end(&mut first).unwrap();
+    clone.read_to_end(&mut second).unwrap();
+    assert_eq!(first, &data[7 ..]);
+    assert_eq!(second, first);
+
+    // spilled data is copied into the buffer of the clone
+    let path = env::temp_dir().join(format!("buf_redux-clone-{}", process::id()));
+    let file = OpenOptions::new().read(true).write(true).create(true).truncate(true)
+        .open(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let mut reader = BufReader::with_capacity(8, io::Cursor::new(&data[..]));
+    reader.set_spill(file, 8).unwrap();
+    reader.read_ahead(30).unwrap();
+    reader.consume(2);
+
+    let mut clone = reader.clone();
+    assert_eq!(clone.total_pending(), 28);
+    assert_eq!(clone.buffer(), &data[2 .. 30]);
+
+    let (mut first, mut second) = (Vec::new(), Vec::new());
+    reader.read_to_end(&mut first).unwrap();
+    clone.read_to_end(&mut second).unwrap();
+    assert_eq!(first, &data[2 ..]);
+    assert_eq!(second, first);
+}
+
+#[test]
+fn test_builder() {
+    use BufReaderBuilder;
+
+    let data = (0 .. 64).collect::<Vec<u8>>();
+    let builder = BufReaderBuilder::new().capacity(16).max_capacity(32).auto_shrink(8, 2);
+
+    // the builder can be reused
+    for _ in 0 .. 2 {
+        let mut reader = builder.build(&data[..]);
+        assert_eq!(reader.capacity(), 16);
+        assert_eq!(reader.max_capacity(), Some(32));
+        assert_eq!(reader.fill_buf_min(40).unwrap_err().kind(), io::ErrorKind::OutOfMemory);
+
+        let mut read = Vec::new();
+        reader.read_to_end(&mut read).unwrap();
+        assert_eq!(read, data);
+    }
+
+    assert_eq!(BufReaderBuilder::default().build(&data[..]).capacity(),
+               BufReader::new(&data[..]).capacity());
+}
+
+#[test]
+#[should_panic(expected = "larger than max_capacity")]
+fn test_builder_conflict() {
+    use BufReaderBuilder;
+
+    BufReaderBuilder::new().capacity(64).max_capacity(32).build(&b""[..]);
+}
+
+#[test]
+fn test_with_policy() {
+    use policy::MinBuffered;
+
+    let mut reader = BufReader::with_capacity_and_policy(4, Dribble::new((0 .. 8).collect(), 2),
+                                                         MinBuffered(3));
+    assert_eq!(reader.policy().0, 3);
+    assert_eq!(reader.capacity(), 4);
+
+    // the policy applies through the `BufRead` impl
+    fn fill(reader: &mut dyn BufRead) -> usize {
+        reader.fill_buf().unwrap().len()
+    }
+
+    assert_eq!(fill(&mut reader), 4);
+    reader.consume(2);
+    assert_eq!(fill(&mut reader), 4);
+
+    let reader = BufReader::with_policy(&b""[..], MinBuffered(2));
+    assert_eq!(reader.capacity(), 8 * 1024);
+}
+
+#[test]
+fn test_ringbuf_fallback() {
+    use Buffer;
+
+    let ringbufs = cfg!(all(feature = "slice-deque", any(unix, windows)));
+    assert_eq!(Buffer::with_capacity_ringbuf(16).is_ringbuf(), ringbufs);
+
+    // the constructors exist either way
+    let mut reader = BufReader::with_capacity_ringbuf(16, &b"abc"[..]);
+    assert_eq!(reader.fill_buf().unwrap(), b"abc");
+    if !ringbufs { assert_eq!(reader.capacity(), 16); }
+}
+
+#[test]
+fn test_map_inner() {
+    let data = (0 .. 32).collect::<Vec<u8>>();
+    let mut reader = BufReader::with_capacity(8, &data[..]);
+    reader.set_max_capacity(16);
+    assert_eq!(reader.peek(3).unwrap(), &data[.. 3]);
+
+    // the buffered bytes come first, then the wrapped reader
+    let mut reader = reader.map_inner(|inner| inner.take(4));
+    assert_eq!(reader.max_capacity(), Some(16));
+    let mut read = Vec::new();
+    reader.read_to_end(&mut read).unwrap();
+    assert_eq!(read, &data[.. 12]);
+
+    let reader = BufReader::with_capacity(8, &data[..]);
+    let (mut reader, err) = reader.try_map_inner(|inner| Err::<io::Empty, _>((inner, "not TLS")))
+        .unwrap_err();
+    assert_eq!(err, "not TLS");
+    read.clear();
+    reader.read_to_end(&mut read).unwrap();
+    assert_eq!(read, data);
+
+    let reader = BufReader::with_capacity(8, &data[..]);
+    let mut reader = reader.try_map_inner(|inner| Ok::<_, (_, ())>(inner.take(5))).unwrap();
+    read.clear();
+    reader.read_to_end(&mut read).unwrap();
+    assert_eq!(read, &data[.. 5]);
+}
+
+#[test]
+fn test_map_inner_resets_eof() {
+    use std::io::SeekFrom;
+
+    let mut reader = BufReader::with_capacity(8, io::Cursor::new(&b"ab"[..]));
+    reader.set_fused(true);
+    reader.set_buffer_aware_seek(true);
+    reader.seek(SeekFrom::Start(1)).unwrap();
+
+    let mut read = Vec::new();
+    reader.read_to_end(&mut read).unwrap();
+    assert!(reader.is_eof());
+
+    // still fused, but for the new reader
+    assert_eq!(reader.buffered_offset(), 2);
+    let mut reader = reader.map_inner(|_| &b"cd"[..]);
+    assert!(!reader.is_eof());
+    // the position from the seek doesn't apply any more, just the count of bytes read
+    assert_eq!(reader.buffered_offset(), 1);
+    assert_eq!(reader.fill_buf().unwrap(), b"cd");
+    reader.consume(2);
+    assert_eq!(reader.fill_buf().unwrap(), b"");
+    assert!(reader.is_eof());
+
+    // wrapping the same stream keeps the state
+    let reader = reader.boxed();
+    assert!(reader.is_eof());
+}
+
+#[test]
+fn test_min_buffered_generic_bufread() {
+    use policy::MinBuffered;
+
+    // a parser which assumes a whole 4-byte token is available
+    fn parse_token(reader: &mut dyn BufRead) -> Option<[u8; 4]> {
+        let buf = reader.fill_buf().unwrap();
+        if buf.len() < 4 { return None; }
+
+        let token = [buf[0], buf[1], buf[2], buf[3]];
+        reader.consume(4);
+        Some(token)
+    }
+
+    let data = (0 .. 20).collect::<Vec<u8>>();
+    let mut reader = BufReader::with_capacity(6, Dribble::new(data, 3))
+        .set_policy(MinBuffered(4));
+
+    let mut tokens = 0;
+    while let Some(token) = parse_token(&mut reader) {
+        assert_eq!(token[0], tokens * 4);
+        tokens += 1;
+    }
+    assert_eq!(tokens, 5);
+}
+
+#[test]
+fn test_fill_full() {
+    use BufReaderBuilder;
+
+    let data = (0 .. 100).collect::<Vec<u8>>();
+
+    let mut reader = BufReader::with_capacity(32, Dribble::new(data.clone(), 5));
+    assert_eq!(reader.fill_buf().unwrap(), &data[..5]);
+
+    reader.set_fill_full(true);
+    reader.consume(5);
+    assert_eq!(reader.fill_buf().unwrap(), &data[5 .. 37]);
+
+    // doesn't read while there's still data buffered
+    reader.consume(30);
+    assert_eq!(reader.fill_buf().unwrap(), &data[35 .. 37]);
+    reader.consume(2);
+
+    // stops at EOF without losing what was read
+    let mut rest = Vec::new();
+    while !reader.fill_buf().unwrap().is_empty() {
+        rest.extend_from_slice(reader.buffer());
+        let len = reader.buf_len();
+        reader.consume(len);
+    }
+    assert_eq!(rest, &data[37..]);
+    assert_eq!(reader.fill_buf().unwrap(), &[][..]);
+
+    /// Returns `WouldBlock` on the third and fourth reads.
+    struct Blocking(Dribble, u32);
+
+    impl Read for Blocking {
+        fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+            self.1 += 1;
+            if self.1 == 3 || self.1 == 4 {
+                return Err(io::Error::new(io::ErrorKind::WouldBlock, "would block"));
+            }
+            self.0.read(out)
+        }
+    }
+
+    let mut reader = BufReader::with_capacity(32, Blocking(Dribble::new(data.clone(), 5), 0));
+    reader.set_fill_full(true);
+    assert_eq!(reader.fill_buf().unwrap(), &data[..10]);
+    reader.consume(10);
+
+    // no data read yet, so the error is returned
+    assert_eq!(reader.fill_buf().unwrap_err().kind(), io::ErrorKind::WouldBlock);
+    assert_eq!(reader.fill_buf().unwrap(), &data[10 .. 42]);
+
+    let mut built = BufReaderBuilder::new().capacity(32).fill_full(true)
+        .build(Dribble::new(data.clone(), 5));
+    assert_eq!(built.fill_buf().unwrap(), &data[..32]);
+}
+
+#[test]
+fn test_bypass_threshold() {
+    use BufReaderBuilder;
+
+    /// Counts calls to `read()` and remembers the length of the last one.
+    struct CountReads<'a>(&'a [u8], usize, usize);
+
+    impl<'a> Read for CountReads<'a> {
+        fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+            self.1 += 1;
+            self.2 = out.len();
+            self.0.read(out)
+        }
+    }
+
+    let data = (0 .. 64).collect::<Vec<u8>>();
+
+    // a small read, then a larger one with the buffer empty
+    fn reads(mut reader: BufReader<CountReads>, len: usize) -> (usize, usize) {
+        let mut out = [0u8; 32];
+        assert_eq!(reader.read(&mut out[..4]).unwrap(), 4);
+        let buf_len = reader.buf_len();
+        reader.consume(buf_len);
+
+        assert!(reader.read(&mut out[..len]).unwrap() > 0);
+        assert_eq!(out[0], 16);
+        (reader.get_ref().1, reader.get_ref().2)
+    }
+
+    let reader = || BufReader::with_capacity(16, CountReads(&data, 0, 0));
+
+    assert_eq!(reader().bypass_threshold(), Some(16));
+    assert_eq!(reads(reader(), 32), (2, 32));
+    assert_eq!(reads(reader(), 8), (2, 16));
+
+    let mut below = reader();
+    below.set_bypass_threshold(Some(8));
+    assert_eq!(reads(below, 8), (2, 8));
+
+    let mut above = reader();
+    above.set_bypass_threshold(Some(33));
+    assert_eq!(reads(above, 32), (2, 16));
+
+    let mut never = reader();
+    never.set_bypass_threshold(None);
+    assert_eq!(never.bypass_threshold(), None);
+    assert_eq!(reads(never, 32), (2, 16));
+
+    let never = BufReaderBuilder::new().capacity(16).bypass_threshold(None)
+        .build(CountReads(&data, 0, 0));
+    assert_eq!(reads(never, 32), (2, 16));
+
+    // `read_exact()` and `read_to_end()` go through the buffer as well
+    let mut never = reader();
+    never.set_bypass_threshold(None);
+    let mut out = [0u8; 32];
+    never.read_exact(&mut out).unwrap();
+    assert_eq!(out, &data[..32]);
+    assert_eq!((never.get_ref().1, never.get_ref().2), (2, 16));
+
+    let mut rest = Vec::new();
+    never.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, &data[32..]);
+    // two more reads to fill, one for EOF
+    assert_eq!((never.get_ref().1, never.get_ref().2), (5, 16));
+}
+
+#[test]
+fn test_grow_on_demand() {
+    use {BufReaderBuilder, UntilOutcome};
+
+    // a few bytes past one, two and four buffer-lengths
+    for &len in &[19, 35, 67] {
+        let mut data = vec![b'a'; len];
+        data.push(b'\n');
+        data.extend_from_slice(b"rest");
+
+        let mut reader = BufReader::with_capacity(16, Dribble::new(data.clone(), 5));
+        reader.set_grow_on_demand(1024);
+
+        let mut line = Vec::new();
+        assert_eq!(reader.read_until(b'\n', &mut line).unwrap(), len + 1);
+        assert_eq!(line, &data[..=len]);
+        assert!(reader.capacity() > len);
+        // the whole line was in the buffer at once
+        assert_eq!(reader.unconsume(len + 1), len + 1);
+        assert!(reader.buffer().starts_with(&line));
+        reader.consume(len + 1);
+
+        line.clear();
+        assert_eq!(reader.read_until(b'\n', &mut line).unwrap(), 4);
+        assert_eq!(line, b"rest");
+
+        // a multi-byte delimiter split across reads
+        let mut data = vec![b'a'; len];
+        data.extend_from_slice(b"\r\n\r\n");
+
+        let mut reader = BufReaderBuilder::new().capacity(16).grow_on_demand(1024)
+            .build(Dribble::new(data.clone(), 5));
+
+        let mut head = Vec::new();
+        assert_eq!(reader.read_until_slice(b"\r\n\r\n", &mut head).unwrap(), (len + 4, true));
+        assert_eq!(head, data);
+        assert_eq!(reader.unconsume(len + 4), len + 4);
+
+        // without it, the buffer is cycled
+        let mut reader = BufReader::with_capacity(16, Dribble::new(data.clone(), 5));
+        head.clear();
+        assert_eq!(reader.read_until_slice(b"\r\n\r\n", &mut head).unwrap(), (len + 4, true));
+        assert_eq!(reader.capacity(), 16);
+        assert!(reader.unconsume(len + 4) < len + 4);
+    }
+
+    // limited by the maximum and the maximum capacity
+    let mut data = vec![b'a'; 67];
+    data.push(b'\n');
+
+    for &(max, max_cap) in &[(32, 1024), (1024, 32)] {
+        let mut reader = BufReader::with_capacity(16, Dribble::new(data.clone(), 5));
+        reader.set_grow_on_demand(max);
+        reader.set_max_capacity(max_cap);
+
+        let mut line = Vec::new();
+        assert_eq!(reader.read_until(b'\n', &mut line).unwrap(), 68);
+        assert_eq!(line, data);
+        assert!(reader.capacity() <= 32);
+    }
+
+    let mut reader = BufReader::with_capacity(16, Dribble::new(data.clone(), 5));
+    reader.set_grow_on_demand(32);
+    let mut line = Vec::new();
+    assert_eq!(reader.read_until_limited(b'\n', 20, &mut line).unwrap(),
+               UntilOutcome::LimitReached(20));
+    assert!(reader.capacity() <= 32);
+}
+
+#[test]
+fn test_fused() {
+    use std::io::{Cursor, SeekFrom};
+
+    /// Panics if read again after returning EOF, unless `.1` is reset.
+    struct PanicAfterEof(Cursor<Vec<u8>>, bool);
+
+    impl Read for PanicAfterEof {
+        fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+            assert!(!self.1, "read after EOF");
+            let read = self.0.read(out)?;
+            self.1 = read == 0 && !out.is_empty();
+            Ok(read)
+        }
+    }
+
+    impl Seek for PanicAfterEof {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.1 = false;
+            self.0.seek(pos)
+        }
+    }
+
+    let data = (0 .. 20).collect::<Vec<u8>>();
+    let mut reader = BufReader::with_capacity(8, PanicAfterEof(Cursor::new(data.clone()), false));
+    reader.set_fused(true);
+
+    let mut out = Vec::new();
+    assert_eq!(reader.read_to_end(&mut out).unwrap(), 20);
+    assert_eq!(out, data);
+
+    // none of these touch the underlying reader anymore
+    assert_eq!(reader.fill_buf().unwrap(), &[][..]);
+    assert_eq!(reader.read(&mut [0; 4]).unwrap(), 0);
+    assert_eq!(reader.read(&mut [0; 16]).unwrap(), 0);
+    assert_eq!(reader.read_to_end(&mut out).unwrap(), 0);
+    assert_eq!(reader.read_exact(&mut [0; 16]).unwrap_err().kind(),
+               io::ErrorKind::UnexpectedEof);
+    assert_eq!(reader.peek(4).unwrap(), &[][..]);
+    assert_eq!(reader.read_into_buf().unwrap(), 0);
+
+    // data added to the buffer is still returned
+    reader.push_bytes(b"ab");
+    assert_eq!(reader.fill_buf().unwrap(), b"ab");
+    reader.consume(2);
+
+    // seeking makes the data readable again
+    assert_eq!(reader.seek(SeekFrom::Start(16)).unwrap(), 16);
+    assert_eq!(reader.fill_buf().unwrap(), &data[16..]);
+    reader.consume(4);
+    assert_eq!(reader.fill_buf().unwrap(), &[][..]);
+    assert_eq!(reader.fill_buf().unwrap(), &[][..]);
+
+    // appending to the underlying reader needs an explicit reset
+    reader.get_mut().0.get_mut().extend_from_slice(b"more");
+    assert_eq!(reader.fill_buf().unwrap(), &[][..]);
+    reader.get_mut().1 = false;
+    reader.reset_eof();
+    assert_eq!(reader.fill_buf().unwrap(), b"more");
+
+    // a bypassing read reaching EOF fuses as well
+    let mut reader = BufReader::with_capacity(8, PanicAfterEof(Cursor::new(data.clone()), false));
+    reader.set_fused(true);
+    let mut out = [0u8; 32];
+    assert_eq!(reader.read(&mut out).unwrap(), 20);
+    assert_eq!(reader.read(&mut out).unwrap(), 0);
+    assert_eq!(reader.read(&mut out).unwrap(), 0);
+    assert_eq!(reader.read(&mut out[..4]).unwrap(), 0);
+
+    // an empty read doesn't
+    let mut reader = BufReader::with_capacity(8, &data[..]);
+    reader.set_fused(true);
+    reader.set_bypass_threshold(Some(0));
+    assert_eq!(reader.read(&mut []).unwrap(), 0);
+    assert_eq!(reader.read(&mut out).unwrap(), 20);
+}
+
+#[test]
+fn test_is_eof() {
+    use std::io::{Cursor, SeekFrom};
+
+    let data = (0 .. 20).collect::<Vec<u8>>();
+    let mut reader = BufReader::with_capacity(8, Cursor::new(data.clone()));
+    assert!(!reader.is_eof());
+    assert!(reader.has_data_left().unwrap());
+
+    let mut out = [0u8; 16];
+    reader.read_exact(&mut out).unwrap();
+    assert!(!reader.is_eof());
+
+    // trailing bytes are buffered
+    assert!(reader.has_data_left().unwrap());
+    assert_eq!(reader.buf_len(), 4);
+    assert!(!reader.is_eof());
+    reader.consume(4);
+
+    // a refill hits EOF
+    assert!(!reader.has_data_left().unwrap());
+    assert!(reader.is_eof());
+
+    reader.prepend_bytes(b"ab");
+    assert!(!reader.is_eof());
+    assert!(reader.has_data_left().unwrap());
+    reader.consume(2);
+
+    // bypassing reads are tracked as well
+    assert_eq!(reader.read(&mut out).unwrap(), 0);
+    assert!(reader.is_eof());
+
+    reader.seek(SeekFrom::Start(4)).unwrap();
+    assert!(!reader.is_eof());
+    assert_eq!(reader.read(&mut out).unwrap(), 16);
+    assert!(!reader.is_eof());
+    assert_eq!(reader.read(&mut out).unwrap(), 0);
+    assert!(reader.is_eof());
+
+    reader.seek(SeekFrom::Start(0)).unwrap();
+    assert_eq!(reader.read_to_end(&mut Vec::new()).unwrap(), 20);
+    assert!(reader.is_eof());
+
+    // more data after EOF
+    reader.get_mut().get_mut().extend_from_slice(b"more");
+    assert!(reader.has_data_left().unwrap());
+    assert!(!reader.is_eof());
+
+    // an empty read says nothing about EOF
+    let mut reader = BufReader::with_capacity(8, &data[..]);
+    reader.set_bypass_threshold(Some(0));
+    assert_eq!(reader.read(&mut []).unwrap(), 0);
+    assert!(!reader.is_eof());
+}
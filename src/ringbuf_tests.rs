@@ -266,6 +266,92 @@ fn test_mirror_boundary() {
     assert_eq!(buffer.usable_space(), cap);
 }
 
+#[test]
+fn test_unconsume() {
+    let mut buffer = Buffer::new_ringbuf();
+    let cap = buffer.capacity();
+    let data = (0 .. cap).map(|x| x as u8).collect::<Vec<_>>();
+
+    assert_eq!(buffer.copy_from_slice(&data), cap);
+    buffer.consume(cap - 2);
+    assert_eq!(buffer.unconsume(3), 3);
+    assert_eq!(buffer.buf(), &data[cap - 5 ..]);
+
+    // writing across the mirror boundary overwrites the oldest consumed bytes
+    buffer.consume(3);
+    assert_eq!(buffer.copy_from_slice(&[1, 2, 3]), 3);
+    assert_eq!(buffer.unconsume(cap), cap - 5);
+    assert_eq!(&buffer.buf()[..2], &data[3 .. 5]);
+    assert_eq!(&buffer.buf()[cap - 3 ..], &[1, 2, 3]);
+
+    buffer.clear();
+    assert_eq!(buffer.unconsume(1), 0);
+}
+
+#[test]
+fn test_mark_reset() {
+    let cap = Buffer::new_ringbuf().capacity();
+    let data = (0 .. cap * 2).map(|x| x as u8).collect::<Vec<_>>();
+    let mut reader = BufReader::new_ringbuf(&data[..]);
+
+    let mut buf = vec![0; cap];
+    reader.read_exact(&mut buf[.. cap - 2]).unwrap();
+    reader.mark(4);
+
+    // the marked bytes lie across the mirror boundary and must not be overwritten by the refill
+    reader.read_exact(&mut buf[.. 4]).unwrap();
+    assert_eq!(reader.fill_buf().unwrap().len(), cap - 4);
+    reader.reset().unwrap();
+    reader.read_exact(&mut buf[.. 6]).unwrap();
+    assert_eq!(&buf[.. 6], &data[cap - 2 .. cap + 4]);
+}
+
+#[test]
+fn test_shrink() {
+    let mut buffer = Buffer::new_ringbuf();
+    let cap = buffer.capacity();
+
+    buffer.reserve(cap * 3);
+    let big_cap = buffer.capacity();
+    assert!(big_cap > cap);
+
+    buffer.copy_from_slice(&[1, 2, 3, 4]);
+    buffer.consume(1);
+
+    // rounded up to the page size
+    buffer.shrink(1);
+    let min_cap = buffer.capacity();
+    assert!(min_cap <= cap);
+    assert_eq!(buffer.buf(), &[2, 3, 4]);
+
+    // no smaller allocation is possible
+    buffer.shrink(0);
+    assert_eq!(buffer.capacity(), min_cap);
+    assert_eq!(buffer.buf(), &[2, 3, 4]);
+}
+
+#[test]
+fn test_prepend_bytes() {
+    let mut buffer = Buffer::new_ringbuf();
+    let cap = buffer.capacity();
+    let data = (0 .. cap).map(|x| x as u8).collect::<Vec<_>>();
+
+    // in front of the data across the mirror boundary
+    assert_eq!(buffer.copy_from_slice(&data), cap);
+    buffer.consume(cap - 2);
+    buffer.copy_from_slice(&[1, 2]);
+    buffer.prepend_bytes(&[7, 8, 9]);
+    assert_eq!(buffer.buf(), &[7, 8, 9, data[cap - 2], data[cap - 1], 1, 2]);
+
+    // the full buffer has to grow
+    buffer.copy_from_slice(&data);
+    buffer.prepend_bytes(&[0xFF; 16]);
+    assert!(buffer.capacity() > cap);
+    assert_eq!(buffer.len(), cap + 16);
+    assert_eq!(&buffer.buf()[..16], &[0xFF; 16]);
+    assert_eq!(buffer.buf()[16], 7);
+}
+
 #[test]
 fn issue_8(){
     let source = vec![0u8; 4096*4];
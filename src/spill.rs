@@ -0,0 +1,135 @@
+// Copyright 2018 Austin Bonander <austin.bonander@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+//! Overflowing lookahead from a `BufReader` into a file, set up by `BufReader::set_spill()`.
+
+use std::cmp;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::mem::MaybeUninit;
+
+use TrustRead;
+
+/// Data read ahead from the underlying reader which didn't fit in the buffer, in stream order.
+///
+/// The pending data is `file[read_pos .. write_pos]`; it follows the data in the buffer and
+/// precedes anything not read from the underlying reader yet.
+#[derive(Debug)]
+pub struct Spill {
+    file: File,
+    /// The capacity the buffer is filled to before data is spilled.
+    threshold: usize,
+    read_pos: u64,
+    write_pos: u64,
+}
+
+impl Spill {
+    pub fn new(file: File, threshold: usize) -> Self {
+        Spill {
+            file,
+            threshold,
+            read_pos: 0,
+            write_pos: 0,
+        }
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// The number of bytes in the file which haven't been read back yet.
+    pub fn pending(&self) -> u64 {
+        self.write_pos - self.read_pos
+    }
+
+    /// Append up to `max` bytes from `inner` to the file, returning the number of bytes copied;
+    /// `0` if `inner` is at EOF.
+    ///
+    /// The pending data is extended after every chunk, so if reading from `inner` fails after
+    /// some data was copied, that count is returned instead of the error; the bytes were already
+    /// taken from `inner` and would be lost otherwise.
+    pub fn fill_from<R: Read + ?Sized>(&mut self, inner: &mut R, max: u64) -> io::Result<u64> {
+        self.file.seek(SeekFrom::Start(self.write_pos))?;
+
+        let mut chunk = [0u8; 8192];
+        let mut copied = 0;
+
+        while copied < max {
+            let len = cmp::min(max - copied, chunk.len() as u64) as usize;
+
+            let read = match inner.read(&mut chunk[..len]) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) if copied > 0 => break,
+                Err(e) => return Err(e),
+            };
+
+            self.file.write_all(&chunk[..read])?;
+            self.write_pos += read as u64;
+            copied += read as u64;
+        }
+
+        Ok(copied)
+    }
+
+    /// Read a copy of the pending data without consuming it.
+    pub fn copy_pending(&self) -> io::Result<Vec<u8>> {
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(self.read_pos))?;
+
+        let mut data = vec![0; self.pending() as usize];
+        file.read_exact(&mut data)?;
+        Ok(data)
+    }
+
+    /// Discard the pending data.
+    pub fn clear(&mut self) {
+        // the file is overwritten from the start next time
+        self.read_pos = 0;
+        self.write_pos = 0;
+    }
+}
+
+/// Reads back the pending data; returns `Ok(0)` once it's all been read.
+impl Read for Spill {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        self.read_with(out, |file, out| file.read(out))
+    }
+}
+
+unsafe impl TrustRead for Spill {
+    fn read_uninit(&mut self, out: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+        self.read_with(out, |file, out| file.read_uninit(out))
+    }
+}
+
+impl Spill {
+    fn read_with<T, F>(&mut self, out: &mut [T], read: F) -> io::Result<usize>
+    where F: FnOnce(&mut File, &mut [T]) -> io::Result<usize> {
+        let pending = self.pending();
+        if pending == 0 || out.is_empty() { return Ok(0); }
+
+        let len = cmp::min(out.len() as u64, pending) as usize;
+
+        self.file.seek(SeekFrom::Start(self.read_pos))?;
+        let read = read(&mut self.file, &mut out[..len])?;
+
+        if read == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                      "BufReader spill file was truncated"));
+        }
+
+        self.read_pos += read as u64;
+
+        if self.read_pos == self.write_pos {
+            self.clear();
+        }
+
+        Ok(read)
+    }
+}
@@ -0,0 +1,190 @@
+// Copyright 2018 Austin Bonander <austin.bonander@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+//! Reading into uninitialized memory, used by `Buffer::read_from_trusted()` and
+//! `BufReader::set_trusted_reads()`.
+
+use std::fs::File;
+use std::io::{self, BufRead, Read};
+use std::mem::MaybeUninit;
+use std::net::TcpStream;
+use std::process::{ChildStderr, ChildStdout};
+use std::{cmp, ptr};
+
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use policy::ReaderPolicy;
+use BufReader;
+
+/// A reader which can read into uninitialized memory.
+///
+/// `Buffer` and `BufReader` zero memory the first time it is read into because `Read::read()`
+/// must be given initialized memory. With `Buffer::read_from_trusted()`,
+/// `BufReader::read_into_buf_trusted()` and `BufReader::set_trusted_reads()`, readers
+/// implementing this trait read into the uninitialized memory with `.read_uninit()` instead.
+///
+/// On Unix, files, sockets, pipes and `ReadAt` read into it directly with `read()` or
+/// `pread()`. On other platforms, and for `Stdin` and `io::Chain` everywhere, the memory is
+/// zeroed first as `Read::read()` needs it to be, so they gain nothing.
+///
+/// ### Safety
+/// `.read_uninit()` must behave like `Read::read()`, except that it must not read from `out` or
+/// write uninitialized values to it, and the count it returns must be no more than the number
+/// of bytes at the start of `out` it actually initialized.
+pub unsafe trait TrustRead: Read {
+    /// Read into `out`, which may be uninitialized, returning the number of bytes initialized
+    /// at the start of it; otherwise like `Read::read()`.
+    fn read_uninit(&mut self, out: &mut [MaybeUninit<u8>]) -> io::Result<usize>;
+}
+
+/// Copy as much of `src` to the start of `out` as fits, returning the number of bytes copied.
+pub fn copy_uninit(src: &[u8], out: &mut [MaybeUninit<u8>]) -> usize {
+    let len = cmp::min(src.len(), out.len());
+    unsafe { ptr::copy_nonoverlapping(src.as_ptr(), out.as_mut_ptr() as *mut u8, len); }
+    len
+}
+
+/// Zero `out` and read into it with `Read::read()`.
+pub fn read_zeroed<R: Read + ?Sized>(rdr: &mut R, out: &mut [MaybeUninit<u8>])
+-> io::Result<usize> {
+    unsafe {
+        ptr::write_bytes(out.as_mut_ptr(), 0, out.len());
+        rdr.read(&mut *(out as *mut [MaybeUninit<u8>] as *mut [u8]))
+    }
+}
+
+/// Read into `out` with `read()` on `fd`.
+#[cfg(unix)]
+pub fn read_fd(fd: RawFd, out: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+    // `read()` fails with `EINVAL` for lengths over `isize::MAX` on some platforms
+    let len = cmp::min(out.len(), isize::MAX as usize);
+    let read = unsafe { ::libc::read(fd, out.as_mut_ptr() as *mut _, len) };
+
+    if read < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(read as usize)
+}
+
+unsafe impl TrustRead for &[u8] {
+    fn read_uninit(&mut self, out: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+        let read = copy_uninit(self, out);
+        *self = &self[read..];
+        Ok(read)
+    }
+}
+
+unsafe impl<T: AsRef<[u8]>> TrustRead for io::Cursor<T> {
+    fn read_uninit(&mut self, out: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+        let read = copy_uninit(self.fill_buf()?, out);
+        self.consume(read);
+        Ok(read)
+    }
+}
+
+unsafe impl TrustRead for io::Empty {
+    fn read_uninit(&mut self, _out: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+        Ok(0)
+    }
+}
+
+unsafe impl TrustRead for io::Repeat {
+    fn read_uninit(&mut self, out: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+        // the byte isn't accessible otherwise
+        let mut byte = [0];
+        self.read_exact(&mut byte)?;
+
+        for slot in out.iter_mut() {
+            *slot = MaybeUninit::new(byte[0]);
+        }
+
+        Ok(out.len())
+    }
+}
+
+// `Stdin` has its own buffer, so reading the file descriptor directly would skip its contents
+unsafe impl TrustRead for io::Stdin {
+    fn read_uninit(&mut self, out: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+        read_zeroed(self, out)
+    }
+}
+
+unsafe impl TrustRead for io::StdinLock<'_> {
+    fn read_uninit(&mut self, out: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+        read_zeroed(self, out)
+    }
+}
+
+macro_rules! impl_trust_read_fd {
+    ($($ty:ty),*) => {$(
+        unsafe impl TrustRead for $ty {
+            #[cfg(unix)]
+            fn read_uninit(&mut self, out: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+                read_fd(self.as_raw_fd(), out)
+            }
+
+            #[cfg(not(unix))]
+            fn read_uninit(&mut self, out: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+                read_zeroed(self, out)
+            }
+        }
+    )*}
+}
+
+impl_trust_read_fd!(File, &File, TcpStream, &TcpStream, ChildStdout, ChildStderr);
+
+#[cfg(unix)]
+impl_trust_read_fd!(::std::os::unix::net::UnixStream);
+
+unsafe impl<R: TrustRead> TrustRead for io::Take<R> {
+    fn read_uninit(&mut self, out: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+        let limit = self.limit();
+        let len = cmp::min(limit, out.len() as u64) as usize;
+        if len == 0 { return Ok(0); }
+
+        let read = self.get_mut().read_uninit(&mut out[..len])?;
+        self.set_limit(limit - read as u64);
+        Ok(read)
+    }
+}
+
+// which of the readers is next isn't exposed
+unsafe impl<A: TrustRead, B: TrustRead> TrustRead for io::Chain<A, B> {
+    fn read_uninit(&mut self, out: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+        read_zeroed(self, out)
+    }
+}
+
+unsafe impl<R: TrustRead + ?Sized> TrustRead for Box<R> {
+    fn read_uninit(&mut self, out: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+        (**self).read_uninit(out)
+    }
+}
+
+unsafe impl<R: TrustRead + ?Sized> TrustRead for &mut R {
+    fn read_uninit(&mut self, out: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+        (**self).read_uninit(out)
+    }
+}
+
+unsafe impl<R: TrustRead + ?Sized, P: ReaderPolicy> TrustRead for BufReader<R, P> {
+    fn read_uninit(&mut self, out: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+        // like `read()`, with a large read bypassing the buffer
+        if self.buf.is_empty() && self.may_bypass(out.len()) {
+            let read = self.inner.read_uninit(out)?;
+            self.count_bypassed(read);
+            if !out.is_empty() { self.check_eof(read); }
+            return Ok(read);
+        }
+
+        let read = copy_uninit(self.fill_buf()?, out);
+        self.consume(read);
+        Ok(read)
+    }
+}